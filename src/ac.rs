@@ -0,0 +1,211 @@
+//! Analog Comparator
+//!
+//! The analog comparator compares the positive input `AIN0` (`PE6`) with a negative
+//! input and sets its output if the positive one is higher.  Instead of `AIN0`, the
+//! internal 1.1V bandgap reference can be used as the positive input.  The negative
+//! input is `AIN1` by default, but it can also be routed to one of the ADC pins.
+//!
+//! This is a lot faster than a full ADC conversion, for example for zero-cross
+//! detection.
+//!
+//! # Example
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut portf = dp.PORTF.split();
+//!
+//! let mut ac = ac::AnalogComparator::new(dp.AC);
+//! let mut pf0 = portf.pf0.into_floating_input(&mut portf.ddr);
+//!
+//! // Compare AIN0 against PF0
+//! ac.set_negative_pin(&mut pf0);
+//!
+//! if ac.output() {
+//!     // AIN0 is higher than PF0
+//! }
+//! ```
+//!
+//! # Interrupts
+//! The `ANALOG_COMP` interrupt can be triggered on edges of the comparator output.
+//! The flag is cleared automatically when the interrupt handler runs:
+//!
+//! ```
+//! ac.enable_interrupt(ac::Edge::Rising);
+//!
+//! interrupt!(ANALOG_COMP, analog_comp_isr);
+//! fn analog_comp_isr() {
+//!     // Output went high
+//! }
+//! ```
+use adc;
+use atmega32u4;
+use hal;
+
+// ACSR
+const ACD: u8 = 1 << 7;
+const ACBG: u8 = 1 << 6;
+const ACO: u8 = 1 << 5;
+const ACI: u8 = 1 << 4;
+const ACIE: u8 = 1 << 3;
+const ACIS_MASK: u8 = 0b11;
+
+// ADC registers
+const ADEN: u8 = 1 << 7;
+const ACME: u8 = 1 << 6;
+const MUX_MASK: u8 = 0b1_1111;
+const MUX5: u8 = 1 << 5;
+
+/// Comparator output edge triggering the interrupt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    /// Trigger on both edges
+    Toggle,
+    /// Trigger when the output goes low
+    Falling,
+    /// Trigger when the output goes high
+    Rising,
+}
+
+impl Edge {
+    /// `ACIS1:0` bits for this edge
+    fn bits(self) -> u8 {
+        match self {
+            Edge::Toggle => 0b00,
+            Edge::Falling => 0b10,
+            Edge::Rising => 0b11,
+        }
+    }
+}
+
+/// Positive comparator input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositiveInput {
+    /// `AIN0` (`PE6`)
+    Ain0,
+    /// Internal 1.1V bandgap reference
+    Bandgap,
+}
+
+/// Analog Comparator
+pub struct AnalogComparator {
+    ac: atmega32u4::AC,
+}
+
+impl AnalogComparator {
+    /// Enable the analog comparator
+    ///
+    /// Inputs are `AIN0` and `AIN1`, the interrupt is disabled.
+    pub fn new(ac: atmega32u4::AC) -> AnalogComparator {
+        // Clearing ACIE first prevents a spurious interrupt when changing ACD
+        ac.acsr.write(|w| unsafe { w.bits(0) });
+        ac.acsr.write(|w| unsafe { w.bits(ACI) });
+
+        AnalogComparator { ac: ac }
+    }
+
+    /// Access the raw `AC` registers
+    ///
+    /// # Safety
+    /// The interrupt must only be enabled through `enable_interrupt()`, and the inputs
+    /// only be changed through the `set_*` methods, which also configure the ADC.
+    pub unsafe fn registers(&mut self) -> &atmega32u4::AC {
+        &self.ac
+    }
+
+    /// Disable the analog comparator and release the raw peripheral
+    pub fn free(self) -> atmega32u4::AC {
+        self.ac.acsr.write(|w| unsafe { w.bits(0) });
+        self.ac.acsr.write(|w| unsafe { w.bits(ACD | ACI) });
+        unsafe {
+            (*atmega32u4::ADC::ptr())
+                .adcsr_b
+                .modify(|r, w| w.bits(r.bits() & !ACME))
+        }
+
+        self.ac
+    }
+
+    /// Current comparator output
+    ///
+    /// `true` if the positive input is higher than the negative one.
+    pub fn output(&self) -> bool {
+        self.ac.acsr.read().bits() & ACO != 0
+    }
+
+    /// Select the positive input
+    pub fn set_positive_input(&mut self, input: PositiveInput) {
+        self.ac.acsr.modify(|r, w| unsafe {
+            match input {
+                PositiveInput::Ain0 => w.bits(r.bits() & !(ACBG | ACI)),
+                PositiveInput::Bandgap => w.bits((r.bits() & !ACI) | ACBG),
+            }
+        });
+    }
+
+    /// Use `AIN1` as the negative input
+    pub fn set_negative_ain1(&mut self) {
+        unsafe {
+            (*atmega32u4::ADC::ptr())
+                .adcsr_b
+                .modify(|r, w| w.bits(r.bits() & !ACME))
+        }
+    }
+
+    /// Use an ADC pin as the negative input
+    ///
+    /// *Note*: The comparator uses the ADC multiplexer for this, which is only possible
+    /// while the ADC is disabled.  This call disables the ADC, so it can't be used until
+    /// `set_negative_ain1()` is called and the ADC is initialized again.
+    pub fn set_negative_pin<PIN>(&mut self, _pin: &mut PIN)
+    where
+        PIN: hal::adc::Channel<adc::Adc, ID = u8>,
+    {
+        let id = PIN::channel();
+        let adc = unsafe { &*atmega32u4::ADC::ptr() };
+
+        adc.adcsr_a.modify(|r, w| unsafe { w.bits(r.bits() & !ADEN) });
+        adc.admux
+            .modify(|r, w| unsafe { w.bits((r.bits() & !MUX_MASK) | (id & MUX_MASK)) });
+        adc.adcsr_b.modify(|r, w| unsafe {
+            if id & MUX5 != 0 {
+                w.bits(r.bits() | MUX5 | ACME)
+            } else {
+                w.bits((r.bits() & !MUX5) | ACME)
+            }
+        });
+    }
+
+    /// Enable the `ANALOG_COMP` interrupt on the given edge
+    pub fn enable_interrupt(&mut self, edge: Edge) {
+        // Changing ACIS may trigger an interrupt, so disable it first and clear the
+        // flag afterwards
+        self.disable_interrupt();
+        self.ac.acsr.modify(|r, w| unsafe {
+            w.bits((r.bits() & !(ACIS_MASK | ACI)) | edge.bits())
+        });
+        self.clear_interrupt();
+        self.ac
+            .acsr
+            .modify(|r, w| unsafe { w.bits((r.bits() & !ACI) | ACIE) });
+    }
+
+    /// Disable the `ANALOG_COMP` interrupt
+    pub fn disable_interrupt(&mut self) {
+        self.ac
+            .acsr
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(ACIE | ACI)) });
+    }
+
+    /// Whether the interrupt flag is set
+    ///
+    /// The flag is set on the selected edge even if the interrupt is disabled, so
+    /// this can also be used for polling.
+    pub fn is_pending(&self) -> bool {
+        self.ac.acsr.read().bits() & ACI != 0
+    }
+
+    /// Clear the interrupt flag
+    pub fn clear_interrupt(&mut self) {
+        // The flag is cleared by writing a one
+        self.ac.acsr.modify(|r, w| unsafe { w.bits(r.bits() | ACI) });
+    }
+}
@@ -0,0 +1,813 @@
+//! Analog to Digital Converter
+//!
+//! The ADC can sample the analog capable pins of `PORTF`:
+//!
+//! | Channel | Port                | Pin   |
+//! |---------|---------------------|-------|
+//! | `ADC0`  | [atmega32u4::PORTF] | `PF0` |
+//! | `ADC1`  | [atmega32u4::PORTF] | `PF1` |
+//! | `ADC4`  | [atmega32u4::PORTF] | `PF4` |
+//! | `ADC5`  | [atmega32u4::PORTF] | `PF5` |
+//! | `ADC6`  | [atmega32u4::PORTF] | `PF6` |
+//! | `ADC7`  | [atmega32u4::PORTF] | `PF7` |
+//!
+//! A pin can only be sampled while it is an input.  The pin is borrowed mutably
+//! for the duration of a conversion, so it can't be driven as an output at the
+//! same time.  [embedded_hal::adc::OneShot] is implemented as well, so the ADC
+//! can be used with generic drivers.
+//!
+//! ## 8-Bit Reads
+//! If 8 bits of resolution are enough, `read8()` is faster when the ADC is set to
+//! left adjust its result using `set_adjust(Adjust::Left)`.  Only `ADCH` needs to be
+//! read then.
+//!
+//! ## Averaging and Oversampling
+//! `analog_read_averaged()` runs several conversions back-to-back and returns their
+//! mean, which removes most of the noise of single reads.  `analog_read_oversampled()`
+//! goes further and takes `4^n` samples to gain `n` extra bits of resolution, which
+//! works best if the signal has some noise on it.
+//!
+//! Each conversion takes 13 ADC clock cycles, 104µs with `Div128` at 16MHz:
+//!
+//! | Samples | Time at 16MHz | Use                       |
+//! |---------|---------------|---------------------------|
+//! | 4       | 0.4ms         | Slightly noisy signals    |
+//! | 16      | 1.7ms         | Potentiometers, sensors   |
+//! | 64      | 6.7ms         | 3 extra bits (oversample) |
+//! | 256     | 26.6ms        | 4 extra bits (oversample) |
+//!
+//! ```
+//! let smooth: u16 = adc.analog_read_averaged(&mut pf7, 16);
+//! // 12-bit result, 0..4095
+//! let fine: u16 = adc.analog_read_oversampled(&mut pf7, 2);
+//! ```
+//!
+//! ## Free-Running Mode
+//! For continuous sampling, `start_free_running()` starts a new conversion as soon as
+//! the last one finished.  Each conversion takes 13 ADC clock cycles, so the sample
+//! rate is `F_CPU / prescaler / 13`.  At 16MHz:
+//!
+//! | Prescaler | Sample Rate |
+//! |-----------|-------------|
+//! | `Div128`  | 9.6kHz      |
+//! | `Div64`   | 19.2kHz     |
+//! | `Div32`   | 38.5kHz     |
+//! | `Div16`   | 76.9kHz     |
+//!
+//! Prescalers below `Div128` run the ADC clock above 200kHz at 16MHz, which reduces
+//! the accuracy.
+//!
+//! ```
+//! adc.start_free_running(&mut pf7);
+//!
+//! interrupt!(ADC, adc_isr);
+//! fn adc_isr() {
+//!     let sample = atmega32u4_hal::adc::adc_isr();
+//!     // Process the sample
+//! }
+//! ```
+//!
+//! ## Triggered Sampling
+//! For a fixed sample rate, `start_triggered()` starts each conversion on a hardware
+//! event instead of right after the last one.  Results are handled the same way as in
+//! free-running mode.  The following sources are available:
+//!
+//! | Trigger                  | Event                               |
+//! |--------------------------|-------------------------------------|
+//! | `FreeRunning`            | End of the last conversion          |
+//! | `AnalogComparator`       | Analog comparator output toggled    |
+//! | `ExternalInterrupt0`     | `INT0` flag set                     |
+//! | `Timer0CompareA`         | Timer0 compare match A              |
+//! | `Timer0Overflow`         | Timer0 overflow                     |
+//! | `Timer1CompareB`         | Timer1 compare match B              |
+//! | `Timer1Overflow`         | Timer1 overflow                     |
+//! | `Timer1Capture`          | Timer1 input capture                |
+//! | `Timer4Overflow`         | Timer4 overflow                     |
+//! | `Timer4CompareA`         | Timer4 compare match A              |
+//! | `Timer4CompareB`         | Timer4 compare match B              |
+//! | `Timer4CompareD`         | Timer4 compare match D              |
+//!
+//! A conversion starts on the rising edge of the event's interrupt flag.  The timer
+//! has to be configured and running, and the flag has to be cleared again before the
+//! next event, either by enabling the timer's interrupt or by clearing it manually in
+//! the `ADC` interrupt handler.  Otherwise only the first event starts a conversion.
+//!
+//! ```
+//! // Timer1 running in a mode where OCR1B is reached once per sample period
+//! adc.start_triggered(&mut pf7, adc::TriggerSource::Timer1CompareB);
+//!
+//! interrupt!(ADC, adc_isr);
+//! fn adc_isr() {
+//!     let sample = atmega32u4_hal::adc::adc_isr();
+//!     // Clear OCF1B for the next trigger
+//!     unsafe { (*atmega32u4::TIMER1::ptr()).tifr.write(|w| w.bits(1 << 2)) };
+//! }
+//! ```
+//!
+//! ## Reading Several Channels
+//! `read_channels()` reads a list of [AdcChannel]s in turn, for example both axes of
+//! a joystick.  After switching the multiplexer, the sample-and-hold capacitor still
+//! holds a bit of the previous channel, which bleeds into the next reading.  So each
+//! channel is converted twice and the first result is discarded, unless it is the
+//! same channel as the one before.  This takes 26 ADC clock cycles per channel,
+//! 208µs with `Div128` at 16MHz.
+//!
+//! ```
+//! let mut values = [0u16; 3];
+//! adc.read_channels(
+//!     &[adc::AdcChannel::Adc4, adc::AdcChannel::Adc5, adc::AdcChannel::Bandgap],
+//!     &mut values,
+//! );
+//! let (x, y) = (values[0], values[1]);
+//! ```
+//!
+//! Unlike the other read methods, the channels don't borrow their pins.  Make sure the
+//! pins of `PORTF` which are read are configured as inputs.
+//!
+//! ## Temperature Sensor
+//! The internal temperature sensor is connected to ADC channel 8.  It can be read
+//! using `read_temperature()` or, for custom calibration, `read_temperature_raw()`.
+//!
+//! ## Supply Voltage
+//! `read_vcc_millivolts()` measures the supply voltage using the internal bandgap
+//! reference, see also the [power] module.
+//!
+//! ## One-Shot Reads
+//! For infrequent readings, [read_once] enables the ADC, reads a pin and disables it
+//! again, without an [Adc] which has to be kept around.  This costs some extra time
+//! for each reading.
+//!
+//! # Example
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//!
+//! let mut adc = atmega32u4_hal::adc::Adc::new(dp.ADC, adc::Reference::AVcc);
+//!
+//! let mut portf = dp.PORTF.split();
+//! let mut pf7 = portf.pf7;
+//!
+//! // Read a 10-bit value
+//! let value: u16 = adc.analog_read(&mut pf7);
+//! ```
+use atmega32u4;
+use clock;
+use delay;
+use global::Global;
+use hal;
+use nb;
+use port;
+
+const ADEN: u8 = 1 << 7;
+const ADSC: u8 = 1 << 6;
+const ADATE: u8 = 1 << 5;
+const ADIF: u8 = 1 << 4;
+const ADIE: u8 = 1 << 3;
+const ADPS_MASK: u8 = 0b111;
+
+const REFS_MASK: u8 = 0b11 << 6;
+const ADLAR: u8 = 1 << 5;
+const MUX_MASK: u8 = 0b1_1111;
+const MUX5: u8 = 1 << 5;
+const ADTS_MASK: u8 = 0b1111;
+
+/// Channel ID of the internal temperature sensor (`MUX5:0 = 0b100111`)
+const TEMPERATURE_CHANNEL: u8 = 0b10_0111;
+
+/// Channel ID of the internal 1.1V bandgap reference (`MUX5:0 = 0b011110`)
+const BANDGAP_CHANNEL: u8 = 0b01_1110;
+
+/// Typical bandgap voltage in millivolts
+const BANDGAP_MV: u32 = 1100;
+
+/// Raw reading of the temperature sensor at -40°C, according to the datasheet
+const TEMPERATURE_RAW_MIN: i32 = 0x010D;
+/// Raw reading of the temperature sensor at +85°C, according to the datasheet
+const TEMPERATURE_RAW_MAX: i32 = 0x01B1;
+
+/// Voltage reference of the ADC
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reference {
+    /// External reference voltage at the `AREF` pin
+    Aref,
+    /// `AVCC` with external capacitor at the `AREF` pin
+    AVcc,
+    /// Internal 2.56V reference with external capacitor at the `AREF` pin
+    Internal2_56,
+}
+
+impl Reference {
+    fn bits(self) -> u8 {
+        match self {
+            Reference::Aref => 0b00 << 6,
+            Reference::AVcc => 0b01 << 6,
+            Reference::Internal2_56 => 0b11 << 6,
+        }
+    }
+}
+
+/// ADC clock prescaler
+///
+/// For full 10-bit accuracy, the ADC clock should be between 50kHz and 200kHz.
+/// At 16MHz this means `Div128` should be used.  `for_clock()` picks the prescaler for
+/// the CPU frequency.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prescaler {
+    /// CPU clock / 2
+    Div2,
+    /// CPU clock / 4
+    Div4,
+    /// CPU clock / 8
+    Div8,
+    /// CPU clock / 16
+    Div16,
+    /// CPU clock / 32
+    Div32,
+    /// CPU clock / 64
+    Div64,
+    /// CPU clock / 128
+    Div128,
+}
+
+impl Prescaler {
+    fn bits(self) -> u8 {
+        match self {
+            Prescaler::Div2 => 0b001,
+            Prescaler::Div4 => 0b010,
+            Prescaler::Div8 => 0b011,
+            Prescaler::Div16 => 0b100,
+            Prescaler::Div32 => 0b101,
+            Prescaler::Div64 => 0b110,
+            Prescaler::Div128 => 0b111,
+        }
+    }
+
+    /// Smallest prescaler which keeps the ADC clock at or below 200kHz
+    ///
+    /// ```
+    /// adc.set_prescaler(adc::Prescaler::for_clock::<delay::Speed>());
+    /// ```
+    pub fn for_clock<SPEED: clock::Clock>() -> Prescaler {
+        Prescaler::for_frequency(SPEED::FREQ)
+    }
+
+    fn for_frequency(freq: u32) -> Prescaler {
+        match (freq + 199_999) / 200_000 {
+            0..=2 => Prescaler::Div2,
+            3..=4 => Prescaler::Div4,
+            5..=8 => Prescaler::Div8,
+            9..=16 => Prescaler::Div16,
+            17..=32 => Prescaler::Div32,
+            33..=64 => Prescaler::Div64,
+            _ => Prescaler::Div128,
+        }
+    }
+}
+
+/// Auto trigger source (`ADTS3:0`)
+///
+/// See the [module documentation](index.html#triggered-sampling).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerSource {
+    /// Start the next conversion as soon as the last one finished
+    FreeRunning,
+    /// Analog comparator
+    AnalogComparator,
+    /// External interrupt `INT0`
+    ExternalInterrupt0,
+    /// Timer0 compare match A
+    Timer0CompareA,
+    /// Timer0 overflow
+    Timer0Overflow,
+    /// Timer1 compare match B
+    Timer1CompareB,
+    /// Timer1 overflow
+    Timer1Overflow,
+    /// Timer1 input capture
+    Timer1Capture,
+    /// Timer4 overflow
+    Timer4Overflow,
+    /// Timer4 compare match A
+    Timer4CompareA,
+    /// Timer4 compare match B
+    Timer4CompareB,
+    /// Timer4 compare match D
+    Timer4CompareD,
+}
+
+impl TriggerSource {
+    fn bits(self) -> u8 {
+        match self {
+            TriggerSource::FreeRunning => 0b0000,
+            TriggerSource::AnalogComparator => 0b0001,
+            TriggerSource::ExternalInterrupt0 => 0b0010,
+            TriggerSource::Timer0CompareA => 0b0011,
+            TriggerSource::Timer0Overflow => 0b0100,
+            TriggerSource::Timer1CompareB => 0b0101,
+            TriggerSource::Timer1Overflow => 0b0110,
+            TriggerSource::Timer1Capture => 0b0111,
+            TriggerSource::Timer4Overflow => 0b1000,
+            TriggerSource::Timer4CompareA => 0b1001,
+            TriggerSource::Timer4CompareB => 0b1010,
+            TriggerSource::Timer4CompareD => 0b1011,
+        }
+    }
+}
+
+/// Latest result in free-running mode
+static LATEST: Global<u16> = Global::new();
+
+/// Alignment of the conversion result in `ADCH`/`ADCL`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Adjust {
+    /// 10-bit result in `ADCH[1:0]` and `ADCL` (default)
+    Right,
+    /// Upper 8 bits of the result in `ADCH`, so `read8()` only needs one register
+    Left,
+}
+
+/// ADC input channel for [Adc::read_channels]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdcChannel {
+    /// `ADC0` (`PF0`)
+    Adc0,
+    /// `ADC1` (`PF1`)
+    Adc1,
+    /// `ADC4` (`PF4`)
+    Adc4,
+    /// `ADC5` (`PF5`)
+    Adc5,
+    /// `ADC6` (`PF6`)
+    Adc6,
+    /// `ADC7` (`PF7`)
+    Adc7,
+    /// Internal 1.1V bandgap reference
+    Bandgap,
+    /// Ground, reads 0
+    Ground,
+    /// Internal temperature sensor
+    ///
+    /// Only meaningful against the 2.56V reference, see `Adc::read_temperature()`.
+    Temperature,
+}
+
+impl AdcChannel {
+    /// `MUX5:0` value for this channel
+    fn id(self) -> u8 {
+        match self {
+            AdcChannel::Adc0 => 0b00_0000,
+            AdcChannel::Adc1 => 0b00_0001,
+            AdcChannel::Adc4 => 0b00_0100,
+            AdcChannel::Adc5 => 0b00_0101,
+            AdcChannel::Adc6 => 0b00_0110,
+            AdcChannel::Adc7 => 0b00_0111,
+            AdcChannel::Bandgap => BANDGAP_CHANNEL,
+            AdcChannel::Ground => 0b01_1111,
+            AdcChannel::Temperature => TEMPERATURE_CHANNEL,
+        }
+    }
+}
+
+/// Analog to Digital Converter
+pub struct Adc {
+    adc: atmega32u4::ADC,
+    reference: Reference,
+    adjust: Adjust,
+}
+
+impl Adc {
+    /// Initialize the ADC
+    ///
+    /// The prescaler is picked for the CPU speed selected using the `mhz*` features
+    /// (see `Prescaler::for_clock()`), `Div128` at 16MHz.  Use `set_prescaler` to
+    /// change it.
+    pub fn new(adc: atmega32u4::ADC, reference: Reference) -> Adc {
+        let prescaler = Prescaler::for_clock::<delay::Speed>();
+        adc.adcsr_a.write(|w| unsafe { w.bits(ADEN | prescaler.bits()) });
+
+        let mut adc = Adc {
+            adc: adc,
+            reference: reference,
+            adjust: Adjust::Right,
+        };
+        adc.set_reference(reference);
+        adc
+    }
+
+    /// Access the raw `ADC` registers
+    ///
+    /// # Safety
+    /// No conversion must be running.  The reference and the result alignment are
+    /// cached by this driver, so `ADMUX` must only change its channel bits.
+    pub unsafe fn registers(&mut self) -> &atmega32u4::ADC {
+        &self.adc
+    }
+
+    /// Disable the ADC and release the raw peripheral
+    pub fn free(self) -> atmega32u4::ADC {
+        self.adc.adcsr_a.write(|w| unsafe { w.bits(ADIF) });
+        self.adc
+    }
+
+    /// Change the voltage reference
+    pub fn set_reference(&mut self, reference: Reference) {
+        self.reference = reference;
+        self.adc
+            .admux
+            .modify(|r, w| unsafe { w.bits((r.bits() & !REFS_MASK) | reference.bits()) });
+    }
+
+    /// Change the alignment of the conversion result
+    ///
+    /// With `Adjust::Left`, `read8()` only reads `ADCH`, which is faster.  The
+    /// 10-bit reads keep working in both modes.
+    ///
+    /// *Note*: Only switch between conversions.  The result of a conversion which was
+    /// running while switching has to be discarded.
+    pub fn set_adjust(&mut self, adjust: Adjust) {
+        self.adjust = adjust;
+        self.adc.admux.modify(|r, w| unsafe {
+            match adjust {
+                Adjust::Right => w.bits(r.bits() & !ADLAR),
+                Adjust::Left => w.bits(r.bits() | ADLAR),
+            }
+        });
+    }
+
+    /// Change the ADC clock prescaler
+    pub fn set_prescaler(&mut self, prescaler: Prescaler) {
+        self.adc
+            .adcsr_a
+            .modify(|r, w| unsafe { w.bits((r.bits() & !(ADPS_MASK | ADIF)) | prescaler.bits()) });
+    }
+
+    /// Read the analog value of a pin
+    ///
+    /// Blocks until the conversion is complete and returns the 10-bit result.
+    pub fn analog_read<PIN>(&mut self, _pin: &mut PIN) -> u16
+    where
+        PIN: hal::adc::Channel<Adc, ID = u8>,
+    {
+        self.convert(PIN::channel())
+    }
+
+    /// Read the mean of `samples` conversions of a pin
+    ///
+    /// The sum is accumulated in a `u32`, so it can't overflow.  The result is rounded
+    /// to the nearest integer.  `samples` of 0 is treated as 1.
+    pub fn analog_read_averaged<PIN>(&mut self, _pin: &mut PIN, samples: u16) -> u16
+    where
+        PIN: hal::adc::Channel<Adc, ID = u8>,
+    {
+        let samples = if samples == 0 { 1 } else { samples as u32 };
+
+        let mut sum: u32 = 0;
+        for _ in 0..samples {
+            sum += self.convert(PIN::channel()) as u32;
+        }
+        ((sum + samples / 2) / samples) as u16
+    }
+
+    /// Read a pin with `extra_bits` more resolution using oversampling
+    ///
+    /// Takes `4^extra_bits` samples and decimates their sum, so the result has
+    /// `10 + extra_bits` bits.  `extra_bits` is limited to 6, which gives a 16-bit
+    /// result from 4096 samples.
+    pub fn analog_read_oversampled<PIN>(&mut self, _pin: &mut PIN, extra_bits: u8) -> u16
+    where
+        PIN: hal::adc::Channel<Adc, ID = u8>,
+    {
+        let extra_bits = if extra_bits > 6 { 6 } else { extra_bits };
+
+        let mut sum: u32 = 0;
+        for _ in 0..(1u32 << (2 * extra_bits)) {
+            sum += self.convert(PIN::channel()) as u32;
+        }
+        (sum >> extra_bits) as u16
+    }
+
+    /// Read the upper 8 bits of the analog value of a pin
+    ///
+    /// If the ADC is set to `Adjust::Left`, only `ADCH` is read.  Otherwise, the
+    /// 10-bit result is read and shifted.
+    pub fn read8<PIN>(&mut self, _pin: &mut PIN) -> u8
+    where
+        PIN: hal::adc::Channel<Adc, ID = u8>,
+    {
+        self.start_conversion(PIN::channel());
+
+        match self.adjust {
+            Adjust::Left => self.adc.adc_h.read().bits(),
+            Adjust::Right => (self.read_result() >> 2) as u8,
+        }
+    }
+
+    /// Read several channels, one after the other
+    ///
+    /// `values[i]` is set to the 10-bit result of `channels[i]`.  If one of the slices
+    /// is longer, its remaining entries are ignored.  The first conversion after each
+    /// channel switch is discarded, see the
+    /// [module documentation](index.html#reading-several-channels).
+    pub fn read_channels(&mut self, channels: &[AdcChannel], values: &mut [u16]) {
+        let mut previous = None;
+
+        for (&channel, value) in channels.iter().zip(values.iter_mut()) {
+            let id = channel.id();
+            if previous != Some(id) {
+                self.convert(id);
+            }
+            *value = self.convert(id);
+            previous = Some(id);
+        }
+    }
+
+    /// Start converting a pin continuously
+    ///
+    /// Conversions run back-to-back and the `ADC` interrupt fires after each one.
+    /// The interrupt handler has to call [adc_isr].  The latest result can then be
+    /// read using `latest()`.
+    ///
+    /// *Note*: Don't use the other read methods until `stop()` was called.
+    pub fn start_free_running<PIN>(&mut self, pin: &mut PIN)
+    where
+        PIN: hal::adc::Channel<Adc, ID = u8>,
+    {
+        self.start_triggered(pin, TriggerSource::FreeRunning);
+    }
+
+    /// Start converting a pin on each trigger event
+    ///
+    /// Like `start_free_running()`, but each conversion is started by `trigger`.  See
+    /// the [module documentation](index.html#triggered-sampling) for the requirements.
+    ///
+    /// *Note*: Don't use the other read methods until `stop()` was called.
+    pub fn start_triggered<PIN>(&mut self, _pin: &mut PIN, trigger: TriggerSource)
+    where
+        PIN: hal::adc::Channel<Adc, ID = u8>,
+    {
+        self.select_channel(PIN::channel());
+        self.set_trigger(trigger);
+    }
+
+    /// Change the trigger source and enable auto triggering
+    ///
+    /// Sets `ADATE` and enables the `ADC` interrupt.  This can be used to switch the
+    /// source of a running `start_triggered()`.  In free-running mode, the first
+    /// conversion is started right away.
+    pub fn set_trigger(&mut self, trigger: TriggerSource) {
+        self.adc
+            .adcsr_b
+            .modify(|r, w| unsafe { w.bits((r.bits() & !ADTS_MASK) | trigger.bits()) });
+
+        let start = if trigger == TriggerSource::FreeRunning {
+            ADSC
+        } else {
+            0
+        };
+        self.adc
+            .adcsr_a
+            .modify(|r, w| unsafe { w.bits(r.bits() | ADIF | ADATE | ADIE | start) });
+    }
+
+    /// Stop free-running or triggered conversions
+    pub fn stop(&mut self) {
+        self.adc
+            .adcsr_a
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(ADATE | ADIE | ADIF)) });
+
+        // Wait for a running conversion to finish
+        while self.adc.adcsr_a.read().bits() & ADSC != 0 {}
+        self.adc
+            .adcsr_a
+            .modify(|r, w| unsafe { w.bits(r.bits() | ADIF) });
+    }
+
+    /// Latest result in free-running mode
+    ///
+    /// Returns `None` if no conversion has finished yet.
+    pub fn latest(&self) -> Option<u16> {
+        LATEST.get_copy()
+    }
+
+    /// Read the raw value of the internal temperature sensor
+    ///
+    /// The sensor is measured against the internal 2.56V reference.  Afterwards
+    /// the previously configured reference is restored.
+    pub fn read_temperature_raw(&mut self) -> u16 {
+        let reference = self.reference;
+        self.set_reference(Reference::Internal2_56);
+
+        // The first conversion after switching the reference is inaccurate
+        self.convert(TEMPERATURE_CHANNEL);
+        let value = self.convert(TEMPERATURE_CHANNEL);
+
+        self.set_reference(reference);
+        value
+    }
+
+    /// Read the internal temperature sensor in degrees celsius
+    ///
+    /// The value is computed using the typical characteristic from the datasheet
+    /// (-40°C to +85°C) and is **not** calibrated.  The actual offset varies from
+    /// chip to chip and can easily be off by +/-10°C.  For more accurate results,
+    /// measure the raw value at a known temperature using `read_temperature_raw()`
+    /// and apply a per-chip offset constant.
+    pub fn read_temperature(&mut self) -> i16 {
+        let raw = self.read_temperature_raw() as i32;
+
+        ((raw - TEMPERATURE_RAW_MIN) * 125 / (TEMPERATURE_RAW_MAX - TEMPERATURE_RAW_MIN) - 40) as i16
+    }
+
+    /// Measure the supply voltage in millivolts
+    ///
+    /// Converts the internal 1.1V bandgap against `AVCC`, so `AVCC` must be connected
+    /// to `VCC`.  Afterwards the previously configured reference is restored.  The
+    /// bandgap voltage varies from chip to chip, so expect an error of up to 10%.
+    pub fn read_vcc_millivolts(&mut self) -> u16 {
+        let reference = self.reference;
+        self.set_reference(Reference::AVcc);
+
+        // The bandgap needs some time to settle after selecting it
+        self.convert(BANDGAP_CHANNEL);
+        let value = self.convert(BANDGAP_CHANNEL) as u32;
+
+        self.set_reference(reference);
+        if value == 0 {
+            return ::core::u16::MAX;
+        }
+        (BANDGAP_MV * 1024 / value) as u16
+    }
+
+    fn select_channel(&mut self, id: u8) {
+        let mut admux = self.reference.bits() | (id & MUX_MASK);
+        if self.adjust == Adjust::Left {
+            admux |= ADLAR;
+        }
+        self.adc.admux.write(|w| unsafe { w.bits(admux) });
+        self.adc.adcsr_b.modify(|r, w| unsafe {
+            if id & MUX5 != 0 {
+                w.bits(r.bits() | MUX5)
+            } else {
+                w.bits(r.bits() & !MUX5)
+            }
+        });
+    }
+
+    fn convert(&mut self, id: u8) -> u16 {
+        self.start_conversion(id);
+        self.read_result()
+    }
+
+    /// Run a conversion and wait for it to finish
+    fn start_conversion(&mut self, id: u8) {
+        self.select_channel(id);
+
+        // Start the conversion
+        self.adc
+            .adcsr_a
+            .modify(|r, w| unsafe { w.bits((r.bits() & !ADIF) | ADSC) });
+
+        // Wait for it to finish
+        while self.adc.adcsr_a.read().bits() & ADIF == 0 {}
+
+        // Clear the flag by writing a one
+        self.adc
+            .adcsr_a
+            .modify(|r, w| unsafe { w.bits(r.bits() | ADIF) });
+    }
+
+    /// Read the 10-bit result of the last conversion
+    fn read_result(&mut self) -> u16 {
+        // ADCL needs to be read first, it locks ADCH until ADCH is read
+        let low = self.adc.adc_l.read().bits() as u16;
+        let high = self.adc.adc_h.read().bits() as u16;
+
+        match self.adjust {
+            Adjust::Right => (high << 8) | low,
+            Adjust::Left => (high << 2) | (low >> 6),
+        }
+    }
+}
+
+/// Read a pin once without keeping an [Adc] around
+///
+/// Enables the ADC, takes a single reading and disables it again.  The ADC
+/// peripheral is passed in and handed back, so it is still owned by the caller.
+///
+/// This is slow: After enabling the ADC and selecting the reference, the first
+/// conversion takes 25 instead of 13 ADC clock cycles and is discarded because the
+/// reference may not have settled yet.  In total, a reading takes 38 ADC clock cycles,
+/// about 300us at 16MHz with the default `Div128`, compared to 104us for
+/// `Adc::analog_read()`.  Fine for an occasional battery check, but keep an [Adc]
+/// for anything regular.
+///
+/// ```
+/// let (value, adc) = adc::read_once(dp.ADC, &mut pf0, adc::Reference::Internal2_56);
+/// ```
+pub fn read_once<PIN>(
+    adc: atmega32u4::ADC,
+    pin: &mut PIN,
+    reference: Reference,
+) -> (u16, atmega32u4::ADC)
+where
+    PIN: hal::adc::Channel<Adc, ID = u8>,
+{
+    let mut adc = Adc::new(adc, reference);
+    adc.analog_read(pin);
+    let value = adc.analog_read(pin);
+    (value, adc.free())
+}
+
+/// `ADC` interrupt hook for free-running mode
+///
+/// Call this from your `ADC` interrupt handler.  It stores the 10-bit result for
+/// `Adc::latest()` and also returns it, so it can be processed right away, for
+/// example by pushing it into a buffer.
+pub fn adc_isr() -> u16 {
+    let adc = unsafe { &*atmega32u4::ADC::ptr() };
+
+    // ADCL needs to be read first, it locks ADCH until ADCH is read
+    let low = adc.adc_l.read().bits() as u16;
+    let high = adc.adc_h.read().bits() as u16;
+    let value = if adc.admux.read().bits() & ADLAR != 0 {
+        (high << 2) | (low >> 6)
+    } else {
+        (high << 8) | low
+    };
+
+    LATEST.set(value);
+    value
+}
+
+impl<WORD, PIN> hal::adc::OneShot<Adc, WORD, PIN> for Adc
+where
+    WORD: From<u16>,
+    PIN: hal::adc::Channel<Adc, ID = u8>,
+{
+    type Error = ();
+
+    fn read(&mut self, _pin: &mut PIN) -> nb::Result<WORD, Self::Error> {
+        Ok(self.convert(PIN::channel()).into())
+    }
+}
+
+macro_rules! adc_channel_impl {
+    ($($PIN:ident: $id:expr,)+) => {
+        $(
+            impl<MODE> hal::adc::Channel<Adc> for port::portf::$PIN<port::mode::io::Input<MODE>> {
+                type ID = u8;
+
+                fn channel() -> u8 {
+                    $id
+                }
+            }
+        )+
+    }
+}
+
+adc_channel_impl! {
+    PF0: 0b00_0000,
+    PF1: 0b00_0001,
+    PF4: 0b00_0100,
+    PF5: 0b00_0101,
+    PF6: 0b00_0110,
+    PF7: 0b00_0111,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn division(prescaler: Prescaler) -> u32 {
+        1 << prescaler.bits()
+    }
+
+    #[test]
+    fn prescaler_for_supported_speeds() {
+        assert_eq!(Prescaler::for_frequency(24_000_000), Prescaler::Div128);
+        assert_eq!(Prescaler::for_frequency(20_000_000), Prescaler::Div128);
+        assert_eq!(Prescaler::for_frequency(16_000_000), Prescaler::Div128);
+        assert_eq!(Prescaler::for_frequency(12_000_000), Prescaler::Div64);
+        assert_eq!(Prescaler::for_frequency(8_000_000), Prescaler::Div64);
+        assert_eq!(Prescaler::for_frequency(1_000_000), Prescaler::Div8);
+    }
+
+    #[test]
+    fn prescaler_at_200khz_boundary() {
+        assert_eq!(Prescaler::for_frequency(12_800_000), Prescaler::Div64);
+        assert_eq!(Prescaler::for_frequency(12_800_001), Prescaler::Div128);
+        assert_eq!(Prescaler::for_frequency(400_000), Prescaler::Div2);
+        assert_eq!(Prescaler::for_frequency(400_001), Prescaler::Div4);
+    }
+
+    #[test]
+    fn prescaler_is_smallest_below_200khz() {
+        for mhz in 1..=25 {
+            let freq = mhz * 1_000_000;
+            let div = division(Prescaler::for_frequency(freq));
+            assert!(freq / div <= 200_000);
+            if div > 2 {
+                assert!(freq / (div / 2) > 200_000);
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+// Shared parts of the board support modules
+
+/// Generate a `Board` struct for a `define_pins!` pinout
+///
+/// The board takes all peripherals, splits the ports into the named pins and keeps
+/// everything else for the user.
+macro_rules! board_impl {
+    (
+        $(#[$attr:meta])*
+        pins: $Pins:ident,
+        speed: $Speed:ty,
+    ) => {
+        $(#[$attr])*
+        pub struct Board {
+            /// Pins with the names printed on the board
+            pub pins: $Pins,
+            /// Delay for the board's CPU clock
+            pub delay: $crate::delay::Delay<$Speed>,
+            /// Analog comparator
+            pub ac: atmega32u4::AC,
+            /// Analog to digital converter
+            pub adc: atmega32u4::ADC,
+            /// CPU registers (clock prescaler, reset flags)
+            pub cpu: atmega32u4::CPU,
+            /// EEPROM
+            pub eeprom: atmega32u4::EEPROM,
+            /// External and pin change interrupts
+            pub exint: atmega32u4::EXINT,
+            /// USB PLL
+            pub pll: atmega32u4::PLL,
+            /// SPI
+            pub spi: atmega32u4::SPI,
+            /// 8-bit timer 0
+            pub timer0: atmega32u4::TIMER0,
+            /// 16-bit timer 1
+            pub timer1: atmega32u4::TIMER1,
+            /// 16-bit timer 3
+            pub timer3: atmega32u4::TIMER3,
+            /// 10-bit high speed timer 4
+            pub timer4: atmega32u4::TIMER4,
+            /// Serial port
+            pub usart1: atmega32u4::USART1,
+            /// USB controller
+            pub usb_device: atmega32u4::USB_DEVICE,
+            /// Watchdog
+            pub wdt: atmega32u4::WDT,
+        }
+
+        impl Board {
+            /// Split the peripherals into the board's pins and the remaining peripherals
+            pub fn new(dp: atmega32u4::Peripherals) -> Board {
+                Board {
+                    pins: $Pins::new(dp.PORTB, dp.PORTC, dp.PORTD, dp.PORTE, dp.PORTF),
+                    delay: $crate::delay::Delay::new(),
+                    ac: dp.AC,
+                    adc: dp.ADC,
+                    cpu: dp.CPU,
+                    eeprom: dp.EEPROM,
+                    exint: dp.EXINT,
+                    pll: dp.PLL,
+                    spi: dp.SPI,
+                    timer0: dp.TIMER0,
+                    timer1: dp.TIMER1,
+                    timer3: dp.TIMER3,
+                    timer4: dp.TIMER4,
+                    usart1: dp.USART1,
+                    usb_device: dp.USB_DEVICE,
+                    wdt: dp.WDT,
+                }
+            }
+        }
+    };
+}
+
+/// Pins, `Led` and `Board` of the Arduino Leonardo and Micro
+///
+/// Both boards share the same pinout, only the documentation differs.  Expects
+/// `atmega32u4`, `delay` and `port` to be in scope.
+#[cfg(any(feature = "leonardo", feature = "micro"))]
+macro_rules! leonardo_pinout {
+    (
+        $(#[$pins_attr:meta])*
+        pins: Pins,
+        $(#[$board_attr:meta])*
+        board: Board,
+    ) => {
+        define_pins! {
+            $(#[$pins_attr])*
+            name: Pins,
+            ddr: DDR {
+                portb: atmega32u4::PORTB,
+                portc: atmega32u4::PORTC,
+                portd: atmega32u4::PORTD,
+                porte: atmega32u4::PORTE,
+                portf: atmega32u4::PORTF,
+            },
+            pins: {
+                /// `D0` / `RX`
+                d0: (portd, pd2, PD2),
+                /// `D1` / `TX`
+                d1: (portd, pd3, PD3),
+                /// `D2` / `SDA`
+                d2: (portd, pd1, PD1),
+                /// `D3` / `SCL`
+                d3: (portd, pd0, PD0),
+                /// `D4`
+                d4: (portd, pd4, PD4),
+                /// `D5`
+                d5: (portc, pc6, PC6),
+                /// `D6`
+                d6: (portd, pd7, PD7),
+                /// `D7`
+                d7: (porte, pe6, PE6),
+                /// `D8`
+                d8: (portb, pb4, PB4),
+                /// `D9`
+                d9: (portb, pb5, PB5),
+                /// `D10`
+                d10: (portb, pb6, PB6),
+                /// `D11`
+                d11: (portb, pb7, PB7),
+                /// `D12`
+                d12: (portd, pd6, PD6),
+                /// `D13` / on-board LED
+                d13: (portc, pc7, PC7),
+                /// `A0`
+                a0: (portf, pf7, PF7),
+                /// `A1`
+                a1: (portf, pf6, PF6),
+                /// `A2`
+                a2: (portf, pf5, PF5),
+                /// `A3`
+                a3: (portf, pf4, PF4),
+                /// `A4`
+                a4: (portf, pf1, PF1),
+                /// `A5`
+                a5: (portf, pf0, PF0),
+                /// `RXLED` (active low) / `SS`
+                led_rx: (portb, pb0, PB0),
+                /// `TXLED` (active low)
+                led_tx: (portd, pd5, PD5),
+                /// `SCK`
+                sck: (portb, pb1, PB1),
+                /// `MOSI`
+                mosi: (portb, pb2, PB2),
+                /// `MISO`
+                miso: (portb, pb3, PB3),
+            }
+        }
+
+        /// The on-board LED (`D13`) configured as an output
+        pub type Led = port::portc::PC7<port::mode::io::Output>;
+
+        board_impl! {
+            $(#[$board_attr])*
+            pins: Pins,
+            speed: delay::MHz16,
+        }
+    };
+}
+
+#[cfg(any(feature = "leonardo", feature = "micro"))]
+use port;
+
+/// `into_led()` for the pin of the on-board LED (`PC7`)
+///
+/// Part of the [prelude](../prelude/index.html).
+#[cfg(any(feature = "leonardo", feature = "micro"))]
+pub trait IntoLed {
+    /// Configure this pin to drive the on-board LED
+    ///
+    /// The LED starts out off.  This is the same as `into_output_low()`.
+    fn into_led<D: port::portc::PortDDR>(
+        self,
+        ddr: &mut D,
+    ) -> port::portc::PC7<port::mode::io::Output>;
+}
+
+#[cfg(any(feature = "leonardo", feature = "micro"))]
+impl<MODE: port::mode::Io> IntoLed for port::portc::PC7<MODE> {
+    fn into_led<D: port::portc::PortDDR>(
+        self,
+        ddr: &mut D,
+    ) -> port::portc::PC7<port::mode::io::Output> {
+        self.into_output_low(ddr)
+    }
+}
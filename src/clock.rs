@@ -0,0 +1,179 @@
+//! System Clock
+//!
+//! The system clock can be divided at runtime using the clock prescaler (`CLKPR`).
+//! Running at a lower frequency saves power.
+//!
+//! *Note*: The speed markers used by [delay::Delay] are not aware of the prescaler.
+//! After changing the prescaler, the effective CPU frequency is the oscillator
+//! frequency divided by the prescaler, so you need to use a `Delay` with the matching
+//! marker.  For example, a 16MHz board using `Div2` needs a `Delay<MHz8>`.
+//!
+//! # Example
+//! ```
+//! use atmega32u4_hal::clock;
+//!
+//! // 16MHz / 16 = 1MHz
+//! clock::set_prescaler(clock::Prescaler::Div16);
+//! let mut delay = delay::Delay::<delay::MHz1>::new();
+//!
+//! // ... do something slow
+//!
+//! // Back to full speed
+//! clock::set_prescaler(clock::Prescaler::Div1);
+//! ```
+//!
+//! # CPU Frequency
+//! The frequency selected using one of the `mhz*` features (see [delay]) is available
+//! as [CPU_FREQUENCY].  All drivers which need to know the clock, like
+//! [delay::Delay], [serial::Serial] or [timer::TimerDelay], default their `SPEED`
+//! parameter to the same feature, so a single feature configures the whole crate:
+//!
+//! ```
+//! // Cargo.toml: atmega32u4-hal = { version = "...", features = ["mhz16"] }
+//! let mut delay: delay::Delay = delay::Delay::new();
+//! let mut serial: serial::Serial = serial::Serial::new(dp.USART1, 115200);
+//! let prescaler = adc::Prescaler::for_clock::<delay::Speed>();
+//!
+//! assert_eq!(clock::CPU_FREQUENCY, 16_000_000);
+//! ```
+//!
+//! One of the `mhz*` features has to be enabled, without one the crate fails to
+//! compile.
+use atmega32u4;
+use delay;
+
+const CLKPCE: u8 = 1 << 7;
+
+/// System clock prescaler
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prescaler {
+    /// Oscillator frequency / 1
+    Div1,
+    /// Oscillator frequency / 2
+    Div2,
+    /// Oscillator frequency / 4
+    Div4,
+    /// Oscillator frequency / 8
+    Div8,
+    /// Oscillator frequency / 16
+    Div16,
+    /// Oscillator frequency / 32
+    Div32,
+    /// Oscillator frequency / 64
+    Div64,
+    /// Oscillator frequency / 128
+    Div128,
+    /// Oscillator frequency / 256
+    Div256,
+}
+
+impl Prescaler {
+    /// `CLKPS3:0` bits for this prescaler
+    fn bits(self) -> u8 {
+        match self {
+            Prescaler::Div1 => 0,
+            Prescaler::Div2 => 1,
+            Prescaler::Div4 => 2,
+            Prescaler::Div8 => 3,
+            Prescaler::Div16 => 4,
+            Prescaler::Div32 => 5,
+            Prescaler::Div64 => 6,
+            Prescaler::Div128 => 7,
+            Prescaler::Div256 => 8,
+        }
+    }
+
+    fn from_bits(bits: u8) -> Prescaler {
+        match bits & 0b1111 {
+            0 => Prescaler::Div1,
+            1 => Prescaler::Div2,
+            2 => Prescaler::Div4,
+            3 => Prescaler::Div8,
+            4 => Prescaler::Div16,
+            5 => Prescaler::Div32,
+            6 => Prescaler::Div64,
+            7 => Prescaler::Div128,
+            _ => Prescaler::Div256,
+        }
+    }
+}
+
+/// A CPU clock speed
+///
+/// Implemented by the speed markers in the [delay] module.
+pub trait Clock {
+    /// Frequency in Hz
+    const FREQ: u32;
+}
+
+impl Clock for delay::MHz24 {
+    const FREQ: u32 = 24_000_000;
+}
+
+impl Clock for delay::MHz20 {
+    const FREQ: u32 = 20_000_000;
+}
+
+impl Clock for delay::MHz16 {
+    const FREQ: u32 = 16_000_000;
+}
+
+impl Clock for delay::MHz12 {
+    const FREQ: u32 = 12_000_000;
+}
+
+impl Clock for delay::MHz8 {
+    const FREQ: u32 = 8_000_000;
+}
+
+impl Clock for delay::MHz1 {
+    const FREQ: u32 = 1_000_000;
+}
+
+/// CPU frequency in Hz, selected using the `mhz*` features
+#[cfg(any(
+    feature = "mhz24",
+    feature = "mhz20",
+    feature = "mhz16",
+    feature = "mhz12",
+    feature = "mhz8",
+    feature = "mhz1",
+))]
+pub const CPU_FREQUENCY: u32 = <delay::Speed as Clock>::FREQ;
+
+/// Set the system clock prescaler
+///
+/// This performs the timed `CLKPCE` sequence with interrupts disabled.
+pub fn set_prescaler(prescaler: Prescaler) {
+    atmega32u4::interrupt::free(|_| timed_write(prescaler.bits()))
+}
+
+/// Read the current system clock prescaler
+pub fn prescaler() -> Prescaler {
+    Prescaler::from_bits(unsafe { (*atmega32u4::CPU::ptr()).clkpr.read().bits() })
+}
+
+// CLKPR is not in IO space (0x61), so `sts` is needed.  The new value has to be
+// written within 4 cycles after setting CLKPCE.
+#[cfg(target_arch = "avr")]
+fn timed_write(value: u8) {
+    unsafe {
+        asm!("sts 0x61, $0\n\tsts 0x61, $1"
+             :
+             : "r"(CLKPCE), "r"(value)
+             : "memory"
+             : "volatile"
+             );
+    }
+}
+
+#[cfg(not(any(target_arch = "avr", feature = "docs")))]
+fn timed_write(_value: u8) {
+    sorry!(This library is made for avr and cannot be compiled for anything else!)
+}
+
+#[cfg(feature = "docs")]
+fn timed_write(_value: u8) {
+    // Empty implementation when building documentation
+    unimplemented!("This library is made for avr and cannot be used for anything else!")
+}
@@ -0,0 +1,108 @@
+//! Debounced Inputs
+//!
+//! Mechanical buttons bounce for a few milliseconds when pressed or released.  A
+//! [Debouncer] samples an input pin each time `update()` is called and only accepts a
+//! new state once it has been stable for 8 consecutive samples.
+//!
+//! Call `update()` at a fixed rate, for example every 2ms from a timer interrupt or a
+//! main loop with a delay.  With 2ms, a press is detected 16ms after the contacts
+//! stopped bouncing.  Any [embedded_hal::digital::v2::InputPin] can be used, including
+//! downgraded pins.
+//!
+//! # Example
+//! A button between `PD2` and GND, using the internal pull-up:
+//!
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut portd = dp.PORTD.split();
+//! let mut delay = delay::Delay::<delay::MHz16>::new();
+//!
+//! let button = portd.pd2.into_pull_up_input(&mut portd.ddr);
+//! let mut button = debounce::Debouncer::new(button, debounce::Active::Low);
+//!
+//! loop {
+//!     button.update();
+//!
+//!     if button.rising() {
+//!         // Button was just pressed
+//!     }
+//!
+//!     delay.delay_ms(2u8);
+//! }
+//! ```
+use hal::digital::v2::InputPin;
+
+/// Level at which the input counts as pressed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Active {
+    /// Pressed while high, for example a button to VCC with a pull-down
+    High,
+    /// Pressed while low, for example a button to GND with a pull-up
+    Low,
+}
+
+/// Debounced input pin
+pub struct Debouncer<PIN> {
+    pin: PIN,
+    active: Active,
+    // Last 8 samples, bit 0 is the newest, a one means pressed
+    history: u8,
+    pressed: bool,
+    changed: bool,
+}
+
+impl<PIN: InputPin> Debouncer<PIN> {
+    /// Wrap an input pin
+    ///
+    /// The input starts out as released.
+    pub fn new(pin: PIN, active: Active) -> Debouncer<PIN> {
+        Debouncer {
+            pin: pin,
+            active: active,
+            history: 0,
+            pressed: false,
+            changed: false,
+        }
+    }
+
+    /// Take a sample
+    ///
+    /// Needs to be called periodically.  Read errors are counted as released.
+    pub fn update(&mut self) {
+        let sample = match self.active {
+            Active::High => self.pin.is_high(),
+            Active::Low => self.pin.is_low(),
+        }.unwrap_or(false);
+
+        self.history = (self.history << 1) | sample as u8;
+        self.changed = false;
+
+        if !self.pressed && self.history == 0xff {
+            self.pressed = true;
+            self.changed = true;
+        } else if self.pressed && self.history == 0x00 {
+            self.pressed = false;
+            self.changed = true;
+        }
+    }
+
+    /// Whether the input is pressed, after debouncing
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Whether the last `update()` detected a press
+    pub fn rising(&self) -> bool {
+        self.changed && self.pressed
+    }
+
+    /// Whether the last `update()` detected a release
+    pub fn falling(&self) -> bool {
+        self.changed && !self.pressed
+    }
+
+    /// Release the pin
+    pub fn free(self) -> PIN {
+        self.pin
+    }
+}
@@ -13,11 +13,68 @@
 //! // Wait 1s
 //! delay.delay_ms(1000);
 //! ```
+//!
+//! ## Selecting the Speed using Features
+//! Instead of passing the speed as a type parameter, it can be selected once using one
+//! of the `mhz24`, `mhz20`, `mhz16`, `mhz12`, `mhz8` or `mhz1` cargo features.  The
+//! parameter can then be omitted:
+//!
+//! ```
+//! let mut delay: delay::Delay = delay::Delay::new();
+//! ```
+//!
+//! Exactly one of these features has to be enabled, enabling none or more than one is
+//! a compile error.
+//!
+//! ## Nanosecond Delays
+//! For very short pulses, `delay_ns()` busy waits for a number of CPU cycles.  The
+//! resolution is one cycle, the requested time is rounded up to the next full cycle:
+//!
+//! | Speed   | Cycle    |
+//! |---------|----------|
+//! | `MHz24` | 41.7ns   |
+//! | `MHz20` | 50ns     |
+//! | `MHz16` | 62.5ns   |
+//! | `MHz12` | 83.3ns   |
+//! | `MHz8`  | 125ns    |
+//! | `MHz1`  | 1000ns   |
+//!
+//! `delay_ns()` is only exact if it is called with a constant, so the cycle count can
+//! be computed at compile time.  Even then, the rounding only applies to the waiting
+//! itself.  Delays of 4 cycles and more call the busy loop, which is not inlined:
+//! Loading the counter, `rcall` and `ret` add about 9 cycles on top.  At low clock
+//! speeds this is much longer than a short requested delay:
+//!
+//! | Speed   | Extra time |
+//! |---------|------------|
+//! | `MHz16` | ~560ns     |
+//! | `MHz8`  | ~1.1µs     |
+//! | `MHz1`  | ~9µs       |
+//!
+//! Delays below 4 cycles are pure `nop`s and don't have this overhead.
+//!
+//! ## Long Delays
+//! Longer delays are split into chunks, so no conversion between units can overflow:
+//! `delay_us()` waits 4096us at a time and `delay_ms()` with a `u32` waits 32768ms at
+//! a time.  The longest possible delays are therefore:
+//!
+//! | Call                  | Maximum                        |
+//! |-----------------------|--------------------------------|
+//! | `delay_us(u32::MAX)`  | about 71.6 minutes             |
+//! | `delay_ms(u16::MAX)`  | 65.5 seconds                   |
+//! | `delay_ms(u32::MAX)`  | about 49.7 days                |
+//!
+//! A `delay_ms(2 * 60 * 60 * 1000u32)` really waits two hours, even though that many
+//! microseconds don't fit into a `u32` anymore.  The loop overhead per chunk adds up,
+//! so a delay of several minutes ends up a few milliseconds too long.
 use hal::blocking::delay;
 use core::marker;
+use clock;
 
 /// Delay abstraction
-pub struct Delay<SPEED> {
+///
+/// If `SPEED` is omitted, the speed selected using the `mhz*` features is used.
+pub struct Delay<SPEED = Speed> {
     _speed: marker::PhantomData<SPEED>,
 }
 
@@ -30,6 +87,96 @@ impl<SPEED> Delay<SPEED> {
     }
 }
 
+impl<SPEED: clock::Clock> Delay<SPEED> {
+    /// Delay for at least `ns` nanoseconds
+    ///
+    /// The delay is rounded up to the next full CPU cycle.  From 4 cycles on, the busy
+    /// loop call adds a fixed overhead of about 9 cycles, see the
+    /// [module documentation](index.html#nanosecond-delays).
+    #[inline(always)]
+    pub fn delay_ns(&mut self, ns: u16) {
+        let cycles = (ns as u32 * (SPEED::FREQ / 1_000_000) + 999) / 1000;
+
+        // busy_loop takes 4 cycles per iteration, the rest is filled with nops
+        let iterations = (cycles / 4) as u16;
+        if iterations > 0 {
+            busy_loop(iterations);
+        }
+        nops((cycles % 4) as u8);
+    }
+}
+
+/// CPU speed selected using the cargo features
+#[cfg(feature = "mhz24")]
+pub type Speed = MHz24;
+
+/// CPU speed selected using the cargo features
+#[cfg(feature = "mhz20")]
+pub type Speed = MHz20;
+
+/// CPU speed selected using the cargo features
+#[cfg(feature = "mhz16")]
+pub type Speed = MHz16;
+
+/// CPU speed selected using the cargo features
+#[cfg(feature = "mhz12")]
+pub type Speed = MHz12;
+
+/// CPU speed selected using the cargo features
+#[cfg(feature = "mhz8")]
+pub type Speed = MHz8;
+
+/// CPU speed selected using the cargo features
+#[cfg(feature = "mhz1")]
+pub type Speed = MHz1;
+
+/// CPU speed selected using the cargo features
+///
+/// No `mhz*` feature is enabled.  This only keeps the missing feature from causing
+/// more errors than the `compile_error!` below.
+#[cfg(not(any(
+    feature = "mhz24",
+    feature = "mhz20",
+    feature = "mhz16",
+    feature = "mhz12",
+    feature = "mhz8",
+    feature = "mhz1",
+)))]
+pub type Speed = NoSpeedSelected;
+
+/// Placeholder speed if no `mhz*` feature is enabled
+#[doc(hidden)]
+pub enum NoSpeedSelected {}
+
+#[cfg(any(
+    all(feature = "mhz24", feature = "mhz20"),
+    all(feature = "mhz24", feature = "mhz16"),
+    all(feature = "mhz24", feature = "mhz12"),
+    all(feature = "mhz24", feature = "mhz8"),
+    all(feature = "mhz24", feature = "mhz1"),
+    all(feature = "mhz20", feature = "mhz16"),
+    all(feature = "mhz20", feature = "mhz12"),
+    all(feature = "mhz20", feature = "mhz8"),
+    all(feature = "mhz20", feature = "mhz1"),
+    all(feature = "mhz16", feature = "mhz12"),
+    all(feature = "mhz16", feature = "mhz8"),
+    all(feature = "mhz16", feature = "mhz1"),
+    all(feature = "mhz12", feature = "mhz8"),
+    all(feature = "mhz12", feature = "mhz1"),
+    all(feature = "mhz8", feature = "mhz1"),
+))]
+compile_error!("Only one of the `mhz*` features can be enabled!");
+
+#[cfg(not(any(
+    feature = "mhz24",
+    feature = "mhz20",
+    feature = "mhz16",
+    feature = "mhz12",
+    feature = "mhz8",
+    feature = "mhz1",
+)))]
+compile_error!("Select the CPU speed by enabling one of the `mhz*` features!");
+
 /// 24 MHz Clock
 pub struct MHz24;
 
@@ -76,6 +223,30 @@ fn busy_loop(_us: u16) {
     unimplemented!("This library is made for avr and cannot be used for anything else!")
 }
 
+#[cfg(target_arch = "avr")]
+#[inline(always)]
+fn nops(n: u8) {
+    unsafe {
+        match n {
+            0 => (),
+            1 => asm!("nop" :::: "volatile"),
+            2 => asm!("nop\n\tnop" :::: "volatile"),
+            _ => asm!("nop\n\tnop\n\tnop" :::: "volatile"),
+        }
+    }
+}
+
+#[cfg(not(any(target_arch = "avr", feature = "docs")))]
+fn nops(_n: u8) {
+    sorry!(This library is made for avr and cannot be compiled for anything else!)
+}
+
+#[cfg(feature = "docs")]
+fn nops(_n: u8) {
+    // Empty implementation when building documentation
+    unimplemented!("This library is made for avr and cannot be used for anything else!")
+}
+
 impl delay::DelayUs<u16> for Delay<MHz24> {
     fn delay_us(&mut self, mut us: u16) {
         // for the 24 MHz clock for the aventurous ones, trying to overclock
@@ -203,10 +374,12 @@ impl delay::DelayUs<u16> for Delay<MHz1> {
     fn delay_us(&mut self, mut us: u16) {
         // for the 1 MHz internal clock (default settings for common Atmega microcontrollers)
 
-        // the overhead of the function calls is 14 (16) cycles
-        if us <= 16 {
-            return;
-        } //= 3 cycles, (4 when true)
+        // the overhead of the function calls is 14 (16) cycles, plus the check below
+        // 3 (4) cycles, so returning right away already takes about 18-20us.  Below
+        // 26us, the loop can't do any better than that: the setup below costs another
+        // 6 cycles and each iteration 4 cycles, so a single iteration already ends up
+        // at about 28us.  The ArduinoCore source has an additional `us <= 16` check in
+        // front of this one which is dead code, as both branches simply return.
         if us <= 25 {
             return;
         } //= 3 cycles, (4 when true), (must be at least 25 if we want to substract 22)
@@ -231,22 +404,134 @@ where
     }
 }
 
+/// Longest delay passed to `DelayUs<u16>`, the busy loop is only accurate up to there
+const US_CHUNK: u16 = 0x1000;
+
+/// Split `us` into a number of `US_CHUNK` delays and the remaining microseconds
+fn us_chunks(us: u32) -> (u32, u16) {
+    (us >> 12, (us & 0xfff) as u16)
+}
+
 impl<SPEED> delay::DelayUs<u32> for Delay<SPEED>
 where
     Delay<SPEED>: delay::DelayUs<u16>,
 {
     fn delay_us(&mut self, us: u32) {
-        for _ in 0..(us >> 12) {
-            delay::DelayUs::<u16>::delay_us(self, 0xfff);
+        let (chunks, rest) = us_chunks(us);
+        for _ in 0..chunks {
+            delay::DelayUs::<u16>::delay_us(self, US_CHUNK);
         }
+        delay::DelayUs::<u16>::delay_us(self, rest);
     }
 }
 
+/// Convert a `DelayMs<u16>` delay to microseconds
+///
+/// 65535ms are 65535000us, well within a `u32`.  Longer delays are split up by
+/// `ms_chunks()` first, the saturation only guards against changing that.
+fn ms_to_us(ms: u16) -> u32 {
+    (ms as u32).saturating_mul(1000)
+}
+
+/// Longest delay passed to `DelayMs<u16>` by `DelayMs<u32>`
+const MS_CHUNK: u16 = 0x8000;
+
+/// Split `ms` into a number of `MS_CHUNK` delays and the remaining milliseconds
+fn ms_chunks(ms: u32) -> (u32, u16) {
+    (ms >> 15, (ms & 0x7fff) as u16)
+}
+
 impl<SPEED> delay::DelayMs<u16> for Delay<SPEED>
 where
     Delay<SPEED>: delay::DelayUs<u32>,
 {
     fn delay_ms(&mut self, ms: u16) {
-        delay::DelayUs::<u32>::delay_us(self, ms as u32 * 1000);
+        delay::DelayUs::<u32>::delay_us(self, ms_to_us(ms));
+    }
+}
+
+impl<SPEED> delay::DelayMs<u8> for Delay<SPEED>
+where
+    Delay<SPEED>: delay::DelayMs<u16>,
+{
+    fn delay_ms(&mut self, ms: u8) {
+        delay::DelayMs::<u16>::delay_ms(self, ms as u16);
+    }
+}
+
+impl<SPEED> delay::DelayMs<u32> for Delay<SPEED>
+where
+    Delay<SPEED>: delay::DelayMs<u16>,
+{
+    fn delay_ms(&mut self, ms: u32) {
+        // Delay in chunks so the conversion to microseconds can't overflow.  Anything
+        // above u32::MAX / 1000 ms would wrap around if converted in one go.
+        let (chunks, rest) = ms_chunks(ms);
+        for _ in 0..chunks {
+            delay::DelayMs::<u16>::delay_ms(self, MS_CHUNK);
+        }
+        delay::DelayMs::<u16>::delay_ms(self, rest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn us_total(us: u32) -> u64 {
+        let (chunks, rest) = us_chunks(us);
+        chunks as u64 * US_CHUNK as u64 + rest as u64
+    }
+
+    #[test]
+    fn us_chunks_add_up() {
+        for &us in [0, 1, 4095, 4096, 4097, 8191, 8192, 1_000_000].iter() {
+            assert_eq!(us_total(us), us as u64);
+        }
+        assert_eq!(us_total(::core::u32::MAX), ::core::u32::MAX as u64);
+    }
+
+    #[test]
+    fn us_chunks_rest_is_shorter_than_a_chunk() {
+        for us in 0..20_000 {
+            assert!(us_chunks(us).1 < US_CHUNK);
+        }
+    }
+
+    // Microseconds actually waited by `DelayMs<u32>`
+    fn ms_total_us(ms: u32) -> u64 {
+        let (chunks, rest) = ms_chunks(ms);
+        chunks as u64 * ms_to_us(MS_CHUNK) as u64 + ms_to_us(rest) as u64
+    }
+
+    #[test]
+    fn ms_to_us_does_not_saturate() {
+        assert_eq!(ms_to_us(0), 0);
+        assert_eq!(ms_to_us(1), 1000);
+        assert_eq!(ms_to_us(::core::u16::MAX), 65_535_000);
+    }
+
+    #[test]
+    fn ms_chunks_around_u32_us_limit() {
+        // u32::MAX / 1000 is the last millisecond count which fits as microseconds
+        let limit = ::core::u32::MAX / 1000;
+        for ms in limit - 2..limit + 3 {
+            assert_eq!(ms_total_us(ms), ms as u64 * 1000);
+        }
+    }
+
+    #[test]
+    fn ms_chunks_rest_is_shorter_than_a_chunk() {
+        for ms in 0..100_000 {
+            assert!(ms_chunks(ms).1 < MS_CHUNK);
+        }
+    }
+
+    #[test]
+    fn ms_chunks_add_up() {
+        for &ms in [0, 1, 32_767, 32_768, 65_536, 65_537, 7_200_000].iter() {
+            assert_eq!(ms_total_us(ms), ms as u64 * 1000);
+        }
+        assert_eq!(ms_total_us(::core::u32::MAX), ::core::u32::MAX as u64 * 1000);
     }
 }
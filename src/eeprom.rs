@@ -0,0 +1,146 @@
+//! EEPROM
+//!
+//! The ATmega32U4 contains 1KB of EEPROM which retains its content when the
+//! device is powered off.  This module allows reading and writing it byte-wise.
+//!
+//! Writes block until the previous write is finished (about 3.4ms per byte).  Bytes
+//! that already contain the value to be written are skipped to reduce wear.
+//!
+//! # Example
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//!
+//! let mut eeprom = atmega32u4_hal::eeprom::Eeprom::new(dp.EEPROM);
+//!
+//! eeprom.write_byte(0x10, 0xC0).unwrap();
+//! assert_eq!(eeprom.read_byte(0x10), Ok(0xC0));
+//!
+//! let mut buf = [0; 4];
+//! eeprom.read(0x10, &mut buf).unwrap();
+//! ```
+use atmega32u4;
+
+/// Size of the EEPROM in bytes
+pub const CAPACITY: u16 = 1024;
+
+const EEPE: u8 = 1 << 1;
+const EERE: u8 = 1 << 0;
+
+/// EEPROM access error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The access would exceed the EEPROM capacity
+    OutOfBounds,
+}
+
+/// EEPROM byte-addressable store
+pub struct Eeprom {
+    eeprom: atmega32u4::EEPROM,
+}
+
+impl Eeprom {
+    /// Wrap the EEPROM peripheral
+    pub fn new(eeprom: atmega32u4::EEPROM) -> Eeprom {
+        Eeprom { eeprom: eeprom }
+    }
+
+    /// Access the raw `EEPROM` registers
+    ///
+    /// # Safety
+    /// A write must not be in progress, and the ready interrupt must stay disabled.
+    pub unsafe fn registers(&mut self) -> &atmega32u4::EEPROM {
+        &self.eeprom
+    }
+
+    /// Read a single byte
+    pub fn read_byte(&mut self, addr: u16) -> Result<u8, Error> {
+        check_bounds(addr, 1)?;
+
+        self.wait_ready();
+        self.set_address(addr);
+        self.eeprom.eecr.write(|w| unsafe { w.bits(EERE) });
+
+        Ok(self.eeprom.eedr.read().bits())
+    }
+
+    /// Write a single byte
+    ///
+    /// If the byte already contains `val`, nothing is written.
+    pub fn write_byte(&mut self, addr: u16, val: u8) -> Result<(), Error> {
+        if self.read_byte(addr)? == val {
+            return Ok(());
+        }
+
+        self.wait_ready();
+        self.set_address(addr);
+        self.eeprom.eedr.write(|w| unsafe { w.bits(val) });
+
+        // Erase and write in one operation (EEPM = 0b00)
+        self.eeprom.eecr.write(|w| unsafe { w.bits(0) });
+
+        // EEMPE and EEPE need to be set within 4 cycles, an interrupt in between
+        // would make the write silently fail
+        atmega32u4::interrupt::free(|_| start_write());
+
+        Ok(())
+    }
+
+    /// Read `buf.len()` bytes starting at `addr`
+    pub fn read(&mut self, addr: u16, buf: &mut [u8]) -> Result<(), Error> {
+        check_bounds(addr, buf.len())?;
+
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = self.read_byte(addr + i as u16)?;
+        }
+        Ok(())
+    }
+
+    /// Write `data` starting at `addr`
+    ///
+    /// Bytes which already contain the correct value are skipped.
+    pub fn write(&mut self, addr: u16, data: &[u8]) -> Result<(), Error> {
+        check_bounds(addr, data.len())?;
+
+        for (i, b) in data.iter().enumerate() {
+            self.write_byte(addr + i as u16, *b)?;
+        }
+        Ok(())
+    }
+
+    fn wait_ready(&mut self) {
+        while self.eeprom.eecr.read().bits() & EEPE != 0 {}
+    }
+
+    fn set_address(&mut self, addr: u16) {
+        self.eeprom.eear_h.write(|w| unsafe { w.bits((addr >> 8) as u8) });
+        self.eeprom.eear_l.write(|w| unsafe { w.bits(addr as u8) });
+    }
+}
+
+fn check_bounds(addr: u16, len: usize) -> Result<(), Error> {
+    if addr as usize + len > CAPACITY as usize {
+        Err(Error::OutOfBounds)
+    } else {
+        Ok(())
+    }
+}
+
+// The timed sequence is written in assembly so the compiler can't put anything
+// in between the two instructions.  EECR is at IO address 0x1F.
+#[cfg(target_arch = "avr")]
+fn start_write() {
+    unsafe {
+        asm!("sbi 0x1f, 2\n\tsbi 0x1f, 1" :::: "volatile");
+    }
+}
+
+#[cfg(not(any(target_arch = "avr", feature = "docs")))]
+fn start_write() {
+    sorry!(This library is made for avr and cannot be compiled for anything else!)
+}
+
+#[cfg(feature = "docs")]
+fn start_write() {
+    // Empty implementation when building documentation
+    unimplemented!("This library is made for avr and cannot be used for anything else!")
+}
@@ -0,0 +1,165 @@
+//! Quadrature Encoders
+//!
+//! Reads a rotary encoder connected to two `PORTB` pins using the `PCINT0` interrupt.
+//! On every edge of either signal, the interrupt handler compares the new state of
+//! both pins with the previous one and counts up or down.  Transitions which skip a
+//! state (both signals changed at once) are invalid and ignored.
+//!
+//! Each full cycle of the two signals counts 4 steps.  Most mechanical encoders move
+//! one full cycle per detent.
+//!
+//! [Encoder] implements [embedded_hal::Qei], so it can be used with generic motor
+//! control code.  `count()` is the position as a `u16`, wrapping from 65535 to 0 and
+//! back, and `direction()` is the direction of the last valid step.
+//!
+//! Only one encoder is supported, as all `PORTB` pins share one interrupt.  The
+//! `PCINT0` handler has to call [pcint_isr].
+//!
+//! # Speed
+//! The interrupt handler needs roughly 150 cycles, about 10µs at 16MHz.  If edges come
+//! faster than that, steps are lost.  This allows up to about 100000 edges per second,
+//! so for an encoder with `PPR` cycles per revolution the limit is about
+//! `1500000 / PPR` RPM, for example 60000 RPM at 24 PPR.  Other interrupts delay the
+//! handler, so stay well below that.  Mechanical encoders are usually limited by
+//! their contact bounce long before.
+//!
+//! # Example
+//! ```
+//! fn main() {
+//!     let dp = atmega32u4::Peripherals::take().unwrap();
+//!     let mut portb = dp.PORTB.split();
+//!
+//!     let encoder = encoder::Encoder::new(
+//!         portb.pb4.into_pull_up_input(&mut portb.ddr),
+//!         portb.pb5.into_pull_up_input(&mut portb.ddr),
+//!     );
+//!     unsafe { atmega32u4::interrupt::enable() };
+//!
+//!     loop {
+//!         let position = encoder.position();
+//!     }
+//! }
+//!
+//! interrupt!(PCINT0, pcint0_isr);
+//! fn pcint0_isr() {
+//!     atmega32u4_hal::encoder::pcint_isr();
+//! }
+//! ```
+use atmega32u4;
+use global::Global;
+use hal;
+use pcint;
+use pcint::PcintPin;
+
+/// Step for each `(previous << 2) | current` state, where bit 1 is A and bit 0 is B
+const STEPS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+struct State {
+    mask_a: u8,
+    mask_b: u8,
+    last: u8,
+    position: i16,
+    up: bool,
+}
+
+impl State {
+    fn sample(&self, pins: u8) -> u8 {
+        let a = (pins & self.mask_a != 0) as u8;
+        let b = (pins & self.mask_b != 0) as u8;
+        (a << 1) | b
+    }
+}
+
+static STATE: Global<State> = Global::new();
+
+/// Quadrature encoder on two `PORTB` pins
+pub struct Encoder<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A: PcintPin, B: PcintPin> Encoder<A, B> {
+    /// Start counting
+    ///
+    /// Enables `PCINT0` for both pins.  The position starts at 0.
+    pub fn new(a: A, b: B) -> Encoder<A, B> {
+        let mut state = State {
+            mask_a: A::MASK,
+            mask_b: B::MASK,
+            last: 0,
+            position: 0,
+            up: true,
+        };
+        state.last = state.sample(pcint::snapshot());
+        STATE.set(state);
+
+        atmega32u4::interrupt::free(|_| unsafe {
+            (*atmega32u4::EXINT::ptr())
+                .pcmsk0
+                .modify(|r, w| w.bits(r.bits() | A::MASK | B::MASK))
+        });
+        pcint::enable();
+
+        Encoder { a: a, b: b }
+    }
+
+    /// Current position in steps
+    ///
+    /// Wraps around on overflow.
+    pub fn position(&self) -> i16 {
+        STATE.get(|s| s.position).unwrap_or(0)
+    }
+
+    /// Set the position back to 0
+    pub fn reset(&mut self) {
+        STATE.get(|s| s.position = 0).ok();
+    }
+
+    /// Stop counting and release the pins
+    pub fn free(self) -> (A, B) {
+        atmega32u4::interrupt::free(|_| unsafe {
+            (*atmega32u4::EXINT::ptr())
+                .pcmsk0
+                .modify(|r, w| w.bits(r.bits() & !(A::MASK | B::MASK)))
+        });
+        STATE.take();
+
+        (self.a, self.b)
+    }
+}
+
+/// `PCINT0` interrupt hook for [Encoder]
+///
+/// Call this from your `PCINT0` interrupt handler.  Does nothing if no encoder
+/// exists.
+pub fn pcint_isr() {
+    let pins = pcint::snapshot();
+
+    STATE
+        .get(|s| {
+            let current = s.sample(pins);
+            let step = STEPS[((s.last << 2) | current) as usize];
+            s.position = s.position.wrapping_add(step as i16);
+            if step != 0 {
+                s.up = step > 0;
+            }
+            s.last = current;
+        })
+        .ok();
+}
+
+impl<A: PcintPin, B: PcintPin> hal::Qei for Encoder<A, B> {
+    type Count = u16;
+
+    fn count(&self) -> u16 {
+        self.position() as u16
+    }
+
+    fn direction(&self) -> hal::Direction {
+        if STATE.get(|s| s.up).unwrap_or(true) {
+            hal::Direction::Upcounting
+        } else {
+            hal::Direction::Downcounting
+        }
+    }
+}
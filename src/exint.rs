@@ -0,0 +1,219 @@
+//! External Interrupts
+//!
+//! The following pins can trigger an external interrupt:
+//!
+//! | Interrupt | Port                | Pin   |
+//! |-----------|---------------------|-------|
+//! | `INT0`    | [atmega32u4::PORTD] | `PD0` |
+//! | `INT1`    | [atmega32u4::PORTD] | `PD1` |
+//! | `INT2`    | [atmega32u4::PORTD] | `PD2` |
+//! | `INT3`    | [atmega32u4::PORTD] | `PD3` |
+//! | `INT6`    | [atmega32u4::PORTE] | `PE6` |
+//!
+//! An input pin can be turned into an interrupt source using `into_interrupt()`.  This
+//! configures the trigger in `EICRA`/`EICRB`, clears a stale flag in `EIFR` and enables
+//! the interrupt in `EIMSK`.  The interrupt service routine itself still needs to be
+//! defined using the `interrupt!` macro.
+//!
+//! `INT6` is the only external interrupt on `PORTE` and is configured in `EICRB`
+//! instead of `EICRA`.  Apart from that it works like the others:
+//!
+//! ```
+//! let mut porte = dp.PORTE.split();
+//! let int6 = porte.pe6
+//!     .into_pull_up_input(&mut porte.ddr)
+//!     .into_interrupt(exint::Edge::RisingEdge);
+//!
+//! interrupt!(INT6, int6_isr);
+//! fn int6_isr() {
+//!     // ...
+//! }
+//! ```
+//!
+//! # Example
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut portd = dp.PORTD.split();
+//!
+//! let mut int0 = portd.pd0
+//!     .into_pull_up_input(&mut portd.ddr)
+//!     .into_interrupt(exint::Edge::FallingEdge);
+//!
+//! // Mask the interrupt while updating shared state
+//! int0.disable();
+//! // ...
+//! int0.enable();
+//!
+//! interrupt!(INT0, int0_isr);
+//! fn int0_isr() {
+//!     // Button was pressed
+//! }
+//! ```
+//!
+//! `ExtInt` keeps the pin with its mode and implements
+//! [embedded_hal::digital::v2::InputPin] by reading it.  Stored in a `Global`, this
+//! allows checking the level of a `BothEdges` interrupt inside the handler:
+//!
+//! ```
+//! static INT0: Global<exint::ExtInt<port::portd::PD0<Input<PullUp>>>> = Global::new();
+//!
+//! interrupt!(INT0, int0_isr);
+//! fn int0_isr() {
+//!     INT0.get(|int0| {
+//!         if int0.is_low().unwrap() {
+//!             // Pressed
+//!         }
+//!     }).ok();
+//! }
+//! ```
+use atmega32u4;
+use core::convert::Infallible;
+use hal::digital::v2::InputPin;
+use port;
+
+/// Interrupt trigger
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    /// Trigger on a rising edge
+    RisingEdge,
+    /// Trigger on a falling edge
+    FallingEdge,
+    /// Trigger on any logical change
+    BothEdges,
+    /// Trigger as long as the pin is low
+    LowLevel,
+}
+
+impl Edge {
+    /// `ISCn1:0` bits for this trigger
+    fn bits(self) -> u8 {
+        match self {
+            Edge::LowLevel => 0b00,
+            Edge::BothEdges => 0b01,
+            Edge::FallingEdge => 0b10,
+            Edge::RisingEdge => 0b11,
+        }
+    }
+}
+
+/// A pin configured as an external interrupt source
+pub struct ExtInt<PIN> {
+    pin: PIN,
+    int: u8,
+}
+
+impl<PIN> ExtInt<PIN> {
+    /// Enable this interrupt
+    ///
+    /// A flag which was set while the interrupt was disabled fires the interrupt right
+    /// away.  Call `clear_pending()` first to ignore it.
+    pub fn enable(&mut self) {
+        let mask = 1 << self.int;
+        atmega32u4::interrupt::free(|_| unsafe {
+            (*atmega32u4::EXINT::ptr())
+                .eimsk
+                .modify(|r, w| w.bits(r.bits() | mask))
+        })
+    }
+
+    /// Disable this interrupt
+    pub fn disable(&mut self) {
+        let mask = 1 << self.int;
+        atmega32u4::interrupt::free(|_| unsafe {
+            (*atmega32u4::EXINT::ptr())
+                .eimsk
+                .modify(|r, w| w.bits(r.bits() & !mask))
+        })
+    }
+
+    /// Clear a pending interrupt
+    pub fn clear_pending(&mut self) {
+        // Flags are cleared by writing a one
+        unsafe { (*atmega32u4::EXINT::ptr()).eifr.write(|w| w.bits(1 << self.int)) }
+    }
+
+    /// Whether the interrupt flag is set
+    ///
+    /// While the interrupt is enabled, the flag is cleared as soon as the handler runs.
+    /// With `LowLevel`, no flag is set at all.
+    pub fn is_pending(&self) -> bool {
+        unsafe { (*atmega32u4::EXINT::ptr()).eifr.read().bits() & (1 << self.int) != 0 }
+    }
+
+    /// Borrow the pin
+    pub fn pin(&self) -> &PIN {
+        &self.pin
+    }
+
+    /// Disable this interrupt and release the pin
+    pub fn free(mut self) -> PIN {
+        self.disable();
+        self.pin
+    }
+}
+
+impl<PIN: InputPin<Error = Infallible>> InputPin for ExtInt<PIN> {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> {
+        self.pin.is_high()
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        self.pin.is_low()
+    }
+}
+
+fn configure(int: u8, edge: Edge) {
+    let mask = 1 << int;
+    let shift = (int % 4) * 2;
+
+    atmega32u4::interrupt::free(|_| {
+        let exint = unsafe { &*atmega32u4::EXINT::ptr() };
+
+        // Changing ISCn can trigger an interrupt, so mask it first
+        exint.eimsk.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+
+        if int < 4 {
+            exint.eicr_a.modify(|r, w| unsafe {
+                w.bits((r.bits() & !(0b11 << shift)) | (edge.bits() << shift))
+            });
+        } else {
+            exint.eicr_b.modify(|r, w| unsafe {
+                w.bits((r.bits() & !(0b11 << shift)) | (edge.bits() << shift))
+            });
+        }
+
+        exint.eifr.write(|w| unsafe { w.bits(mask) });
+        exint.eimsk.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+    })
+}
+
+macro_rules! exint_impl {
+    ($($port:ident::$PIN:ident: $int:expr,)+) => {
+        $(
+            impl<MODE> port::$port::$PIN<port::mode::io::Input<MODE>> {
+                /// Make this pin an external interrupt source
+                pub fn into_interrupt(
+                    self,
+                    edge: Edge,
+                ) -> ExtInt<port::$port::$PIN<port::mode::io::Input<MODE>>> {
+                    configure($int, edge);
+
+                    ExtInt {
+                        pin: self,
+                        int: $int,
+                    }
+                }
+            }
+        )+
+    }
+}
+
+exint_impl! {
+    portd::PD0: 0,
+    portd::PD1: 1,
+    portd::PD2: 2,
+    portd::PD3: 3,
+    porte::PE6: 6,
+}
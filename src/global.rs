@@ -17,8 +17,64 @@
 //!     }).expect("Interrupt fired before initialisation!");
 //! }
 //! ```
+//!
+//! # Inside Interrupt Handlers
+//! Interrupts are already disabled while an interrupt handler runs, so disabling them
+//! again in `get` only costs time.  In hot handlers, the unsafe `get_unchecked` and
+//! `set_unchecked` skip this:
+//!
+//! ```
+//! interrupt!(INT1, int1_isr);
+//! fn int1_isr() {
+//!     // Interrupts are disabled in here
+//!     unsafe { MY_GLOBAL.get_unchecked(|v| *v += 1) }.ok();
+//! }
+//! ```
+//!
+//! # Flags and Counters
+//! For a `bool`, `u8` or `u16` shared with an interrupt handler, [GlobalCell] is
+//! simpler: It always holds a value, so there is no `Option` and no closure.  Single
+//! byte values are read and written without disabling interrupts, because a single
+//! load or store on AVR can't be interrupted halfway.
+//!
+//! ```
+//! use atmega32u4_hal::GlobalCell;
+//!
+//! static BUTTON_PRESSED: GlobalCell<bool> = GlobalCell::new(false);
+//! static EDGES: GlobalCell<u16> = GlobalCell::new(0);
+//!
+//! fn main() {
+//!     loop {
+//!         if BUTTON_PRESSED.replace(false) {
+//!             let edges = EDGES.get();
+//!             // Handle the button
+//!         }
+//!     }
+//! }
+//!
+//! interrupt!(INT0, int0_isr);
+//! fn int0_isr() {
+//!     BUTTON_PRESSED.set(true);
+//!     EDGES.update(|e| e.wrapping_add(1));
+//! }
+//! ```
+//!
+//! # Arrays of Globals
+//! To give each of several interrupts its own slot, declare an array of globals using
+//! the [global_array] macro:
+//!
+//! ```
+//! static COUNTERS: [Global<u16>; 4] = global_array![u16; 4];
+//!
+//! interrupt!(INT2, int2_isr);
+//! fn int2_isr() {
+//!     COUNTERS[2].get(|c| *c += 1).ok();
+//! }
+//! ```
 use atmega32u4;
 use core::cell;
+use core::mem;
+use core::ptr;
 
 /// A global variable store
 ///
@@ -41,14 +97,36 @@ use core::cell;
 ///     }).expect("Interrupt fired before initialisation!");
 /// }
 /// ```
-pub struct Global<T>(cell::UnsafeCell<Option<T>>);
+///
+/// # Reentrancy
+/// `get` hands out a `&mut T`.  To prevent creating a second, aliasing reference, the
+/// global is marked as borrowed while the closure runs.  A nested `get` on the same
+/// global returns `Err(())`, as do `init_with` and `get_copy` (`None`).  Calling `set`,
+/// `replace` or `take` while the global is borrowed panics.
+pub struct Global<T> {
+    value: cell::UnsafeCell<Option<T>>,
+    borrowed: cell::Cell<bool>,
+}
 
 unsafe impl<T> Sync for Global<T> {}
 
 impl<T> Global<T> {
     /// Create a new global variable
     pub const fn new() -> Global<T> {
-        Global(cell::UnsafeCell::new(None))
+        Global {
+            value: cell::UnsafeCell::new(None),
+            borrowed: cell::Cell::new(false),
+        }
+    }
+
+    /// Get the inner option, panicking if it is currently borrowed
+    ///
+    /// Must only be called with interrupts disabled.
+    unsafe fn inner(&self) -> &mut Option<T> {
+        if self.borrowed.get() {
+            panic!("Global is already borrowed!");
+        }
+        &mut *self.value.get()
     }
 
     /// Set this global to some value
@@ -56,24 +134,345 @@ impl<T> Global<T> {
     /// Used for initialization
     pub fn set(&self, val: T) {
         atmega32u4::interrupt::free(|_| unsafe {
-            *self.0.get() = Some(val);
+            *self.inner() = Some(val);
         })
     }
 
+    /// Initialize this global lazily
+    ///
+    /// Runs `f` and stores its result, but only if the global hasn't been initialized
+    /// yet.  Otherwise the existing value is kept and `f` is not called.  Combined with
+    /// `get`, this allows initializing a global on first access:
+    ///
+    /// ```
+    /// MY_GLOBAL.init_with(|| 0xC0FFEE).unwrap();
+    /// MY_GLOBAL.get(|v| {
+    ///     // Do something
+    /// }).unwrap();
+    /// ```
+    ///
+    /// Returns `Err(())` if the global is currently borrowed by an outer `get`.  If `f`
+    /// initializes the global itself, its result is dropped and the value set by `f`
+    /// is kept.
+    ///
+    /// *Note*: `f` runs with interrupts disabled, so it should be short and must not
+    /// block.
+    pub fn init_with<F: FnOnce() -> T>(&self, f: F) -> Result<(), ()> {
+        atmega32u4::interrupt::free(|_| {
+            if self.borrowed.get() {
+                return Err(());
+            }
+            if unsafe { (*self.value.get()).is_some() } {
+                return Ok(());
+            }
+
+            // No reference into the global may be live while `f` runs, it might
+            // access the global as well
+            let new = f();
+
+            let val = unsafe { &mut *self.value.get() };
+            if val.is_none() {
+                *val = Some(new);
+            }
+            Ok(())
+        })
+    }
+
+    /// Whether this global has been initialized
+    pub fn is_initialized(&self) -> bool {
+        atmega32u4::interrupt::free(|_| {
+            // A borrowed global is always initialized
+            self.borrowed.get() || unsafe { (*self.value.get()).is_some() }
+        })
+    }
+
+    /// Replace the value of this global, returning the previous one
+    ///
+    /// Unlike a `take` followed by a `set`, this happens in a single critical section.
+    pub fn replace(&self, val: T) -> Option<T> {
+        atmega32u4::interrupt::free(|_| unsafe { mem::replace(self.inner(), Some(val)) })
+    }
+
+    /// Move the value out of this global
+    ///
+    /// Returns `None` if the global hasn't been initialized.  Afterwards the global
+    /// is uninitialized again, so `get` will return `Err(())` until it is `set`
+    /// again.
+    pub fn take(&self) -> Option<T> {
+        atmega32u4::interrupt::free(|_| unsafe { self.inner().take() })
+    }
+
     /// Get the value of this global
     ///
     /// Will execute `f` with the value of the global if the global
-    /// has been initialized.  If it hasn't been, return `Err(())`.
+    /// has been initialized.  If it hasn't been, or if it is already borrowed
+    /// by an outer `get`, return `Err(())`.
     ///
     /// While the closure is executed, interrupts are disabled.
     pub fn get<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> Result<R, ()> {
+        atmega32u4::interrupt::free(|_| unsafe { self.get_unchecked(f) })
+    }
+
+    /// Get the value of this global without disabling interrupts
+    ///
+    /// Behaves like `get`, but skips saving `SREG` and disabling interrupts, which
+    /// saves a few cycles in hot interrupt handlers.
+    ///
+    /// # Safety
+    /// Interrupts must be disabled for the whole call.  This is the case inside an
+    /// interrupt handler, as long as it doesn't re-enable interrupts itself, and
+    /// inside `interrupt::free`.  Calling this from normal code with interrupts
+    /// enabled allows an interrupt handler to access the global at the same time.
+    pub unsafe fn get_unchecked<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> Result<R, ()> {
+        if self.borrowed.get() {
+            return Err(());
+        }
+
+        let val = &mut *self.value.get();
+        if let &mut Some(ref mut v) = val {
+            self.borrowed.set(true);
+            let res = f(v);
+            self.borrowed.set(false);
+            Ok(res)
+        } else {
+            Err(())
+        }
+    }
+
+    /// Set this global without disabling interrupts
+    ///
+    /// Behaves like `set`, including the panic if the global is borrowed.
+    ///
+    /// # Safety
+    /// Interrupts must be disabled, see `get_unchecked`.
+    pub unsafe fn set_unchecked(&self, val: T) {
+        *self.inner() = Some(val);
+    }
+}
+
+impl<T> Default for Global<T> {
+    fn default() -> Global<T> {
+        Global::new()
+    }
+}
+
+impl<T: Copy> Global<T> {
+    /// Get a copy of the value of this global
+    ///
+    /// Returns `None` if the global hasn't been initialized or is currently borrowed.
+    /// This is a shorthand for `get` without a closure, useful for counters and flags.
+    pub fn get_copy(&self) -> Option<T> {
         atmega32u4::interrupt::free(|_| {
-            let val = unsafe { &mut *self.0.get() };
-            if let &mut Some(ref mut v) = val {
-                Ok(f(v))
+            if self.borrowed.get() {
+                None
             } else {
-                Err(())
+                unsafe { *self.value.get() }
             }
         })
     }
 }
+
+/// A global holding a `Copy` value
+///
+/// Unlike [Global], it is initialized right away and is accessed by value.  `get()` and
+/// `set()` of values larger than one byte disable interrupts, so an interrupt handler
+/// can't see half of an update.  `update()` and `replace()` always do, as they read
+/// and write.
+pub struct GlobalCell<T: Copy> {
+    value: cell::UnsafeCell<T>,
+}
+
+unsafe impl<T: Copy> Sync for GlobalCell<T> {}
+
+impl<T: Copy> GlobalCell<T> {
+    /// Create a new global with an initial value
+    pub const fn new(value: T) -> GlobalCell<T> {
+        GlobalCell {
+            value: cell::UnsafeCell::new(value),
+        }
+    }
+
+    /// Whether a single load or store accesses the whole value
+    #[inline(always)]
+    fn is_atomic() -> bool {
+        mem::size_of::<T>() <= 1
+    }
+
+    /// Read the value
+    #[inline]
+    pub fn get(&self) -> T {
+        if GlobalCell::<T>::is_atomic() {
+            unsafe { ptr::read_volatile(self.value.get()) }
+        } else {
+            atmega32u4::interrupt::free(|_| unsafe { ptr::read_volatile(self.value.get()) })
+        }
+    }
+
+    /// Write the value
+    #[inline]
+    pub fn set(&self, value: T) {
+        if GlobalCell::<T>::is_atomic() {
+            unsafe { ptr::write_volatile(self.value.get(), value) }
+        } else {
+            atmega32u4::interrupt::free(|_| unsafe {
+                ptr::write_volatile(self.value.get(), value)
+            })
+        }
+    }
+
+    /// Replace the value, returning the previous one
+    pub fn replace(&self, value: T) -> T {
+        self.update_with(|_| value)
+    }
+
+    /// Compute the new value from the old one in a single critical section
+    ///
+    /// `f` runs with interrupts disabled, so keep it short.
+    pub fn update<F: FnOnce(T) -> T>(&self, f: F) {
+        self.update_with(f);
+    }
+
+    /// Apply `f` and return the old value
+    fn update_with<F: FnOnce(T) -> T>(&self, f: F) -> T {
+        atmega32u4::interrupt::free(|_| unsafe {
+            let old = ptr::read_volatile(self.value.get());
+            ptr::write_volatile(self.value.get(), f(old));
+            old
+        })
+    }
+}
+
+impl<T: Copy + Default> Default for GlobalCell<T> {
+    fn default() -> GlobalCell<T> {
+        GlobalCell::new(T::default())
+    }
+}
+
+/// Create an array of uninitialized [Global]s
+///
+/// Array repeat expressions only work for `Copy` types, so this macro spells out the
+/// initializer instead.  It supports arrays of 1 to 16 globals:
+///
+/// ```
+/// #[macro_use]
+/// extern crate atmega32u4_hal;
+///
+/// use atmega32u4_hal::Global;
+///
+/// static COUNTERS: [Global<u16>; 4] = global_array![u16; 4];
+/// ```
+#[macro_export]
+macro_rules! global_array {
+    (@fill $T:ty; 0; $($acc:tt)*) => { [$($acc)*] };
+    (@fill $T:ty; 1; $($acc:tt)*) => {
+        global_array!(@fill $T; 0; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 2; $($acc:tt)*) => {
+        global_array!(@fill $T; 1; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 3; $($acc:tt)*) => {
+        global_array!(@fill $T; 2; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 4; $($acc:tt)*) => {
+        global_array!(@fill $T; 3; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 5; $($acc:tt)*) => {
+        global_array!(@fill $T; 4; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 6; $($acc:tt)*) => {
+        global_array!(@fill $T; 5; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 7; $($acc:tt)*) => {
+        global_array!(@fill $T; 6; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 8; $($acc:tt)*) => {
+        global_array!(@fill $T; 7; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 9; $($acc:tt)*) => {
+        global_array!(@fill $T; 8; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 10; $($acc:tt)*) => {
+        global_array!(@fill $T; 9; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 11; $($acc:tt)*) => {
+        global_array!(@fill $T; 10; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 12; $($acc:tt)*) => {
+        global_array!(@fill $T; 11; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 13; $($acc:tt)*) => {
+        global_array!(@fill $T; 12; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 14; $($acc:tt)*) => {
+        global_array!(@fill $T; 13; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 15; $($acc:tt)*) => {
+        global_array!(@fill $T; 14; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    (@fill $T:ty; 16; $($acc:tt)*) => {
+        global_array!(@fill $T; 15; $crate::Global::<$T>::new(), $($acc)*)
+    };
+    // Has to come last, `@fill` is not a type
+    ($T:ty; $n:tt) => { global_array!(@fill $T; $n;) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_get_fails() {
+        static G: Global<u8> = Global::new();
+        G.set(1);
+
+        let inner = G.get(|v| {
+            *v += 1;
+            G.get(|_| ())
+        });
+        assert_eq!(inner, Ok(Err(())));
+        assert_eq!(G.get_copy(), Some(2));
+    }
+
+    #[test]
+    fn get_copy_fails_while_borrowed() {
+        static G: Global<u8> = Global::new();
+        G.set(3);
+
+        assert_eq!(G.get(|_| G.get_copy()), Ok(None));
+    }
+
+    #[test]
+    fn init_with_fails_while_borrowed() {
+        static G: Global<u8> = Global::new();
+        G.set(1);
+
+        let mut called = false;
+        let inner = G.get(|_| G.init_with(|| {
+            called = true;
+            2
+        }));
+        assert_eq!(inner, Ok(Err(())));
+        assert!(!called);
+        assert_eq!(G.get_copy(), Some(1));
+    }
+
+    #[test]
+    fn init_with_only_initializes_once() {
+        static G: Global<u8> = Global::new();
+
+        assert_eq!(G.init_with(|| 1), Ok(()));
+        assert_eq!(G.init_with(|| panic!("already initialized")), Ok(()));
+        assert_eq!(G.get_copy(), Some(1));
+    }
+
+    #[test]
+    fn init_with_keeps_value_set_by_f() {
+        static G: Global<u8> = Global::new();
+
+        assert_eq!(G.init_with(|| {
+            G.set(5);
+            6
+        }), Ok(()));
+        assert_eq!(G.get_copy(), Some(5));
+    }
+}
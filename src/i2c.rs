@@ -0,0 +1,273 @@
+//! I2C Master
+//!
+//! The TWI peripheral uses `PD1` (SDA) and `PD0` (SCL).  [I2c] implements the blocking
+//! [embedded_hal::blocking::i2c::Write], [embedded_hal::blocking::i2c::Read] and
+//! [embedded_hal::blocking::i2c::WriteRead] traits.  The bus needs pull-up resistors,
+//! usually 4.7k to VCC.  The internal pull-ups (`into_pull_up_input()`) are too weak
+//! for anything but short wires at 100kHz.
+//!
+//! The clock is `F_CPU / (16 + 2 * TWBR)`, common rates are 100kHz and 400kHz.
+//!
+//! # Example
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut portd = dp.PORTD.split();
+//!
+//! let mut i2c: i2c::I2c<delay::MHz16> = i2c::I2c::new(
+//!     dp.TWI,
+//!     portd.pd1.into_floating_input(&mut portd.ddr),
+//!     portd.pd0.into_floating_input(&mut portd.ddr),
+//!     100_000,
+//! );
+//!
+//! // Read the WHO_AM_I register of an MPU-6050
+//! let mut buf = [0u8; 1];
+//! i2c.write_read(0x68, &[0x75], &mut buf).unwrap();
+//! ```
+//!
+//! # Scanning the Bus
+//! `scan()` returns an iterator over the addresses of all devices which acknowledge
+//! their address.  Each address is probed with an empty write, a start condition
+//! followed directly by a stop:
+//!
+//! ```
+//! for address in i2c.scan() {
+//!     writeln!(serial, "Found device at {:#04x}", address).unwrap();
+//! }
+//! ```
+//!
+//! Only the addresses `0x08` to `0x77` are probed, `0x00`-`0x07` and `0x78`-`0x7f` are
+//! reserved by the I2C specification.  Some devices misbehave when they see an
+//! empty write, for example by starting a conversion or holding the bus, so only
+//! scan buses whose devices you know.
+use atmega32u4;
+use clock;
+use core::marker;
+use delay;
+use hal;
+use port;
+use port::mode::io::Input;
+
+// TWCR
+const TWINT: u8 = 1 << 7;
+const TWEA: u8 = 1 << 6;
+const TWSTA: u8 = 1 << 5;
+const TWSTO: u8 = 1 << 4;
+const TWEN: u8 = 1 << 2;
+
+// TWSR status codes, prescaler bits masked out
+const STATUS_MASK: u8 = 0xf8;
+const START: u8 = 0x08;
+const REPEATED_START: u8 = 0x10;
+const SLA_W_ACK: u8 = 0x18;
+const SLA_W_NACK: u8 = 0x20;
+const DATA_W_ACK: u8 = 0x28;
+const DATA_W_NACK: u8 = 0x30;
+const ARBITRATION_LOST: u8 = 0x38;
+const SLA_R_ACK: u8 = 0x40;
+const SLA_R_NACK: u8 = 0x48;
+
+/// I2C error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// No device acknowledged the address
+    AddressNack,
+    /// The device didn't acknowledge a data byte
+    DataNack,
+    /// Another master took over the bus
+    ArbitrationLost,
+    /// Unexpected bus state, for example an illegal start or stop condition
+    Bus,
+}
+
+/// I2C master using the TWI peripheral
+///
+/// If `SPEED` is omitted, the speed selected using the `mhz*` features is used.
+pub struct I2c<SDA, SCL, SPEED = delay::Speed> {
+    twi: atmega32u4::TWI,
+    sda: SDA,
+    scl: SCL,
+    _speed: marker::PhantomData<SPEED>,
+}
+
+impl<SDAMODE, SCLMODE, SPEED: clock::Clock>
+    I2c<port::portd::PD1<Input<SDAMODE>>, port::portd::PD0<Input<SCLMODE>>, SPEED>
+{
+    /// Enable the TWI peripheral as a master with the given clock rate
+    ///
+    /// The rate is rounded down to the next possible one.
+    pub fn new(
+        twi: atmega32u4::TWI,
+        sda: port::portd::PD1<Input<SDAMODE>>,
+        scl: port::portd::PD0<Input<SCLMODE>>,
+        frequency: u32,
+    ) -> Self {
+        let frequency = if frequency == 0 { 1 } else { frequency };
+        let divider = (SPEED::FREQ + frequency - 1) / frequency;
+        let twbr = if divider <= 16 {
+            0
+        } else if (divider - 16 + 1) / 2 > 0xff {
+            0xff
+        } else {
+            ((divider - 16 + 1) / 2) as u8
+        };
+
+        // Prescaler of 1
+        twi.twsr.write(|w| unsafe { w.bits(0) });
+        twi.twbr.write(|w| unsafe { w.bits(twbr) });
+        twi.twcr.write(|w| unsafe { w.bits(TWEN) });
+
+        I2c {
+            twi: twi,
+            sda: sda,
+            scl: scl,
+            _speed: marker::PhantomData,
+        }
+    }
+
+    /// Access the raw `TWI` registers
+    ///
+    /// # Safety
+    /// A transfer must not be in progress, and `TWCR` must keep `TWEN` set and `TWIE`
+    /// cleared.
+    pub unsafe fn registers(&mut self) -> &atmega32u4::TWI {
+        &self.twi
+    }
+
+    /// Disable the TWI peripheral and release it together with its pins
+    pub fn free(
+        self,
+    ) -> (
+        atmega32u4::TWI,
+        port::portd::PD1<Input<SDAMODE>>,
+        port::portd::PD0<Input<SCLMODE>>,
+    ) {
+        self.twi.twcr.reset();
+        (self.twi, self.sda, self.scl)
+    }
+}
+
+impl<SDA, SCL, SPEED> I2c<SDA, SCL, SPEED> {
+    /// Iterate over the addresses of all devices on the bus
+    ///
+    /// See the [module documentation](index.html#scanning-the-bus).
+    pub fn scan(&mut self) -> Scan<SDA, SCL, SPEED> {
+        Scan {
+            i2c: self,
+            next: 0x08,
+        }
+    }
+
+    fn command(&mut self, twcr: u8) -> u8 {
+        self.twi.twcr.write(|w| unsafe { w.bits(twcr | TWINT | TWEN) });
+        while self.twi.twcr.read().bits() & TWINT == 0 {}
+        self.twi.twsr.read().bits() & STATUS_MASK
+    }
+
+    /// Send a (repeated) start condition and the address
+    fn start(&mut self, address: u8, read: bool) -> Result<(), Error> {
+        match self.command(TWSTA) {
+            START | REPEATED_START => (),
+            ARBITRATION_LOST => return Err(Error::ArbitrationLost),
+            _ => return Err(Error::Bus),
+        }
+
+        let sla = (address << 1) | read as u8;
+        self.twi.twdr.write(|w| unsafe { w.bits(sla) });
+        match self.command(0) {
+            SLA_W_ACK | SLA_R_ACK => Ok(()),
+            SLA_W_NACK | SLA_R_NACK => Err(Error::AddressNack),
+            ARBITRATION_LOST => Err(Error::ArbitrationLost),
+            _ => Err(Error::Bus),
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        for &byte in bytes {
+            self.twi.twdr.write(|w| unsafe { w.bits(byte) });
+            match self.command(0) {
+                DATA_W_ACK => (),
+                DATA_W_NACK => return Err(Error::DataNack),
+                ARBITRATION_LOST => return Err(Error::ArbitrationLost),
+                _ => return Err(Error::Bus),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, buffer: &mut [u8]) {
+        let last = buffer.len().saturating_sub(1);
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            // Acknowledge every byte but the last one
+            self.command(if i == last { 0 } else { TWEA });
+            *byte = self.twi.twdr.read().bits();
+        }
+    }
+
+    /// Send a stop condition, or only release the bus if it was lost
+    fn stop(&mut self, result: Result<(), Error>) -> Result<(), Error> {
+        if result == Err(Error::ArbitrationLost) {
+            self.twi.twcr.write(|w| unsafe { w.bits(TWINT | TWEN) });
+        } else {
+            self.twi.twcr.write(|w| unsafe { w.bits(TWINT | TWEN | TWSTO) });
+            while self.twi.twcr.read().bits() & TWSTO != 0 {}
+        }
+        result
+    }
+}
+
+impl<SDA, SCL, SPEED> hal::blocking::i2c::Write for I2c<SDA, SCL, SPEED> {
+    type Error = Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        let result = self.start(address, false).and_then(|_| self.write_bytes(bytes));
+        self.stop(result)
+    }
+}
+
+impl<SDA, SCL, SPEED> hal::blocking::i2c::Read for I2c<SDA, SCL, SPEED> {
+    type Error = Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        let result = self.start(address, true).map(|_| self.read_bytes(buffer));
+        self.stop(result)
+    }
+}
+
+impl<SDA, SCL, SPEED> hal::blocking::i2c::WriteRead for I2c<SDA, SCL, SPEED> {
+    type Error = Error;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        let result = self
+            .start(address, false)
+            .and_then(|_| self.write_bytes(bytes))
+            .and_then(|_| self.start(address, true))
+            .map(|_| self.read_bytes(buffer));
+        self.stop(result)
+    }
+}
+
+/// Iterator over the devices on a bus
+///
+/// Created using [I2c::scan].
+pub struct Scan<'a, SDA: 'a, SCL: 'a, SPEED: 'a> {
+    i2c: &'a mut I2c<SDA, SCL, SPEED>,
+    next: u8,
+}
+
+impl<'a, SDA, SCL, SPEED> Iterator for Scan<'a, SDA, SCL, SPEED> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        while self.next <= 0x77 {
+            let address = self.next;
+            self.next += 1;
+
+            let result = self.i2c.start(address, false);
+            if self.i2c.stop(result).is_ok() {
+                return Some(address);
+            }
+        }
+        None
+    }
+}
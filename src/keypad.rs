@@ -0,0 +1,207 @@
+//! Keypad Matrix
+//!
+//! A [Matrix] scans a keypad with up to 8 columns and 8 rows.  Each column is pulled
+//! low in turn while the rows, inputs with pull-ups, are read.  A row reading low
+//! means the key at this row and column is pressed.  The result is a [Keys] value with
+//! one bit per key.
+//!
+//! Columns and rows are arrays (or slices) of pins.  Downgrade the pins to store pins
+//! of different ports in one array.  Const generics are not available, so the size of
+//! the matrix is only known at runtime.
+//!
+//! # Columns
+//! Idle columns are driven high.  If two keys in the same row are pressed, a push-pull
+//! column driven high is shorted to the active one driven low.  Use open-drain
+//! columns (`into_open_drain_output()`) to prevent this, or diodes in series with the
+//! keys.
+//!
+//! # Example
+//! A 4x3 phone keypad, with columns on `PD4`, `PD6`, `PD7` and rows on `PB4`-`PB7`:
+//!
+//! ```
+//! let mut keypad = keypad::Matrix::new(
+//!     [
+//!         portd.pd4.into_open_drain_output(&mut portd.ddr).downgrade(),
+//!         portd.pd6.into_open_drain_output(&mut portd.ddr).downgrade(),
+//!         portd.pd7.into_open_drain_output(&mut portd.ddr).downgrade(),
+//!     ],
+//!     [
+//!         portb.pb4.into_pull_up_input(&mut portb.ddr).downgrade(),
+//!         portb.pb5.into_pull_up_input(&mut portb.ddr).downgrade(),
+//!         portb.pb6.into_pull_up_input(&mut portb.ddr).downgrade(),
+//!         portb.pb7.into_pull_up_input(&mut portb.ddr).downgrade(),
+//!     ],
+//! );
+//!
+//! const LABELS: [[char; 3]; 4] = [
+//!     ['1', '2', '3'],
+//!     ['4', '5', '6'],
+//!     ['7', '8', '9'],
+//!     ['*', '0', '#'],
+//! ];
+//!
+//! for (row, col) in keypad.scan(&mut delay).iter() {
+//!     let label = LABELS[row as usize][col as usize];
+//! }
+//! ```
+//!
+//! `scan()` does not debounce.  Scan every few milliseconds and only accept a key once
+//! it was reported in several scans in a row, like the [debounce] module does for
+//! single pins.
+use hal::blocking::delay::DelayUs;
+use hal::digital::v2::{InputPin, OutputPin};
+
+/// Time for a row to follow its column, in microseconds
+const SETTLE_US: u8 = 5;
+
+/// Keypad matrix of column outputs and row inputs
+pub struct Matrix<COLS, ROWS> {
+    cols: COLS,
+    rows: ROWS,
+}
+
+impl<COLS, ROWS> Matrix<COLS, ROWS> {
+    /// Create a matrix from its column and row pins
+    ///
+    /// All columns are driven high.  Only the first 8 columns and rows are used.
+    pub fn new<C>(mut cols: COLS, rows: ROWS) -> Matrix<COLS, ROWS>
+    where
+        COLS: AsMut<[C]>,
+        C: OutputPin,
+    {
+        for col in cols.as_mut().iter_mut() {
+            col.set_high().ok();
+        }
+
+        Matrix {
+            cols: cols,
+            rows: rows,
+        }
+    }
+
+    /// Read which keys are pressed
+    ///
+    /// After pulling a column low, the rows are given 5µs to settle before they are
+    /// read.  Long wires or weak pull-ups might need more, in that case use external
+    /// pull-ups on the rows.
+    pub fn scan<C, R, D>(&mut self, delay: &mut D) -> Keys
+    where
+        COLS: AsMut<[C]>,
+        C: OutputPin,
+        ROWS: AsRef<[R]>,
+        R: InputPin,
+        D: DelayUs<u8>,
+    {
+        let mut keys = 0u64;
+
+        for (c, col) in self.cols.as_mut().iter_mut().take(8).enumerate() {
+            col.set_low().ok();
+            delay.delay_us(SETTLE_US);
+
+            for (r, row) in self.rows.as_ref().iter().take(8).enumerate() {
+                if row.is_low().unwrap_or(false) {
+                    keys |= 1 << (r * 8 + c);
+                }
+            }
+
+            col.set_high().ok();
+        }
+
+        Keys(keys)
+    }
+
+    /// Release the column and row pins
+    pub fn free(self) -> (COLS, ROWS) {
+        (self.cols, self.rows)
+    }
+}
+
+/// Pressed keys of a [Matrix]
+///
+/// Bit `row * 8 + col` is set for each pressed key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Keys(u64);
+
+impl Keys {
+    /// Whether the key at `row` and `col` is pressed
+    pub fn is_pressed(&self, row: u8, col: u8) -> bool {
+        row < 8 && col < 8 && self.0 & (1 << (row * 8 + col)) != 0
+    }
+
+    /// Whether no key is pressed
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Raw value, one bit per key
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Iterate over the `(row, col)` of all pressed keys
+    pub fn iter(&self) -> KeysIter {
+        KeysIter { keys: self.0 }
+    }
+}
+
+/// Iterator over the pressed keys
+///
+/// Created using [Keys::iter].  Keys are returned row by row.
+pub struct KeysIter {
+    keys: u64,
+}
+
+impl Iterator for KeysIter {
+    type Item = (u8, u8);
+
+    fn next(&mut self) -> Option<(u8, u8)> {
+        if self.keys == 0 {
+            return None;
+        }
+
+        let bit = self.keys.trailing_zeros() as u8;
+        self.keys &= self.keys - 1;
+        Some((bit / 8, bit % 8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_keys() {
+        let keys = Keys(0);
+        assert!(keys.is_empty());
+        assert_eq!(keys.iter().next(), None);
+    }
+
+    #[test]
+    fn keys_row_by_row() {
+        // (3, 2), (0, 5) and (0, 1)
+        let keys = Keys((1 << 26) | (1 << 5) | (1 << 1));
+        let mut iter = keys.iter();
+        assert_eq!(iter.next(), Some((0, 1)));
+        assert_eq!(iter.next(), Some((0, 5)));
+        assert_eq!(iter.next(), Some((3, 2)));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn all_keys() {
+        let keys = Keys(!0);
+        assert_eq!(keys.iter().count(), 64);
+        for (i, (row, col)) in keys.iter().enumerate() {
+            assert_eq!((row, col), (i as u8 / 8, i as u8 % 8));
+            assert!(keys.is_pressed(row, col));
+        }
+        assert_eq!(keys.iter().last(), Some((7, 7)));
+    }
+
+    #[test]
+    fn out_of_range_keys() {
+        let keys = Keys(!0);
+        assert!(!keys.is_pressed(8, 0));
+        assert!(!keys.is_pressed(0, 8));
+    }
+}
@@ -0,0 +1,80 @@
+//! Arduino Leonardo pinout
+//!
+//! Pin names as printed on the Arduino Leonardo (and Arduino Micro) board.  Enable
+//! the `leonardo` feature to use this module.
+//!
+//! | Arduino | Pin   | Notes                  |
+//! |---------|-------|------------------------|
+//! | `D0`    | `PD2` | `RX`                   |
+//! | `D1`    | `PD3` | `TX`                   |
+//! | `D2`    | `PD1` | `SDA`                  |
+//! | `D3`    | `PD0` | `SCL`, PWM             |
+//! | `D4`    | `PD4` |                        |
+//! | `D5`    | `PC6` | PWM                    |
+//! | `D6`    | `PD7` | PWM                    |
+//! | `D7`    | `PE6` |                        |
+//! | `D8`    | `PB4` |                        |
+//! | `D9`    | `PB5` | PWM                    |
+//! | `D10`   | `PB6` | PWM                    |
+//! | `D11`   | `PB7` | PWM                    |
+//! | `D12`   | `PD6` |                        |
+//! | `D13`   | `PC7` | PWM, on-board LED      |
+//! | `A0`    | `PF7` |                        |
+//! | `A1`    | `PF6` |                        |
+//! | `A2`    | `PF5` |                        |
+//! | `A3`    | `PF4` |                        |
+//! | `A4`    | `PF1` |                        |
+//! | `A5`    | `PF0` |                        |
+//! | `RXLED` | `PB0` | Active low             |
+//! | `TXLED` | `PD5` | Active low             |
+//! | `SCK`   | `PB1` | ICSP header            |
+//! | `MOSI`  | `PB2` | ICSP header            |
+//! | `MISO`  | `PB3` | ICSP header            |
+//!
+//! USB (`D+`, `D-`, `VBUS`) and the 16MHz crystal use dedicated pins, so all
+//! GPIOs are available.  `PE2` (`HWB`) is tied to GND and not accessible.
+//!
+//! # Example
+//! [Board] splits all peripherals and creates a [delay::Delay] for the 16MHz clock:
+//!
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut board = atmega32u4_hal::leonardo::Board::new(dp);
+//!
+//! let mut led = board.pins.d13.into_led(&mut board.pins.ddr);
+//! loop {
+//!     led.toggle().unwrap();
+//!     board.delay.delay_ms(500u16);
+//! }
+//! ```
+//!
+//! Or, to only use the ports:
+//!
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//!
+//! let mut pins = atmega32u4_hal::leonardo::Pins::new(
+//!     dp.PORTB,
+//!     dp.PORTC,
+//!     dp.PORTD,
+//!     dp.PORTE,
+//!     dp.PORTF,
+//! );
+//!
+//! let mut led = pins.d13.into_led(&mut pins.ddr);
+//! led.set_high().unwrap();
+//! ```
+use atmega32u4;
+use delay;
+use port;
+
+pub use board::IntoLed;
+
+leonardo_pinout! {
+    /// Pins of the Arduino Leonardo
+    pins: Pins,
+    /// Arduino Leonardo with all peripherals
+    ///
+    /// The Leonardo runs at 16MHz.
+    board: Board,
+}
@@ -9,14 +9,75 @@
 //!   Examples can be found in the [port] module.
 //! * Port PWM: Using the 4 builtin timers, pwm can be configured for a few
 //!   pins.  For more info, take a look at the [timer] module.
+//! * Software PWM: Dimming any output pin from a timer interrupt.  Examples in the
+//!   [softpwm] module.
+//! * Tones: Square waves for a buzzer using the 16-bit timers.  Examples in the
+//!   [timer] module.
+//! * Servos: Hobby servo control using the 16-bit timers.  Examples in the [timer]
+//!   module.
+//! * Input Capture: Measuring periods and pulse widths using the 16-bit timers.
+//!   Examples in the [timer] module.
 //! * Delay: Delay using a busy loop.  Implementation taken from the ArduinoCore
 //!   library. Examples in the [delay] module.
+//! * Timer Delay: Delay using a 16-bit timer.  Examples in the [timer] module.
+//! * Periodic Interrupts: Timebase using the overflow interrupt of a 16-bit timer.
+//!   Examples in the [timer] module.
+//! * Timekeeping: `millis()` and `micros()` using Timer0.  Examples in the [time] module.
+//! * Debouncing: Debounced button inputs.  Examples in the [debounce] module.
+//! * Keypads: Scanning a matrix keypad.  Examples in the [keypad] module.
+//! * ADC: Reading analog values from the `PORTF` pins.  Examples in the [adc] module.
+//! * Analog Comparator: Fast threshold detection on `AIN0`.  Examples in the [ac]
+//!   module.
+//! * EEPROM: Reading and writing the internal EEPROM.  Examples in the [eeprom] module.
+//! * Watchdog: Watchdog timer using `embedded-hal` traits.  Examples in the [wdt] module.
+//! * External Interrupts: Configuring `INT0`-`INT3` and `INT6`.  Examples in the
+//!   [exint] module.
+//! * Pin Change Interrupts: `PCINT0` for the pins of `PORTB`.  Examples in the
+//!   [pcint] module.
+//! * Steppers: Four coil and step/direction stepper motors.  Examples in the
+//!   [stepper] module.
+//! * Encoders: Quadrature encoders on `PORTB` using `PCINT0`.  Examples in the
+//!   [encoder] module.
+//! * Clock: Changing the system clock prescaler at runtime.  Examples in the
+//!   [clock] module.
+//! * Reset Cause: Finding out why the device was reset.  Examples in the [reset]
+//!   module.
+//! * Supply Voltage: Measuring `VCC` to act before a brown-out.  Examples in the
+//!   [power] module.
+//! * Serial: `USART1` with an optional interrupt driven transmit buffer.  Examples
+//!   in the [serial] module.
+//! * Panic Output: Printing panic messages to `USART1` before resetting.  Examples in
+//!   the [panic] module.
+//! * SPI: SPI master using `embedded-hal` traits.  Examples in the [spi] module.
+//! * I2C: I2C master with a bus scanner using `embedded-hal` traits.  Examples in the
+//!   [i2c] module.
+//! * USB: `usb-device` bus implementation for the native USB controller (`usb-device`
+//!   feature).  Examples in the [usb] module.
+//!
+//! ## Boards
+//! Board support modules provide the pins under the names printed on the board,
+//! and a `Board::new()` which splits all peripherals and creates a matching delay:
+//!
+//! * Arduino Leonardo: [leonardo] module (`leonardo` feature)
+//! * Arduino Micro: [micro] module (`micro` feature)
+//! * SparkFun Pro Micro: [promicro] module (`promicro` feature)
+//!
+//! ## Raw Register Access
+//! Drivers which own a peripheral have an unsafe `registers()` method, which gives
+//! access to the raw registers of the [atmega32u4] crate.  It allows using a feature
+//! this crate doesn't support yet without releasing the driver using `free()`.  The
+//! caller has to make sure not to change anything the driver relies on, the
+//! `# Safety` section of each method lists what that is.
 //!
 //! ## Easy Globals
 //! Because a lot of times you need to exchange data between your application code
 //! and interrupt handlers, this crate contains a safe abstraction for globals.  While
 //! a global is accessed interrupts are disabled, so you don't need to worry about
-//! data races.  For more info, take a look at the [global] module.
+//! data races.  For more info, take a look at the [global] module.  Simple flags and
+//! counters fit into a [GlobalCell], which needs neither an `Option` nor a closure.
+//!
+//! If you prefer the `Mutex<RefCell<T>>` pattern known from `cortex-m`, or want to
+//! access several globals in one critical section, use the [mutex] module instead.
 #![feature(asm, const_fn)]
 #![cfg_attr(feature = "docs", feature(extern_prelude))]
 #![no_std]
@@ -24,11 +85,48 @@
 
 pub extern crate embedded_hal as hal;
 extern crate atmega32u4;
+extern crate nb;
+#[cfg(feature = "ufmt")]
+extern crate ufmt;
+#[cfg(feature = "usb-device")]
+extern crate usb_device;
 
+#[macro_use]
 pub mod port;
 pub mod delay;
 pub mod prelude;
+pub mod debounce;
+pub mod keypad;
 pub mod timer;
+pub mod softpwm;
+pub mod time;
+pub mod adc;
+pub mod ac;
+pub mod eeprom;
+pub mod wdt;
+pub mod exint;
+pub mod pcint;
+pub mod encoder;
+pub mod stepper;
+pub mod clock;
+pub mod reset;
+pub mod power;
+pub mod serial;
+pub mod panic;
+pub mod spi;
+pub mod i2c;
+#[cfg(feature = "usb-device")]
+pub mod usb;
+
+#[macro_use]
+mod board;
+#[cfg(feature = "leonardo")]
+pub mod leonardo;
+#[cfg(feature = "micro")]
+pub mod micro;
+#[cfg(feature = "promicro")]
+pub mod promicro;
 
 pub mod global;
-pub use global::Global;
+pub use global::{Global, GlobalCell};
+pub mod mutex;
@@ -0,0 +1,99 @@
+//! Critical sections and mutexes
+//!
+//! An alternative to [Global] for users who know the `Mutex<RefCell<Option<T>>>`
+//! pattern from `cortex-m`.  A [Mutex] can only be accessed with a [CriticalSection]
+//! token, which only exists inside a [free] closure.  Because interrupts are disabled
+//! for the whole closure, several mutexes can be accessed in one critical section,
+//! instead of each `Global::get` disabling interrupts on its own.
+//!
+//! `Mutex` only hands out shared references.  For mutable access, wrap the value in
+//! a `RefCell` (or a `Cell` for `Copy` types).
+//!
+//! # Example
+//! ```
+//! use core::cell::{Cell, RefCell};
+//! use atmega32u4_hal::mutex::{self, Mutex};
+//!
+//! static COUNTER: Mutex<Cell<u16>> = Mutex::new(Cell::new(0));
+//! static LED: Mutex<RefCell<Option<leonardo::Led>>> = Mutex::new(RefCell::new(None));
+//!
+//! fn main() {
+//!     let dp = atmega32u4::Peripherals::take().unwrap();
+//!     let mut pins = leonardo::Pins::new(dp.PORTB, dp.PORTC, dp.PORTD, dp.PORTE, dp.PORTF);
+//!     let led = pins.d13.into_led(&mut pins.ddr);
+//!
+//!     mutex::free(|cs| {
+//!         *LED.borrow(cs).borrow_mut() = Some(led);
+//!     });
+//!
+//!     loop { }
+//! }
+//!
+//! interrupt!(INT1, int1_isr);
+//! fn int1_isr() {
+//!     mutex::free(|cs| {
+//!         // Both globals in a single critical section
+//!         let count = COUNTER.borrow(cs);
+//!         count.set(count.get() + 1);
+//!
+//!         if let Some(ref mut led) = *LED.borrow(cs).borrow_mut() {
+//!             led.toggle().unwrap();
+//!         }
+//!     });
+//! }
+//! ```
+//!
+//! [Global]: ../global/struct.Global.html
+use atmega32u4;
+use core::cell;
+
+/// Token proving that interrupts are disabled
+///
+/// Only available inside a [free] closure.
+pub struct CriticalSection {
+    _private: (),
+}
+
+/// Execute `f` with interrupts disabled
+///
+/// `f` gets a [CriticalSection] token for accessing [Mutex]es.  Interrupts are
+/// restored to their previous state afterwards, so this can be nested.
+pub fn free<R, F: FnOnce(&CriticalSection) -> R>(f: F) -> R {
+    atmega32u4::interrupt::free(|_| f(&CriticalSection { _private: () }))
+}
+
+/// A value which can only be accessed inside a critical section
+pub struct Mutex<T> {
+    value: cell::UnsafeCell<T>,
+}
+
+// Only ever accessed with interrupts disabled on a single core
+unsafe impl<T> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    /// Create a new mutex
+    pub const fn new(value: T) -> Mutex<T> {
+        Mutex {
+            value: cell::UnsafeCell::new(value),
+        }
+    }
+
+    /// Access the value
+    ///
+    /// The reference can't outlive the critical section.
+    pub fn borrow<'cs>(&'cs self, _cs: &'cs CriticalSection) -> &'cs T {
+        unsafe { &*self.value.get() }
+    }
+
+    /// Access the value mutably
+    ///
+    /// This needs no critical section, because `&mut self` guarantees exclusive access.
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.value.get() }
+    }
+
+    /// Move the value out of the mutex
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
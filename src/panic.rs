@@ -0,0 +1,59 @@
+//! Panic Output
+//!
+//! By default a panic just stops the program without a trace.  [serial_panic] prints
+//! the panic message and location to a serial port and resets the device using the
+//! watchdog.  The crate doesn't define a `#[panic_handler]` itself, so you stay free to
+//! choose; call it from your own:
+//!
+//! ```
+//! use core::panic::PanicInfo;
+//!
+//! #[panic_handler]
+//! fn panic(info: &PanicInfo) -> ! {
+//!     // The Serial used by the application is out of reach here, so take over USART1
+//!     let mut serial = unsafe { panic::emergency_serial::<delay::MHz16>(57600) };
+//!     panic::serial_panic(info, &mut serial)
+//! }
+//! ```
+//!
+//! The output looks like `panicked at 'index out of bounds', src/main.rs:12:5`.
+//!
+//! Interrupts are disabled the whole time, so only a blocking [serial::Serial] works
+//! here.  A buffered [serial::Tx] never drains without its interrupt.
+//!
+//! After the reset, the watchdog keeps running with a 16ms timeout, see
+//! [wdt::disable_after_reset].  Combine it with [reset::reset_cause] to find out that
+//! the last run ended in a panic.
+use atmega32u4;
+use clock;
+use core::fmt;
+use core::panic::PanicInfo;
+use hal;
+use nb;
+use serial;
+use wdt;
+
+/// Print the panic message and location, then reset using the watchdog
+///
+/// Interrupts are disabled first.  All bytes are sent completely before the reset.
+pub fn serial_panic<W>(info: &PanicInfo, serial: &mut W) -> !
+where
+    W: fmt::Write + hal::serial::Write<u8>,
+{
+    atmega32u4::interrupt::disable();
+
+    fmt::Write::write_fmt(serial, format_args!("{}\r\n", info)).ok();
+    while let Err(nb::Error::WouldBlock) = hal::serial::Write::flush(serial) {}
+
+    wdt::reset_mcu()
+}
+
+/// Take over `USART1` and initialize it with the given baudrate and 8N1
+///
+/// # Safety
+/// Only call this from a panic handler, where the program won't continue.  Any other
+/// [serial::Serial] or [serial::Tx] using `USART1` must never be used again, and a
+/// byte it was sending is cut off.
+pub unsafe fn emergency_serial<SPEED: clock::Clock>(baudrate: u32) -> serial::Serial<SPEED> {
+    serial::Serial::new(atmega32u4::Peripherals::steal().USART1, baudrate)
+}
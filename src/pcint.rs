@@ -0,0 +1,123 @@
+//! Pin Change Interrupts
+//!
+//! All eight pins of `PORTB` can trigger the `PCINT0` interrupt when their level
+//! changes.  Because they share one vector, the interrupt service routine needs to
+//! figure out which pin changed itself.  To do so, store the last `snapshot()` and
+//! compare it to the current one.
+//!
+//! # Example
+//! ```
+//! static LAST: atmega32u4_hal::Global<u8> = atmega32u4_hal::Global::new();
+//!
+//! fn main() {
+//!     let dp = atmega32u4::Peripherals::take().unwrap();
+//!     let mut portb = dp.PORTB.split();
+//!
+//!     let mut pb3 = portb.pb3.into_pull_up_input(&mut portb.ddr);
+//!     let mut pb4 = portb.pb4.into_pull_up_input(&mut portb.ddr);
+//!
+//!     LAST.set(pcint::snapshot());
+//!     pb3.enable_pcint();
+//!     pb4.enable_pcint();
+//!
+//!     loop { }
+//! }
+//!
+//! interrupt!(PCINT0, pcint0_isr);
+//! fn pcint0_isr() {
+//!     let now = pcint::snapshot();
+//!     LAST.get(|last| {
+//!         let changed = *last ^ now;
+//!         // Bit n is set if PBn changed
+//!         *last = now;
+//!     }).unwrap();
+//! }
+//! ```
+use atmega32u4;
+use port;
+
+const PCIE0: u8 = 1 << 0;
+const PCIF0: u8 = 1 << 0;
+
+/// Enable the `PCINT0` interrupt
+///
+/// Only pins which have been enabled using `enable_pcint()` will trigger it.
+pub fn enable() {
+    atmega32u4::interrupt::free(|_| unsafe {
+        (*atmega32u4::EXINT::ptr())
+            .pcicr
+            .modify(|r, w| w.bits(r.bits() | PCIE0))
+    })
+}
+
+/// Disable the `PCINT0` interrupt for all pins
+pub fn disable() {
+    atmega32u4::interrupt::free(|_| unsafe {
+        (*atmega32u4::EXINT::ptr())
+            .pcicr
+            .modify(|r, w| w.bits(r.bits() & !PCIE0))
+    })
+}
+
+/// Clear a pending `PCINT0` interrupt
+pub fn clear() {
+    // Flags are cleared by writing a one
+    unsafe { (*atmega32u4::EXINT::ptr()).pcifr.write(|w| w.bits(PCIF0)) }
+}
+
+/// Read the current input levels of all `PORTB` pins
+///
+/// Bit n corresponds to `PBn`.
+pub fn snapshot() -> u8 {
+    unsafe { (*atmega32u4::PORTB::ptr()).pin.read().bits() }
+}
+
+/// A `PORTB` input pin which can trigger `PCINT0`
+pub trait PcintPin {
+    /// Bit of this pin in `PINB` and `PCMSK0`
+    const MASK: u8;
+}
+
+macro_rules! pcint_impl {
+    ($($PIN:ident: $i:expr,)+) => {
+        $(
+            impl<MODE> PcintPin for port::portb::$PIN<port::mode::io::Input<MODE>> {
+                const MASK: u8 = 1 << $i;
+            }
+
+            impl<MODE> port::portb::$PIN<port::mode::io::Input<MODE>> {
+                /// Let this pin trigger the `PCINT0` interrupt
+                ///
+                /// This also enables `PCINT0` itself.
+                pub fn enable_pcint(&mut self) {
+                    atmega32u4::interrupt::free(|_| unsafe {
+                        (*atmega32u4::EXINT::ptr())
+                            .pcmsk0
+                            .modify(|r, w| w.bits(r.bits() | (1 << $i)))
+                    });
+                    enable();
+                }
+
+                /// Stop this pin from triggering the `PCINT0` interrupt
+                pub fn disable_pcint(&mut self) {
+                    atmega32u4::interrupt::free(|_| unsafe {
+                        (*atmega32u4::EXINT::ptr())
+                            .pcmsk0
+                            .modify(|r, w| w.bits(r.bits() & !(1 << $i)))
+                    });
+                }
+            }
+        )+
+    }
+}
+
+pcint_impl! {
+    PB0: 0,
+    PB1: 1,
+    PB2: 2,
+    PB3: 3,
+    PB4: 4,
+    PB5: 5,
+    PB6: 6,
+    PB7: 7,
+}
@@ -5,27 +5,135 @@
 //! # Design
 //! For each port, you can call `.split()` on the raw periperal to separate the pins.
 //!
-//! By default, each pin is `Input<Floating>`.  There are three methods to change the
+//! By default, each pin is `Input<Floating>`.  There are several methods to change the
 //! mode:
 //! * `into_floating_input()`: Turn a pin into a floating input
 //! * `into_pull_up_input()`: Turn a pin into a pull-up input
-//! * `into_output()`: Turn a pin into an output
+//! * `into_output()`: Turn a pin into an output, keeping the previous PORT level
+//! * `into_output_high()`/`into_output_low()`: Turn a pin into an output with a defined
+//!   initial level, without glitching
+//! * `into_open_drain_output()`: Turn a pin into an open-drain output
 //!
-//! For input pins [embedded_hal::digital::InputPin] is implemented, for output
-//! pins [embedded_hal::digital::OutputPin] & [embedded_hal::digital::StatefulOutputPin].
+//! For input pins [embedded_hal::digital::v2::InputPin] is implemented, for output
+//! pins [embedded_hal::digital::v2::OutputPin],
+//! [embedded_hal::digital::v2::StatefulOutputPin] &
+//! [embedded_hal::digital::v2::ToggleableOutputPin].  Toggling uses the hardware's
+//! PIN register write, which flips a single bit atomically.
+//!
+//! This holds for downgraded pins as well.  A concrete pin toggles with a constant
+//! mask, which compiles to a single `out` (plus loading the mask).  Downgraded pins
+//! first shift the mask into place and `Pin` also selects the port, which adds a few
+//! cycles, but the write itself stays atomic.  A read-modify-write of PORT would need
+//! an `in`, an `eor` and an `out`, and could undo a change an interrupt handler made
+//! to another pin of the same port in between.
+//!
+//! The pins implement the fallible `v2` traits.  As GPIO can't fail, the error type is
+//! [core::convert::Infallible].  Drivers which still expect the deprecated `v1` traits
+//! can be used by wrapping a pin in [embedded_hal::digital::v1_compat::OldOutputPin] or
+//! [embedded_hal::digital::v1_compat::OldInputPin].
+//!
+//! ## Open-Drain
+//! Open-drain outputs are implemented by switching the data direction: To drive the
+//! pin low, it is made an output with a low PORT bit.  To release it (`set_high()`),
+//! it is made an input, again with a low PORT bit so the internal pull-up stays off.
+//! This means a pull-up resistor is required to actually get a high level, usually an
+//! external one.  Open-drain pins also implement [embedded_hal::digital::v2::InputPin]
+//! to read back the actual level of the line.
 //!
 //! ## Downgrading
 //! After `.split()` each pin is of a separate type.  This means you can't store them
 //! in an array.  To allow doing so you can `.downgrade()` a pin.  This can be done
-//! twice:  The first downgrade makes the pin generic for its port, the second downgrade
+//! in two steps:  `downgrade_port()` makes the pin generic for its port, `downgrade()`
 //! makes it fully generic.
 //!
-//! *Note*: After downgrading a pin, you can no longer change its mode!
+//! Both are also available as `From` conversions, so functions can accept any pin and
+//! downgrade it themselves:
+//!
+//! ```
+//! fn add_led<P: Into<port::Pin<Output>>>(leds: &mut Leds, pin: P) {
+//!     leds.push(pin.into());
+//! }
+//!
+//! add_led(&mut leds, portc.pc7.into_output(&mut portc.ddr));
+//! add_led(&mut leds, portb.pb0.into_output(&mut portb.ddr).downgrade_port());
+//! ```
+//!
+//! *Note*: After downgrading a pin, you can no longer change its mode!  If you know
+//! which pin it is, `upgrade()` turns it back into the concrete type.  This checks the
+//! pin at runtime and returns it unchanged if it is a different one:
+//!
+//! ```
+//! let pins = [portc.pc6.downgrade(), portc.pc7.downgrade()];
+//! // ...
+//! let [_, pc7] = pins;
+//! let pc7 = pc7.upgrade::<portc::PC7<_>>().unwrap();
+//! ```
+//!
+//! If you need to change the mode of downgraded pins at runtime, turn the fully
+//! generic pin into a [DynamicPin] using `into_dynamic()`.  It tracks the current mode in a field and allows switching
+//! between input and output using `make_output()` and `make_input()`.
+//!
+//! Concrete pins can be turned into a [DynamicPin] directly using `into_dynamic()`.
+//! [embedded_hal::digital::v2::IoPin] is implemented as well, so drivers which switch a
+//! line between input and output can take a `DynamicPin` without knowing about AVR.
+//! For open-drain buses like 1-Wire, `set_low_output()` and `release_to_input()`
+//! switch between driving the line low and releasing it to the pull-up.
+//!
+//! ## Pull-Ups
+//! The internal pull-up of an input pin can also be switched at runtime using
+//! `set_pull_up()`, for example to disable it before going to sleep.  This is only
+//! available for inputs.
+//!
+//! ## Whole Port Access
+//! For parallel interfaces, a whole port can be read or written at once using
+//! `read_port()` and `write_port()` on the port's `DDR`.  `snapshot()` returns the
+//! input levels as a [PortInput], which can be queried for single pins:
+//!
+//! ```
+//! let input = portd.ddr.snapshot();
+//! if input.is_high(2) && input.is_low(3) {
+//!     // PD2 and PD3 were sampled at the same time
+//! }
+//! ```
+//!
+//! To switch several outputs in one go, for example the coils of a stepper motor,
+//! use `set_bits()` and `clear_bits()`.  All writes happen with interrupts disabled,
+//! so they can't race with an ISR changing other pins of the same port:
+//!
+//! ```
+//! // PB4 and PB5 high, PB6 and PB7 low
+//! portb.ddr.set_bits(0b0011_0000);
+//! portb.ddr.clear_bits(0b1100_0000);
+//! ```
+//!
+//! If the pins of a parallel interface are spread over several ports, bundle their
+//! downgraded versions into a [Bus] instead.
+//!
+//! ## All Pins at Once
+//! If you are not using a board module, [all] splits all five ports and returns every
+//! pin under its hardware name, together with a `ddr` that works for all of them:
+//!
+//! ```
+//! let mut pins = port::all(dp.PORTB, dp.PORTC, dp.PORTD, dp.PORTE, dp.PORTF);
+//! let mut led = pins.pc7.into_output(&mut pins.ddr);
+//! let button = pins.pd2.into_pull_up_input(&mut pins.ddr);
+//! ```
 //!
 //! ## PWM
 //! Some pins can be configured to output a PWM signal.  This is not implemented in the port
 //! module but in the [timer] module.
 //!
+//! ## `PORTE`
+//! Only two pins of `PORTE` exist, both with a special role:
+//!
+//! * `PE2` is `HWB`.  If the `HWBE` fuse is set, pulling it low during reset starts
+//!   the bootloader.  Boards like the Leonardo tie it to GND for that reason, so
+//!   check the schematic before using it as a GPIO.  After reset, it works like any
+//!   other pin.
+//! * `PE6` is `INT6`, the only external interrupt on `PORTE`, and `AIN0`, the
+//!   positive input of the analog comparator.  See `into_interrupt()` in the [exint]
+//!   module and the [ac] module.
+//!
 //! # Example
 //! ```
 //! // Get the raw peripherals
@@ -36,12 +144,14 @@
 //! let mut pc7 = portc.pc7.into_output(&mut portc.ddr);
 //!
 //! // Use the pin
-//! pc7.set_high();
-//! pc7.set_low();
+//! pc7.set_high().unwrap();
+//! pc7.set_low().unwrap();
 //! ```
 use atmega32u4;
 use hal::digital;
 use core::marker;
+use core::convert::{Infallible, TryFrom};
+use core::fmt;
 
 
 /// A splittable port
@@ -53,6 +163,33 @@ pub trait PortExt {
     fn split(self) -> Self::Parts;
 }
 
+/// Input levels of a whole port, sampled at one instant
+///
+/// Created using `snapshot()` on a port's `DDR`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PortInput(u8);
+
+impl PortInput {
+    /// Whether pin `index` was high
+    ///
+    /// A port only has pins 0 to 7, for any other `index` this is `false`.
+    pub fn is_high(&self, index: u8) -> bool {
+        index < 8 && self.0 & (1 << index) != 0
+    }
+
+    /// Whether pin `index` was low
+    ///
+    /// Like `is_high()`, this is `false` for an `index` past pin 7.
+    pub fn is_low(&self, index: u8) -> bool {
+        index < 8 && !self.is_high(index)
+    }
+
+    /// Raw value, bit n corresponds to pin n
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+}
+
 /// Pin modes
 pub mod mode {
     use core::marker;
@@ -77,11 +214,21 @@ pub mod mode {
         /// Floating Input
         pub struct Floating;
 
+        /// Open-Drain Output
+        ///
+        /// The pin is either actively pulled low or released (high impedance).
+        pub struct OpenDrain;
+
         impl<MODE> super::Io for Input<MODE> {}
         impl super::Io for Output {}
+        impl super::Io for OpenDrain {}
     }
 
     /// Pulse Width Modulated Output
+    ///
+    /// `TIMER` is the timer driving this pin, for example
+    /// [Timer4Pwm](../../timer/struct.Timer4Pwm.html).  Pins in this mode can only be
+    /// created by the timer's `into_pwm()`.
     pub struct Pwm<TIMER> {
         pub(crate) _tim: marker::PhantomData<TIMER>,
     }
@@ -94,6 +241,7 @@ macro_rules! port_impl {
         /// Port Types
         pub mod $portx {
             use core::marker;
+            use core::convert::{Infallible, TryFrom};
 
             use atmega32u4;
             use hal::digital;
@@ -140,55 +288,231 @@ macro_rules! port_impl {
                 }
             }
 
+            impl DDR {
+                /// Read the input levels of the whole port at once
+                ///
+                /// Bit n corresponds to pin n.  Pins which have been moved out are
+                /// included as well, as they share the same hardware register.
+                pub fn read_port(&self) -> u8 {
+                    unsafe { (*atmega32u4::$PORTX::ptr()).pin.read().bits() }
+                }
+
+                /// Take a snapshot of the input levels of the whole port
+                ///
+                /// All pins are sampled at the same instant with a single register
+                /// read.
+                pub fn snapshot(&self) -> super::PortInput {
+                    super::PortInput(self.read_port())
+                }
+
+                /// Write multiple pins of this port at once
+                ///
+                /// Only bits which are set in `mask` *and* configured as outputs are
+                /// changed, all other pins keep their state.  The write happens with
+                /// interrupts disabled so it can't race with an ISR modifying the same
+                /// port.
+                pub fn write_port(&mut self, mask: u8, value: u8) {
+                    atmega32u4::interrupt::free(|_| {
+                        let port = unsafe { &*atmega32u4::$PORTX::ptr() };
+                        let mask = mask & port.ddr.read().bits();
+
+                        port.port.modify(|r, w| unsafe {
+                            w.bits((r.bits() & !mask) | (value & mask))
+                        })
+                    })
+                }
+
+                /// Drive all output pins in `mask` high at once
+                ///
+                /// Like `write_port()`, this is atomic with respect to interrupts and
+                /// only affects outputs.
+                #[inline(always)]
+                pub fn set_bits(&mut self, mask: u8) {
+                    self.write_port(mask, 0xff);
+                }
+
+                /// Drive all output pins in `mask` low at once
+                ///
+                /// Like `write_port()`, this is atomic with respect to interrupts and
+                /// only affects outputs.
+                #[inline(always)]
+                pub fn clear_bits(&mut self, mask: u8) {
+                    self.write_port(mask, 0x00);
+                }
+            }
+
             /// Generalized pin
             pub struct $PXx<MODE> {
                 i: u8,
                 _mode: marker::PhantomData<MODE>,
             }
 
-            impl digital::OutputPin for $PXx<mode::io::Output> {
-                fn set_high(&mut self) {
+            impl<MODE> $PXx<MODE> {
+                /// Turn this pin back into its concrete type
+                ///
+                /// Fails and returns the pin unchanged if it isn't pin `P`, for example
+                /// `pin.upgrade::<PC7<_>>()`.
+                pub fn upgrade<P>(self) -> Result<P, $PXx<MODE>>
+                where
+                    P: TryFrom<$PXx<MODE>, Error = $PXx<MODE>>,
+                {
+                    P::try_from(self)
+                }
+
+                /// Downgrade this pin further into a fully generic pin
+                pub fn downgrade(self) -> super::Pin<MODE> {
+                    super::Pin {
+                        i: self.i,
+                        port: super::Port::$PortEnum,
+                        _mode: marker::PhantomData,
+                    }
+                }
+            }
+
+            impl<MODE> From<$PXx<MODE>> for super::Pin<MODE> {
+                fn from(pin: $PXx<MODE>) -> super::Pin<MODE> {
+                    pin.downgrade()
+                }
+            }
+
+            impl digital::v2::OutputPin for $PXx<mode::io::Output> {
+                type Error = Infallible;
+
+                fn set_high(&mut self) -> Result<(), Self::Error> {
                     unsafe {
                         (*atmega32u4::$PORTX::ptr())
                             .port.modify(|r, w| w.bits(r.bits() | (1 << self.i)))
                     }
+                    Ok(())
                 }
 
-                fn set_low(&mut self) {
+                fn set_low(&mut self) -> Result<(), Self::Error> {
                     unsafe {
                         (*atmega32u4::$PORTX::ptr())
                             .port.modify(|r, w| w.bits(r.bits() & !(1 << self.i)))
                     }
+                    Ok(())
                 }
             }
 
-            impl digital::StatefulOutputPin for $PXx<mode::io::Output> {
-                fn is_set_high(&self) -> bool {
-                    (unsafe {
+            impl digital::v2::StatefulOutputPin for $PXx<mode::io::Output> {
+                fn is_set_high(&self) -> Result<bool, Self::Error> {
+                    Ok((unsafe {
                         (*atmega32u4::$PORTX::ptr()).port.read().bits()
-                    } & (1 << self.i)) != 0
+                    } & (1 << self.i)) != 0)
                 }
 
-                fn is_set_low(&self) -> bool {
-                    (unsafe {
+                fn is_set_low(&self) -> Result<bool, Self::Error> {
+                    Ok((unsafe {
                         (*atmega32u4::$PORTX::ptr()).port.read().bits()
-                    } & (1 << self.i)) == 0
+                    } & (1 << self.i)) == 0)
+                }
+            }
+
+            impl digital::v2::ToggleableOutputPin for $PXx<mode::io::Output> {
+                type Error = Infallible;
+
+                /// Toggle the pin
+                ///
+                /// Writes the runtime mask to the PIN register, like the concrete pins.
+                fn toggle(&mut self) -> Result<(), Self::Error> {
+                    unsafe {
+                        (*atmega32u4::$PORTX::ptr())
+                            .pin.write(|w| w.bits(1 << self.i))
+                    }
+                    Ok(())
                 }
             }
 
-            impl digital::toggleable::Default for $PXx<mode::io::Output> { }
+            impl<MODE> $PXx<mode::io::Input<MODE>> {
+                /// Read the level of this input
+                ///
+                /// `true` means high.  Unlike `is_high()`, this can't fail.
+                pub fn read(&self) -> bool {
+                    let pin = unsafe { (*atmega32u4::$PORTX::ptr()).pin.read().bits() };
+                    pin & (1 << self.i) != 0
+                }
 
-            impl<MODE> digital::InputPin for $PXx<mode::io::Input<MODE>> {
-                fn is_high(&self) -> bool {
-                    (unsafe {
+                /// Enable or disable the internal pull-up of this input
+                ///
+                /// This does not change the pin's type, so an `Input<Floating>` pin might
+                /// have its pull-up enabled afterwards.
+                pub fn set_pull_up(&mut self, enable: bool) {
+                    unsafe {
+                        (*atmega32u4::$PORTX::ptr()).port.modify(|r, w| if enable {
+                            w.bits(r.bits() | (1 << self.i))
+                        } else {
+                            w.bits(r.bits() & !(1 << self.i))
+                        })
+                    }
+                }
+            }
+
+            impl digital::v2::OutputPin for $PXx<mode::io::OpenDrain> {
+                type Error = Infallible;
+
+                fn set_high(&mut self) -> Result<(), Self::Error> {
+                    unsafe {
+                        (*atmega32u4::$PORTX::ptr())
+                            .ddr.modify(|r, w| w.bits(r.bits() & !(1 << self.i)))
+                    }
+                    Ok(())
+                }
+
+                fn set_low(&mut self) -> Result<(), Self::Error> {
+                    unsafe {
+                        (*atmega32u4::$PORTX::ptr())
+                            .ddr.modify(|r, w| w.bits(r.bits() | (1 << self.i)))
+                    }
+                    Ok(())
+                }
+            }
+
+            impl digital::v2::StatefulOutputPin for $PXx<mode::io::OpenDrain> {
+                fn is_set_high(&self) -> Result<bool, Self::Error> {
+                    Ok((unsafe {
+                        (*atmega32u4::$PORTX::ptr()).ddr.read().bits()
+                    } & (1 << self.i)) == 0)
+                }
+
+                fn is_set_low(&self) -> Result<bool, Self::Error> {
+                    Ok((unsafe {
+                        (*atmega32u4::$PORTX::ptr()).ddr.read().bits()
+                    } & (1 << self.i)) != 0)
+                }
+            }
+
+            impl digital::v2::toggleable::Default for $PXx<mode::io::OpenDrain> { }
+
+            impl digital::v2::InputPin for $PXx<mode::io::OpenDrain> {
+                type Error = Infallible;
+
+                fn is_high(&self) -> Result<bool, Self::Error> {
+                    Ok((unsafe {
+                        (*atmega32u4::$PORTX::ptr()).pin.read().bits()
+                    } & (1 << self.i)) != 0)
+                }
+
+                fn is_low(&self) -> Result<bool, Self::Error> {
+                    Ok((unsafe {
+                        (*atmega32u4::$PORTX::ptr()).pin.read().bits()
+                    } & (1 << self.i)) == 0)
+                }
+            }
+
+            impl<MODE> digital::v2::InputPin for $PXx<mode::io::Input<MODE>> {
+                type Error = Infallible;
+
+                fn is_high(&self) -> Result<bool, Self::Error> {
+                    Ok((unsafe {
                         (*atmega32u4::$PORTX::ptr()).pin.read().bits()
-                    } & (1 << self.i)) != 0
+                    } & (1 << self.i)) != 0)
                 }
 
-                fn is_low(&self) -> bool {
-                    (unsafe {
+                fn is_low(&self) -> Result<bool, Self::Error> {
+                    Ok((unsafe {
                         (*atmega32u4::$PORTX::ptr()).pin.read().bits()
-                    } & (1 << self.i)) == 0
+                    } & (1 << self.i)) == 0)
                 }
             }
 
@@ -227,6 +551,44 @@ macro_rules! port_impl {
                     }
                 }
 
+                impl<MODE> From<$PXi<MODE>> for $PXx<MODE> {
+                    fn from(pin: $PXi<MODE>) -> $PXx<MODE> {
+                        pin.downgrade_port()
+                    }
+                }
+
+                impl<MODE> From<$PXi<MODE>> for super::Pin<MODE> {
+                    fn from(pin: $PXi<MODE>) -> super::Pin<MODE> {
+                        pin.downgrade()
+                    }
+                }
+
+                impl<MODE> TryFrom<super::Pin<MODE>> for $PXi<MODE> {
+                    type Error = super::Pin<MODE>;
+
+                    /// Fails if the downgraded pin is a different one
+                    fn try_from(pin: super::Pin<MODE>) -> Result<Self, Self::Error> {
+                        if pin.port == super::Port::$PortEnum && pin.i == $i {
+                            Ok($PXi { _mode: marker::PhantomData })
+                        } else {
+                            Err(pin)
+                        }
+                    }
+                }
+
+                impl<MODE> TryFrom<$PXx<MODE>> for $PXi<MODE> {
+                    type Error = $PXx<MODE>;
+
+                    /// Fails if the downgraded pin is a different one
+                    fn try_from(pin: $PXx<MODE>) -> Result<Self, Self::Error> {
+                        if pin.i == $i {
+                            Ok($PXi { _mode: marker::PhantomData })
+                        } else {
+                            Err(pin)
+                        }
+                    }
+                }
+
                 impl<MODE: mode::Io> $PXi<MODE> {
                     /// Turn this pin into a floating input
                     pub fn into_floating_input<D: PortDDR>(
@@ -264,51 +626,231 @@ macro_rules! port_impl {
 
                         $PXi { _mode: marker::PhantomData }
                     }
+
+                    /// Turn this pin into an output which is initially high
+                    ///
+                    /// The PORT bit is set before switching the direction, so the pin
+                    /// never glitches low.
+                    pub fn into_output_high<D: PortDDR>(
+                        self,
+                        ddr: &mut D,
+                    ) -> $PXi<mode::io::Output> {
+                        unsafe {
+                            (*atmega32u4::$PORTX::ptr())
+                                .port.modify(|r, w| w.bits(r.bits() | (1 << $i)))
+                        }
+
+                        ddr.ddr().modify(|r, w| unsafe { w.bits(r.bits() | (1 << $i)) });
+
+                        $PXi { _mode: marker::PhantomData }
+                    }
+
+                    /// Turn this pin into an output which is initially low
+                    ///
+                    /// The PORT bit is cleared before switching the direction, so the pin
+                    /// never glitches high.
+                    pub fn into_output_low<D: PortDDR>(
+                        self,
+                        ddr: &mut D,
+                    ) -> $PXi<mode::io::Output> {
+                        unsafe {
+                            (*atmega32u4::$PORTX::ptr())
+                                .port.modify(|r, w| w.bits(r.bits() & !(1 << $i)))
+                        }
+
+                        ddr.ddr().modify(|r, w| unsafe { w.bits(r.bits() | (1 << $i)) });
+
+                        $PXi { _mode: marker::PhantomData }
+                    }
+
+                    /// Turn this pin into a pin whose mode can be changed at runtime
+                    ///
+                    /// The pin starts out as an input with internal pull-up.  The bit mask
+                    /// is computed once, switching the mode later doesn't need the DDR.
+                    ///
+                    /// # Example
+                    /// ```
+                    /// // 1-Wire reset pulse
+                    /// let mut ow = portd.pd4.into_dynamic(&mut portd.ddr);
+                    /// ow.set_low_output();
+                    /// delay.delay_us(480u16);
+                    /// ow.release_to_input();
+                    /// delay.delay_us(70u16);
+                    /// let present = ow.is_low().unwrap();
+                    /// ```
+                    pub fn into_dynamic<D: PortDDR>(self, ddr: &mut D) -> super::DynamicPin {
+                        ddr.ddr().modify(|r, w| unsafe { w.bits(r.bits() & !(1 << $i)) });
+
+                        unsafe {
+                            (*atmega32u4::$PORTX::ptr())
+                                .port.modify(|r, w| w.bits(r.bits() | (1 << $i)))
+                        }
+
+                        super::DynamicPin {
+                            mask: 1 << $i,
+                            port: super::Port::$PortEnum,
+                            mode: super::DynamicMode::PullUpInput,
+                        }
+                    }
+
+                    /// Turn this pin into an open-drain output
+                    ///
+                    /// The pin starts out released (high impedance).  A pull-up resistor
+                    /// is needed to get a high level.
+                    pub fn into_open_drain_output<D: PortDDR>(
+                        self,
+                        ddr: &mut D,
+                    ) -> $PXi<mode::io::OpenDrain> {
+                        ddr.ddr().modify(|r, w| unsafe { w.bits(r.bits() & !(1 << $i)) });
+
+                        unsafe {
+                            (*atmega32u4::$PORTX::ptr())
+                                .port.modify(|r, w| w.bits(r.bits() & !(1 << $i)))
+                        }
+
+                        $PXi { _mode: marker::PhantomData }
+                    }
                 }
 
-                impl digital::OutputPin for $PXi<mode::io::Output> {
-                    fn set_high(&mut self) {
+                impl digital::v2::OutputPin for $PXi<mode::io::Output> {
+                    type Error = Infallible;
+
+                    fn set_high(&mut self) -> Result<(), Self::Error> {
                         unsafe {
                             (*atmega32u4::$PORTX::ptr())
                                 .port.modify(|r, w| w.bits(r.bits() | (1 << $i)))
                         }
+                        Ok(())
                     }
 
-                    fn set_low(&mut self) {
+                    fn set_low(&mut self) -> Result<(), Self::Error> {
                         unsafe {
                             (*atmega32u4::$PORTX::ptr())
                                 .port.modify(|r, w| w.bits(r.bits() & !(1 << $i)))
                         }
+                        Ok(())
                     }
                 }
 
-                impl digital::StatefulOutputPin for $PXi<mode::io::Output> {
-                    fn is_set_high(&self) -> bool {
-                        (unsafe {
+                impl digital::v2::StatefulOutputPin for $PXi<mode::io::Output> {
+                    fn is_set_high(&self) -> Result<bool, Self::Error> {
+                        Ok((unsafe {
                             (*atmega32u4::$PORTX::ptr()).port.read().bits()
-                        } & (1 << $i)) != 0
+                        } & (1 << $i)) != 0)
                     }
 
-                    fn is_set_low(&self) -> bool {
-                        (unsafe {
+                    fn is_set_low(&self) -> Result<bool, Self::Error> {
+                        Ok((unsafe {
                             (*atmega32u4::$PORTX::ptr()).port.read().bits()
-                        } & (1 << $i)) == 0
+                        } & (1 << $i)) == 0)
                     }
                 }
 
-                impl digital::toggleable::Default for $PXi<mode::io::Output> { }
+                impl digital::v2::ToggleableOutputPin for $PXi<mode::io::Output> {
+                    type Error = Infallible;
+
+                    /// Toggle the pin
+                    ///
+                    /// Writing a one to the PIN register toggles the pin in a single
+                    /// instruction, without touching the other pins of this port.
+                    fn toggle(&mut self) -> Result<(), Self::Error> {
+                        unsafe {
+                            (*atmega32u4::$PORTX::ptr())
+                                .pin.write(|w| w.bits(1 << $i))
+                        }
+                        Ok(())
+                    }
+                }
 
-                impl<MODE> digital::InputPin for $PXi<mode::io::Input<MODE>> {
-                    fn is_high(&self) -> bool {
-                        (unsafe {
+                impl<MODE> $PXi<mode::io::Input<MODE>> {
+                    /// Read the level of this input
+                    ///
+                    /// `true` means high.  Unlike `is_high()`, this can't fail.
+                    pub fn read(&self) -> bool {
+                        let pin = unsafe { (*atmega32u4::$PORTX::ptr()).pin.read().bits() };
+                        pin & (1 << $i) != 0
+                    }
+
+                    /// Enable or disable the internal pull-up of this input
+                    ///
+                    /// This does not change the pin's type, so an `Input<Floating>` pin might
+                    /// have its pull-up enabled afterwards.
+                    pub fn set_pull_up(&mut self, enable: bool) {
+                        unsafe {
+                            (*atmega32u4::$PORTX::ptr()).port.modify(|r, w| if enable {
+                                w.bits(r.bits() | (1 << $i))
+                            } else {
+                                w.bits(r.bits() & !(1 << $i))
+                            })
+                        }
+                    }
+                }
+
+                impl<MODE> digital::v2::InputPin for $PXi<mode::io::Input<MODE>> {
+                    type Error = Infallible;
+
+                    fn is_high(&self) -> Result<bool, Self::Error> {
+                        Ok((unsafe {
                             (*atmega32u4::$PORTX::ptr()).pin.read().bits()
-                        } & (1 << $i)) != 0
+                        } & (1 << $i)) != 0)
                     }
 
-                    fn is_low(&self) -> bool {
-                        (unsafe {
+                    fn is_low(&self) -> Result<bool, Self::Error> {
+                        Ok((unsafe {
                             (*atmega32u4::$PORTX::ptr()).pin.read().bits()
-                        } & (1 << $i)) == 0
+                        } & (1 << $i)) == 0)
+                    }
+                }
+
+                impl digital::v2::OutputPin for $PXi<mode::io::OpenDrain> {
+                    type Error = Infallible;
+
+                    fn set_high(&mut self) -> Result<(), Self::Error> {
+                        unsafe {
+                            (*atmega32u4::$PORTX::ptr())
+                                .ddr.modify(|r, w| w.bits(r.bits() & !(1 << $i)))
+                        }
+                        Ok(())
+                    }
+
+                    fn set_low(&mut self) -> Result<(), Self::Error> {
+                        unsafe {
+                            (*atmega32u4::$PORTX::ptr())
+                                .ddr.modify(|r, w| w.bits(r.bits() | (1 << $i)))
+                        }
+                        Ok(())
+                    }
+                }
+
+                impl digital::v2::StatefulOutputPin for $PXi<mode::io::OpenDrain> {
+                    fn is_set_high(&self) -> Result<bool, Self::Error> {
+                        Ok((unsafe {
+                            (*atmega32u4::$PORTX::ptr()).ddr.read().bits()
+                        } & (1 << $i)) == 0)
+                    }
+
+                    fn is_set_low(&self) -> Result<bool, Self::Error> {
+                        Ok((unsafe {
+                            (*atmega32u4::$PORTX::ptr()).ddr.read().bits()
+                        } & (1 << $i)) != 0)
+                    }
+                }
+
+                impl digital::v2::toggleable::Default for $PXi<mode::io::OpenDrain> { }
+
+                impl digital::v2::InputPin for $PXi<mode::io::OpenDrain> {
+                    type Error = Infallible;
+
+                    fn is_high(&self) -> Result<bool, Self::Error> {
+                        Ok((unsafe {
+                            (*atmega32u4::$PORTX::ptr()).pin.read().bits()
+                        } & (1 << $i)) != 0)
+                    }
+
+                    fn is_low(&self) -> Result<bool, Self::Error> {
+                        Ok((unsafe {
+                            (*atmega32u4::$PORTX::ptr()).pin.read().bits()
+                        } & (1 << $i)) == 0)
                     }
                 }
             )+
@@ -318,21 +860,53 @@ macro_rules! port_impl {
 
 macro_rules! generic_pin_impl {
     ($($PortEnum:ident: $Port:ident,)+) => {
-        #[derive(Clone, Copy, Debug)]
+        // Debug prints the plain port letter
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
         enum Port {
             $($PortEnum,)+
         }
 
         /// A completely generic pin
-        #[derive(Debug)]
+        ///
+        /// Two handles are equal if they refer to the same physical pin.  `Debug`
+        /// prints the pin's name, for example `Pin(PC7)`.
         pub struct Pin<MODE> {
             i: u8,
             port: Port,
             _mode: marker::PhantomData<MODE>,
         }
 
-        impl digital::OutputPin for Pin<mode::io::Output> {
-            fn set_high(&mut self) {
+        impl<MODE> fmt::Debug for Pin<MODE> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "Pin(P{:?}{})", self.port, self.i)
+            }
+        }
+
+        impl<MODE> PartialEq for Pin<MODE> {
+            fn eq(&self, other: &Pin<MODE>) -> bool {
+                self.port == other.port && self.i == other.i
+            }
+        }
+
+        impl<MODE> Eq for Pin<MODE> {}
+
+        impl<MODE> Pin<MODE> {
+            /// Turn this pin back into its concrete type
+            ///
+            /// Fails and returns the pin unchanged if it isn't pin `P`, for example
+            /// `pin.upgrade::<portc::PC7<_>>()`.
+            pub fn upgrade<P>(self) -> Result<P, Pin<MODE>>
+            where
+                P: TryFrom<Pin<MODE>, Error = Pin<MODE>>,
+            {
+                P::try_from(self)
+            }
+        }
+
+        impl digital::v2::OutputPin for Pin<mode::io::Output> {
+            type Error = Infallible;
+
+            fn set_high(&mut self) -> Result<(), Self::Error> {
                 match self.port {
                     $(
                         Port::$PortEnum => unsafe {
@@ -341,9 +915,10 @@ macro_rules! generic_pin_impl {
                         },
                     )+
                 }
+                Ok(())
             }
 
-            fn set_low(&mut self) {
+            fn set_low(&mut self) -> Result<(), Self::Error> {
                 match self.port {
                     $(
                         Port::$PortEnum => unsafe {
@@ -352,54 +927,395 @@ macro_rules! generic_pin_impl {
                         },
                     )+
                 }
+                Ok(())
             }
         }
 
-        impl digital::StatefulOutputPin for Pin<mode::io::Output> {
-            fn is_set_high(&self) -> bool {
-                match self.port {
+        impl digital::v2::StatefulOutputPin for Pin<mode::io::Output> {
+            fn is_set_high(&self) -> Result<bool, Self::Error> {
+                Ok(match self.port {
                     $(
                         Port::$PortEnum => unsafe {
                             ((*atmega32u4::$Port::ptr()).port.read().bits() & (1 << self.i)) != 0
                         },
                     )+
-                }
+                })
             }
 
-            fn is_set_low(&self) -> bool {
-                match self.port {
+            fn is_set_low(&self) -> Result<bool, Self::Error> {
+                Ok(match self.port {
                     $(
                         Port::$PortEnum => unsafe {
                             ((*atmega32u4::$Port::ptr()).port.read().bits() & (1 << self.i)) == 0
                         },
                     )+
+                })
+            }
+        }
+
+        impl digital::v2::ToggleableOutputPin for Pin<mode::io::Output> {
+            type Error = Infallible;
+
+            /// Toggle the pin
+            ///
+            /// Selects the port's PIN register at runtime and writes the pin's mask to it.
+            fn toggle(&mut self) -> Result<(), Self::Error> {
+                match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            (*atmega32u4::$Port::ptr()).pin.write(|w| w.bits(1 << self.i))
+                        },
+                    )+
                 }
+                Ok(())
             }
         }
 
-        impl digital::toggleable::Default for Pin<mode::io::Output> { }
+        impl<MODE> Pin<mode::io::Input<MODE>> {
+            /// Read the level of this input
+            ///
+            /// `true` means high.  Unlike `is_high()`, this can't fail.
+            pub fn read(&self) -> bool {
+                match self.port {
+                    $(
+                        Port::$PortEnum => {
+                            let pin = unsafe { (*atmega32u4::$Port::ptr()).pin.read().bits() };
+                            pin & (1 << self.i) != 0
+                        }
+                    )+
+                }
+            }
 
-        impl<MODE> digital::InputPin for Pin<mode::io::Input<MODE>> {
-            fn is_high(&self) -> bool {
+            /// Enable or disable the internal pull-up of this input
+            ///
+            /// This does not change the pin's type, so an `Input<Floating>` pin might
+            /// have its pull-up enabled afterwards.
+            pub fn set_pull_up(&mut self, enable: bool) {
+                let mask = 1 << self.i;
                 match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            (*atmega32u4::$Port::ptr()).port.modify(|r, w| if enable {
+                                w.bits(r.bits() | mask)
+                            } else {
+                                w.bits(r.bits() & !mask)
+                            })
+                        },
+                    )+
+                }
+            }
+        }
+
+        impl<MODE> digital::v2::InputPin for Pin<mode::io::Input<MODE>> {
+            type Error = Infallible;
+
+            fn is_high(&self) -> Result<bool, Self::Error> {
+                Ok(match self.port {
                     $(
                         Port::$PortEnum => unsafe {
                             ((*atmega32u4::$Port::ptr()).pin.read().bits() & (1 << self.i)) != 0
                         },
                     )+
+                })
+            }
+
+            fn is_low(&self) -> Result<bool, Self::Error> {
+                Ok(match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            ((*atmega32u4::$Port::ptr()).pin.read().bits() & (1 << self.i)) == 0
+                        },
+                    )+
+                })
+            }
+        }
+
+        impl digital::v2::OutputPin for Pin<mode::io::OpenDrain> {
+            type Error = Infallible;
+
+            fn set_high(&mut self) -> Result<(), Self::Error> {
+                match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            (*atmega32u4::$Port::ptr())
+                                .ddr.modify(|r, w| w.bits(r.bits() & !(1 << self.i)))
+                        },
+                    )+
                 }
+                Ok(())
             }
 
-            fn is_low(&self) -> bool {
+            fn set_low(&mut self) -> Result<(), Self::Error> {
                 match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            (*atmega32u4::$Port::ptr())
+                                .ddr.modify(|r, w| w.bits(r.bits() | (1 << self.i)))
+                        },
+                    )+
+                }
+                Ok(())
+            }
+        }
+
+        impl digital::v2::StatefulOutputPin for Pin<mode::io::OpenDrain> {
+            fn is_set_high(&self) -> Result<bool, Self::Error> {
+                Ok(match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            ((*atmega32u4::$Port::ptr()).ddr.read().bits() & (1 << self.i)) == 0
+                        },
+                    )+
+                })
+            }
+
+            fn is_set_low(&self) -> Result<bool, Self::Error> {
+                Ok(match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            ((*atmega32u4::$Port::ptr()).ddr.read().bits() & (1 << self.i)) != 0
+                        },
+                    )+
+                })
+            }
+        }
+
+        impl digital::v2::toggleable::Default for Pin<mode::io::OpenDrain> { }
+
+        impl digital::v2::InputPin for Pin<mode::io::OpenDrain> {
+            type Error = Infallible;
+
+            fn is_high(&self) -> Result<bool, Self::Error> {
+                Ok(match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            ((*atmega32u4::$Port::ptr()).pin.read().bits() & (1 << self.i)) != 0
+                        },
+                    )+
+                })
+            }
+
+            fn is_low(&self) -> Result<bool, Self::Error> {
+                Ok(match self.port {
                     $(
                         Port::$PortEnum => unsafe {
                             ((*atmega32u4::$Port::ptr()).pin.read().bits() & (1 << self.i)) == 0
                         },
                     )+
+                })
+            }
+        }
+
+        /// Runtime mode of a [DynamicPin]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum DynamicMode {
+            /// Floating input
+            FloatingInput,
+            /// Input with internal pull-up
+            PullUpInput,
+            /// Push-pull output
+            Output,
+        }
+
+        /// A completely generic pin whose mode can be changed at runtime
+        ///
+        /// Unlike [Pin], the mode is not part of the type but tracked in a field.  This is
+        /// useful for bit-banged protocols which need to switch a pin between input and
+        /// output all the time.
+        #[derive(Debug)]
+        pub struct DynamicPin {
+            mask: u8,
+            port: Port,
+            mode: DynamicMode,
+        }
+
+        impl<MODE: mode::Io> Pin<MODE> {
+            /// Turn this pin into a pin whose mode can be changed at runtime
+            ///
+            /// The initial mode is read back from the hardware.
+            pub fn into_dynamic(self) -> DynamicPin {
+                let mut pin = DynamicPin {
+                    mask: 1 << self.i,
+                    port: self.port,
+                    mode: DynamicMode::FloatingInput,
+                };
+                pin.mode = if pin.read_ddr() {
+                    DynamicMode::Output
+                } else if pin.read_port() {
+                    DynamicMode::PullUpInput
+                } else {
+                    DynamicMode::FloatingInput
+                };
+                pin
+            }
+        }
+
+        impl DynamicPin {
+            /// Current mode of this pin
+            pub fn mode(&self) -> DynamicMode {
+                self.mode
+            }
+
+            /// Whether this pin is currently an output
+            pub fn is_output(&self) -> bool {
+                self.mode == DynamicMode::Output
+            }
+
+            /// Whether this pin is currently an input
+            pub fn is_input(&self) -> bool {
+                !self.is_output()
+            }
+
+            /// Make this pin an output
+            ///
+            /// The PORT bit is left untouched, so a pull-up input becomes a high output.
+            pub fn make_output(&mut self) {
+                self.modify_ddr(true);
+                self.mode = DynamicMode::Output;
+            }
+
+            /// Make this pin a floating input
+            pub fn make_input(&mut self) {
+                self.modify_ddr(false);
+                self.modify_port(false);
+                self.mode = DynamicMode::FloatingInput;
+            }
+
+            /// Make this pin an input with internal pull-up
+            pub fn make_pull_up_input(&mut self) {
+                self.modify_ddr(false);
+                self.modify_port(true);
+                self.mode = DynamicMode::PullUpInput;
+            }
+
+            /// Actively drive the pin low
+            ///
+            /// The pull-up is disabled before the pin becomes an output, so the line
+            /// never sees a high pulse.  Meant for open-drain buses like 1-Wire.
+            pub fn set_low_output(&mut self) {
+                self.modify_port(false);
+                self.modify_ddr(true);
+                self.mode = DynamicMode::Output;
+            }
+
+            /// Release the pin to an input with internal pull-up
+            ///
+            /// Counterpart to `set_low_output()`.  Afterwards the bus level can be sampled
+            /// using `is_high()`.
+            pub fn release_to_input(&mut self) {
+                self.modify_ddr(false);
+                self.modify_port(true);
+                self.mode = DynamicMode::PullUpInput;
+            }
+
+            fn modify_ddr(&mut self, set: bool) {
+                let mask = self.mask;
+                match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            (*atmega32u4::$Port::ptr()).ddr.modify(|r, w| if set {
+                                w.bits(r.bits() | mask)
+                            } else {
+                                w.bits(r.bits() & !mask)
+                            })
+                        },
+                    )+
+                }
+            }
+
+            fn modify_port(&mut self, set: bool) {
+                let mask = self.mask;
+                match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            (*atmega32u4::$Port::ptr()).port.modify(|r, w| if set {
+                                w.bits(r.bits() | mask)
+                            } else {
+                                w.bits(r.bits() & !mask)
+                            })
+                        },
+                    )+
+                }
+            }
+
+            fn read_ddr(&self) -> bool {
+                match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            ((*atmega32u4::$Port::ptr()).ddr.read().bits() & self.mask) != 0
+                        },
+                    )+
+                }
+            }
+
+            fn read_port(&self) -> bool {
+                match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            ((*atmega32u4::$Port::ptr()).port.read().bits() & self.mask) != 0
+                        },
+                    )+
+                }
+            }
+
+            fn read_pin(&self) -> bool {
+                match self.port {
+                    $(
+                        Port::$PortEnum => unsafe {
+                            ((*atmega32u4::$Port::ptr()).pin.read().bits() & self.mask) != 0
+                        },
+                    )+
                 }
             }
         }
+
+        /// *Note*: While the pin is an input, `set_high()`/`set_low()` enable/disable
+        /// the internal pull-up.
+        impl digital::v2::OutputPin for DynamicPin {
+            type Error = Infallible;
+
+            fn set_high(&mut self) -> Result<(), Self::Error> {
+                self.modify_port(true);
+                Ok(())
+            }
+
+            fn set_low(&mut self) -> Result<(), Self::Error> {
+                self.modify_port(false);
+                Ok(())
+            }
+        }
+
+        impl digital::v2::InputPin for DynamicPin {
+            type Error = Infallible;
+
+            fn is_high(&self) -> Result<bool, Self::Error> {
+                Ok(self.read_pin())
+            }
+
+            fn is_low(&self) -> Result<bool, Self::Error> {
+                Ok(!self.read_pin())
+            }
+        }
+
+        /// Converting to an input makes the pin a floating input.  Converting to an
+        /// output sets the level before switching the direction, so it doesn't glitch.
+        impl digital::v2::IoPin<DynamicPin, DynamicPin> for DynamicPin {
+            type Error = Infallible;
+
+            fn into_input_pin(mut self) -> Result<DynamicPin, Infallible> {
+                self.make_input();
+                Ok(self)
+            }
+
+            fn into_output_pin(
+                mut self,
+                state: digital::v2::PinState,
+            ) -> Result<DynamicPin, Infallible> {
+                self.modify_port(state == digital::v2::PinState::High);
+                self.make_output();
+                Ok(self)
+            }
+        }
     }
 }
 
@@ -411,6 +1327,85 @@ generic_pin_impl!(
     F: PORTF,
 );
 
+/// Several downgraded pins used as one value
+///
+/// `PINS` is an array (or slice) of fully downgraded pins.  Bit 0 of the value
+/// corresponds to the first pin, bit 1 to the second and so on.  At most 8 pins are
+/// used.  The pins may belong to different ports, but then they are not switched at
+/// the same time:  Each pin is written one after the other, first to last.
+///
+/// # Example
+/// ```
+/// let mut bus = port::Bus::new([
+///     portb.pb4.into_output(&mut portb.ddr).downgrade(),
+///     portb.pb5.into_output(&mut portb.ddr).downgrade(),
+///     portd.pd6.into_output(&mut portd.ddr).downgrade(),
+///     portd.pd7.into_output(&mut portd.ddr).downgrade(),
+/// ]);
+///
+/// // PB4 and PD6 high, PB5 and PD7 low
+/// bus.write(0b0101);
+/// ```
+pub struct Bus<PINS> {
+    pins: PINS,
+}
+
+impl<PINS> Bus<PINS> {
+    /// Bundle pins into a bus
+    pub fn new(pins: PINS) -> Bus<PINS> {
+        Bus { pins: pins }
+    }
+
+    /// Release the pins
+    pub fn free(self) -> PINS {
+        self.pins
+    }
+
+    /// Write a value to an output bus
+    ///
+    /// Each pin is set according to its bit in `value`.
+    pub fn write(&mut self, value: u8)
+    where
+        PINS: AsMut<[Pin<mode::io::Output>]>,
+    {
+        use hal::digital::v2::OutputPin;
+
+        for (i, pin) in self.pins.as_mut().iter_mut().take(8).enumerate() {
+            if bus_bit(value, i) {
+                pin.set_high().unwrap();
+            } else {
+                pin.set_low().unwrap();
+            }
+        }
+    }
+
+    /// Read the value of an input bus
+    pub fn read<MODE>(&self) -> u8
+    where
+        PINS: AsRef<[Pin<mode::io::Input<MODE>>]>,
+    {
+        use hal::digital::v2::InputPin;
+
+        bus_value(self.pins.as_ref().iter().map(|pin| pin.is_high().unwrap()))
+    }
+}
+
+/// Level of the `i`-th pin of a [Bus] for `value`
+fn bus_bit(value: u8, i: usize) -> bool {
+    value & (1 << i) != 0
+}
+
+/// Value of a [Bus] from the levels of its pins, first pin first
+fn bus_value<I: Iterator<Item = bool>>(levels: I) -> u8 {
+    let mut value = 0;
+    for (i, high) in levels.take(8).enumerate() {
+        if high {
+            value |= 1 << i;
+        }
+    }
+    value
+}
+
 port_impl! (B, PORTB, portb, PBx, [
     PB0: (pb0, 0, mode::io::Input<mode::io::Floating>),
     PB1: (pb1, 1, mode::io::Input<mode::io::Floating>),
@@ -479,7 +1474,7 @@ macro_rules! define_pins {
         $(
             impl $crate::port::$portx::PortDDR for $DDR {
                 fn ddr(&mut self) -> &atmega32u4::$portx::DDR {
-                    self.$portx.ddr()
+                    $crate::port::$portx::PortDDR::ddr(&mut self.$portx)
                 }
             }
         )+
@@ -502,12 +1497,12 @@ macro_rules! define_pins {
             /// Initialize pins
             pub fn new(
                 $( $portx: $PORTX, )+
-            ) -> Pins {
+            ) -> $Pins {
                 use $crate::port::PortExt;
 
                 $( let $portx = $portx.split(); )+
 
-                Pins {
+                $Pins {
                     $(
                         $name: $port.$pin,
                     )+
@@ -521,3 +1516,129 @@ macro_rules! define_pins {
         }
     };
 }
+
+define_pins! {
+    /// All pins of the ATmega32U4, created using [all]
+    name: Pins,
+    ddr: DDR {
+        portb: atmega32u4::PORTB,
+        portc: atmega32u4::PORTC,
+        portd: atmega32u4::PORTD,
+        porte: atmega32u4::PORTE,
+        portf: atmega32u4::PORTF,
+    },
+    pins: {
+        /// `PB0`
+        pb0: (portb, pb0, PB0),
+        /// `PB1`
+        pb1: (portb, pb1, PB1),
+        /// `PB2`
+        pb2: (portb, pb2, PB2),
+        /// `PB3`
+        pb3: (portb, pb3, PB3),
+        /// `PB4`
+        pb4: (portb, pb4, PB4),
+        /// `PB5`
+        pb5: (portb, pb5, PB5),
+        /// `PB6`
+        pb6: (portb, pb6, PB6),
+        /// `PB7`
+        pb7: (portb, pb7, PB7),
+        /// `PC6`
+        pc6: (portc, pc6, PC6),
+        /// `PC7`
+        pc7: (portc, pc7, PC7),
+        /// `PD0`
+        pd0: (portd, pd0, PD0),
+        /// `PD1`
+        pd1: (portd, pd1, PD1),
+        /// `PD2`
+        pd2: (portd, pd2, PD2),
+        /// `PD3`
+        pd3: (portd, pd3, PD3),
+        /// `PD4`
+        pd4: (portd, pd4, PD4),
+        /// `PD5`
+        pd5: (portd, pd5, PD5),
+        /// `PD6`
+        pd6: (portd, pd6, PD6),
+        /// `PD7`
+        pd7: (portd, pd7, PD7),
+        /// `PE2`
+        pe2: (porte, pe2, PE2),
+        /// `PE6`
+        pe6: (porte, pe6, PE6),
+        /// `PF0`
+        pf0: (portf, pf0, PF0),
+        /// `PF1`
+        pf1: (portf, pf1, PF1),
+        /// `PF4`
+        pf4: (portf, pf4, PF4),
+        /// `PF5`
+        pf5: (portf, pf5, PF5),
+        /// `PF6`
+        pf6: (portf, pf6, PF6),
+        /// `PF7`
+        pf7: (portf, pf7, PF7),
+    }
+}
+
+/// Split all ports at once
+///
+/// Every pin is available under its hardware name, and the combined `ddr` can be
+/// used for all of them.
+pub fn all(
+    portb: atmega32u4::PORTB,
+    portc: atmega32u4::PORTC,
+    portd: atmega32u4::PORTD,
+    porte: atmega32u4::PORTE,
+    portf: atmega32u4::PORTF,
+) -> Pins {
+    Pins::new(portb, portc, portd, porte, portf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bus_first_pin_is_bit_0() {
+        assert!(bus_bit(0b0000_0001, 0));
+        assert!(!bus_bit(0b0000_0001, 1));
+        assert!(bus_bit(0b1000_0000, 7));
+
+        assert_eq!(bus_value([true, false, false, false].iter().cloned()), 0b0001);
+        assert_eq!(bus_value([false, false, false, true].iter().cloned()), 0b1000);
+    }
+
+    #[test]
+    fn bus_value_round_trip() {
+        for value in 0..=255u8 {
+            assert_eq!(bus_value((0..8).map(|i| bus_bit(value, i))), value);
+        }
+    }
+
+    #[test]
+    fn bus_uses_at_most_8_pins() {
+        assert_eq!(bus_value([true; 10].iter().cloned()), 0xff);
+        assert_eq!(bus_value([false, true, true].iter().cloned()), 0b110);
+    }
+
+    #[test]
+    fn port_input_levels() {
+        let input = PortInput(0b1000_0101);
+        assert!(input.is_high(0));
+        assert!(input.is_low(1));
+        assert!(input.is_high(2));
+        assert!(input.is_high(7));
+    }
+
+    #[test]
+    fn port_input_index_out_of_range() {
+        let input = PortInput(0xff);
+        for index in 8..=255 {
+            assert!(!input.is_high(index));
+            assert!(!input.is_low(index));
+        }
+    }
+}
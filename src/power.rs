@@ -0,0 +1,39 @@
+//! Supply Voltage Monitoring
+//!
+//! The ATmega32U4's brown-out detector can only reset the chip.  Its level is set
+//! by the `BODLEVEL` fuses and it has **no** interrupt, so there is no way to get
+//! notified right before a brown-out.  What the chip can do:
+//!
+//! * After the reset, [reset::reset_cause] reports [reset::ResetCause::BrownOut].
+//! * While running, the supply voltage can be measured using the ADC and the
+//!   internal bandgap reference.  Polling [supply_below] periodically with a
+//!   threshold above the `BODLEVEL` gives the application a chance to save its
+//!   state to the [eeprom] while the voltage drops.
+//!
+//! How much time is left depends on the decoupling capacitance and the load.  A
+//! single EEPROM byte write takes 3.4ms, so keep the data to save small.
+//!
+//! # Example
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut adc = adc::Adc::new(dp.ADC, adc::Reference::AVcc);
+//!
+//! if reset::reset_cause() == reset::ResetCause::BrownOut {
+//!     // Power was lost last time, restore state from EEPROM
+//! }
+//!
+//! loop {
+//!     if power::supply_below(&mut adc, 4300) {
+//!         // Save state to EEPROM
+//!     }
+//! }
+//! ```
+use adc;
+
+/// Whether the supply voltage is below `millivolts`
+///
+/// Measures the voltage using [adc::Adc::read_vcc_millivolts], which takes two
+/// conversions.
+pub fn supply_below(adc: &mut adc::Adc, millivolts: u16) -> bool {
+    adc.read_vcc_millivolts() < millivolts
+}
@@ -1,5 +1,30 @@
 //! Prelude
+//!
+//! Brings all traits needed to use this crate into scope:
+//!
+//! ```
+//! use atmega32u4_hal::prelude::*;
+//! ```
+//!
+//! This includes
+//!
+//! * [PortExt](../port/trait.PortExt.html) for splitting ports into pins,
+//! * the `embedded-hal` prelude, which contains `PwmPin`, `DelayUs`/`DelayMs`,
+//!   `OneShot`, the serial and SPI traits and the watchdog traits,
+//! * the `digital::v2` pin traits `OutputPin`, `InputPin`, `StatefulOutputPin` and
+//!   `ToggleableOutputPin`,
+//! * [PwmPinExt](../timer/trait.PwmPinExt.html) for setting PWM duty cycles in percent,
+//! * `IntoLed` for `into_led()` on the LED pin, with the `leonardo` or `micro` feature,
+//! * `core::fmt::Write`, so `write!` and `writeln!` work with [Serial](../serial/struct.Serial.html).
+//!
+//! All traits are imported anonymously, so they don't clash with your own names.
 pub use port::PortExt as _atmega32u4_hal_port_PortExt;
 pub use hal::prelude::*;
-pub use hal::digital::StatefulOutputPin as _atmega_embedded_hal_digital_StatefulOutputPin;
-pub use hal::digital::ToggleableOutputPin as _atmega_embedded_hal_digital_ToggleableOutputPin;
+pub use hal::digital::v2::OutputPin as _atmega_embedded_hal_digital_v2_OutputPin;
+pub use hal::digital::v2::InputPin as _atmega_embedded_hal_digital_v2_InputPin;
+pub use hal::digital::v2::StatefulOutputPin as _atmega_embedded_hal_digital_v2_StatefulOutputPin;
+pub use hal::digital::v2::ToggleableOutputPin as _atmega_embedded_hal_digital_v2_ToggleableOutputPin;
+pub use core::fmt::Write as _atmega_core_fmt_Write;
+pub use timer::PwmPinExt as _atmega32u4_hal_timer_PwmPinExt;
+#[cfg(any(feature = "leonardo", feature = "micro"))]
+pub use board::IntoLed as _atmega32u4_hal_board_IntoLed;
@@ -0,0 +1,110 @@
+//! SparkFun Pro Micro pinout
+//!
+//! Pin names as printed on the SparkFun Pro Micro board.  Enable the `promicro`
+//! feature to use this module.
+//!
+//! | Board   | Pin   | Notes                  |
+//! |---------|-------|------------------------|
+//! | `D0`    | `PD2` | `RX`                   |
+//! | `D1`    | `PD3` | `TX`                   |
+//! | `D2`    | `PD1` | `SDA`                  |
+//! | `D3`    | `PD0` | `SCL`, PWM             |
+//! | `D4`    | `PD4` |                        |
+//! | `D5`    | `PC6` | PWM                    |
+//! | `D6`    | `PD7` | PWM                    |
+//! | `D7`    | `PE6` |                        |
+//! | `D8`    | `PB4` |                        |
+//! | `D9`    | `PB5` | PWM                    |
+//! | `D10`   | `PB6` | PWM                    |
+//! | `D14`   | `PB3` | `MISO`                 |
+//! | `D15`   | `PB1` | `SCK`                  |
+//! | `D16`   | `PB2` | `MOSI`                 |
+//! | `A0`    | `PF7` |                        |
+//! | `A1`    | `PF6` |                        |
+//! | `A2`    | `PF5` |                        |
+//! | `A3`    | `PF4` |                        |
+//! | `RXLED` | `PB0` | Active low             |
+//! | `TXLED` | `PD5` | Active low             |
+//!
+//! The Pro Micro has no `D13` LED.  `PB7`, `PC7`, `PD6`, `PF0`, `PF1` and `PE2`
+//! (`HWB`) are not connected.  USB and the crystal use dedicated pins.
+//!
+//! The Pro Micro exists as a 5V/16MHz and a 3.3V/8MHz version.  Select the clock
+//! using the `mhz16` or `mhz8` feature, [Board] uses it for its delay.
+//!
+//! # Example
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut board = atmega32u4_hal::promicro::Board::new(dp);
+//!
+//! // The RX LED is active low, so it starts out off
+//! let mut led = board.pins.led_rx.into_output_high(&mut board.pins.ddr);
+//! loop {
+//!     led.toggle().unwrap();
+//!     board.delay.delay_ms(500u16);
+//! }
+//! ```
+use atmega32u4;
+use delay;
+
+define_pins! {
+    /// Pins of the SparkFun Pro Micro
+    name: Pins,
+    ddr: DDR {
+        portb: atmega32u4::PORTB,
+        portc: atmega32u4::PORTC,
+        portd: atmega32u4::PORTD,
+        porte: atmega32u4::PORTE,
+        portf: atmega32u4::PORTF,
+    },
+    pins: {
+        /// `D0` / `RX`
+        d0: (portd, pd2, PD2),
+        /// `D1` / `TX`
+        d1: (portd, pd3, PD3),
+        /// `D2` / `SDA`
+        d2: (portd, pd1, PD1),
+        /// `D3` / `SCL`
+        d3: (portd, pd0, PD0),
+        /// `D4`
+        d4: (portd, pd4, PD4),
+        /// `D5`
+        d5: (portc, pc6, PC6),
+        /// `D6`
+        d6: (portd, pd7, PD7),
+        /// `D7`
+        d7: (porte, pe6, PE6),
+        /// `D8`
+        d8: (portb, pb4, PB4),
+        /// `D9`
+        d9: (portb, pb5, PB5),
+        /// `D10`
+        d10: (portb, pb6, PB6),
+        /// `D14` / `MISO`
+        d14: (portb, pb3, PB3),
+        /// `D15` / `SCK`
+        d15: (portb, pb1, PB1),
+        /// `D16` / `MOSI`
+        d16: (portb, pb2, PB2),
+        /// `A0`
+        a0: (portf, pf7, PF7),
+        /// `A1`
+        a1: (portf, pf6, PF6),
+        /// `A2`
+        a2: (portf, pf5, PF5),
+        /// `A3`
+        a3: (portf, pf4, PF4),
+        /// `RXLED` (active low)
+        led_rx: (portb, pb0, PB0),
+        /// `TXLED` (active low)
+        led_tx: (portd, pd5, PD5),
+    }
+}
+
+board_impl! {
+    /// SparkFun Pro Micro with all peripherals
+    ///
+    /// Uses the clock selected with the `mhz*` features.
+    pins: Pins,
+    speed: delay::Speed,
+}
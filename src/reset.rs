@@ -0,0 +1,85 @@
+//! Reset Cause
+//!
+//! The cause of the last reset is stored in `MCUSR`.  The flags are sticky, they are
+//! only cleared by a power-on reset or by writing to the register.  To get reliable
+//! results, call [reset_cause] once, early in `main`.  It clears the flags afterwards,
+//! so the next reset reports only its own cause.
+//!
+//! # Example
+//! ```
+//! use atmega32u4_hal::reset;
+//!
+//! match reset::reset_cause() {
+//!     reset::ResetCause::Watchdog => {
+//!         // Recover from a crash
+//!     },
+//!     _ => (),
+//! }
+//! ```
+//!
+//! *Note*: Clearing `WDRF` is also needed to turn off the watchdog after a watchdog
+//! reset, see [wdt].  If you use [wdt::Wdt::is_watchdog_reset] as well, call it
+//! before `reset_cause()`.
+use atmega32u4;
+
+const PORF: u8 = 1 << 0;
+const EXTRF: u8 = 1 << 1;
+const BORF: u8 = 1 << 2;
+const WDRF: u8 = 1 << 3;
+const JTRF: u8 = 1 << 4;
+const USBRF: u8 = 1 << 5;
+
+/// Cause of the last reset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetCause {
+    /// Supply voltage came up
+    PowerOn,
+    /// Supply voltage dropped below the brown-out level
+    BrownOut,
+    /// Low level on the `RESET` pin
+    External,
+    /// Watchdog timeout
+    Watchdog,
+    /// JTAG reset instruction
+    Jtag,
+    /// USB end of reset
+    Usb,
+    /// No flag was set, probably because they have been cleared already
+    Unknown,
+}
+
+/// Read the cause of the last reset and clear all reset flags
+///
+/// If multiple flags are set, the first one in the order of [ResetCause] is reported.
+/// To inspect all of them, use [reset_flags].
+pub fn reset_cause() -> ResetCause {
+    let flags = reset_flags();
+
+    if flags & PORF != 0 {
+        ResetCause::PowerOn
+    } else if flags & BORF != 0 {
+        ResetCause::BrownOut
+    } else if flags & EXTRF != 0 {
+        ResetCause::External
+    } else if flags & WDRF != 0 {
+        ResetCause::Watchdog
+    } else if flags & JTRF != 0 {
+        ResetCause::Jtag
+    } else if flags & USBRF != 0 {
+        ResetCause::Usb
+    } else {
+        ResetCause::Unknown
+    }
+}
+
+/// Read the raw `MCUSR` reset flags and clear them
+pub fn reset_flags() -> u8 {
+    atmega32u4::interrupt::free(|_| unsafe {
+        let cpu = &*atmega32u4::CPU::ptr();
+        let flags = cpu.mcusr.read().bits();
+        cpu.mcusr.modify(|r, w| {
+            w.bits(r.bits() & !(PORF | EXTRF | BORF | WDRF | JTRF | USBRF))
+        });
+        flags
+    })
+}
@@ -0,0 +1,1090 @@
+//! Serial (USART1)
+//!
+//! `USART1` is connected to `PD2` (RX) and `PD3` (TX).  The baudrate is calculated
+//! from the CPU speed, which is either passed as a type parameter or selected using
+//! the `mhz*` features (see [delay]).  The frame format defaults to 8N1, other formats
+//! can be selected using [Config]:
+//!
+//! ```
+//! let config = serial::Config {
+//!     parity: serial::Parity::Even,
+//!     ..serial::Config::default()
+//! };
+//! let serial: serial::Serial<delay::MHz16> =
+//!     serial::Serial::with_config(dp.USART1, 9600, config);
+//! ```
+//!
+//! Framing, parity and overrun errors of received bytes are reported by `read()` as
+//! [Error].
+//!
+//! Writing returns as soon as a byte has been loaded into the data register (`UDRE`),
+//! while the previous byte may still be shifting out.  `flush()` waits for transmit
+//! complete (`TXC`) instead, so the last bit has left the pin.  Call it before
+//! changing the baudrate, disabling `USART1` or going to sleep, otherwise the last
+//! byte gets corrupted.  `free()` does this already.
+//!
+//! # Baudrate Accuracy
+//! Not every baudrate can be reached exactly.  [Baudrate] picks the `UBRR1` divider
+//! and whether to use double speed (`U2X1`), whichever is closer.  The classic case is
+//! 115200 baud at 16MHz:
+//!
+//! | Mode         | `UBRR1` | Actual baudrate | Error |
+//! |--------------|---------|-----------------|-------|
+//! | Normal       | 8       | 111111          | -3.5% |
+//! | Double speed | 16      | 117647          | +2.1% |
+//!
+//! Double speed is selected.  It is still slightly above 2%, but works with most
+//! USB-serial adapters, which run at exactly 115200.  At 9600 baud both modes are 0.2%
+//! off and normal speed is used.  If the other side can do it, 250000 or 500000 baud
+//! are exact at 16MHz.
+//!
+//! # Example
+//! ```
+//! use core::fmt::Write;
+//!
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut serial: serial::Serial<delay::MHz16> = serial::Serial::new(dp.USART1, 57600);
+//!
+//! writeln!(serial, "Hello World!").unwrap();
+//! ```
+//!
+//! ## Smaller Formatting with `ufmt`
+//! `core::fmt` takes up a lot of flash.  With the `ufmt` feature, [Serial] and [Tx]
+//! implement `ufmt::uWrite` as well, so the much smaller
+//! [`ufmt`](https://crates.io/crates/ufmt) macros can be used instead:
+//!
+//! ```
+//! use ufmt::uwriteln;
+//!
+//! uwriteln!(serial, "Sample: {}", sample).unwrap();
+//! ```
+//!
+//! # Buffered Transmission
+//! Writing to a `Serial` blocks until every byte has been handed to the hardware.
+//! For logging from time critical code, convert it into a buffered [Tx] instead.
+//! Bytes are then stored in a ring buffer of [TX_BUFFER_SIZE] bytes, which is
+//! drained by the `USART1_UDRE` interrupt.  The interrupt handler has to call
+//! [udre_isr]:
+//!
+//! ```
+//! use core::fmt::Write;
+//!
+//! fn main() {
+//!     let dp = atmega32u4::Peripherals::take().unwrap();
+//!     let serial: serial::Serial<delay::MHz16> = serial::Serial::new(dp.USART1, 57600);
+//!     let mut tx = serial.into_buffered_tx();
+//!
+//!     unsafe { atmega32u4::interrupt::enable() };
+//!
+//!     // Returns as soon as the message is queued
+//!     writeln!(tx, "Hello World!").unwrap();
+//!
+//!     loop { }
+//! }
+//!
+//! interrupt!(USART1_UDRE, usart1_udre_isr);
+//! fn usart1_udre_isr() {
+//!     atmega32u4_hal::serial::udre_isr();
+//! }
+//! ```
+//!
+//! When the buffer is full, `write_byte()` and the `fmt::Write` implementation wait
+//! until the interrupt made room.  `try_write_byte()` returns `WouldBlock` instead, so
+//! the byte can be dropped.  **Never** use the blocking variants with interrupts
+//! disabled, as the buffer will not drain and they wait forever.
+//!
+//! # Buffered Reception
+//! Polling `read()` loses bytes as soon as the application doesn't look for longer
+//! than one byte takes.  After `enable_rx_buffer()`, the `USART1_RX` interrupt stores
+//! received bytes in a ring buffer of [RX_BUFFER_SIZE] bytes, and `read()` takes them
+//! from there.  The interrupt handler has to call [rx_isr]:
+//!
+//! ```
+//! fn main() {
+//!     let dp = atmega32u4::Peripherals::take().unwrap();
+//!     let mut serial: serial::Serial<delay::MHz16> = serial::Serial::new(dp.USART1, 57600);
+//!     serial.enable_rx_buffer();
+//!
+//!     unsafe { atmega32u4::interrupt::enable() };
+//!
+//!     loop {
+//!         match serial.read() {
+//!             Ok(byte) => { /* Process the byte */ },
+//!             Err(nb::Error::WouldBlock) => { /* Do something else */ },
+//!             Err(nb::Error::Other(e)) => { /* Bytes were lost */ },
+//!         }
+//!     }
+//! }
+//!
+//! interrupt!(USART1_RX, usart1_rx_isr);
+//! fn usart1_rx_isr() {
+//!     atmega32u4_hal::serial::rx_isr();
+//! }
+//! ```
+//!
+//! If the buffer is full, further bytes are dropped and the next `read()` returns
+//! `Error::Overrun` once, before the buffered bytes.  Bytes with a framing or parity
+//! error are dropped as well and reported the same way.  The buffer size is a
+//! constant because const generics are not available; at 115200 baud, 64 bytes last
+//! about 5.5ms.
+//!
+//! # SPI Master Mode
+//! `USART1` can also work as a second SPI master, [SpiMaster], for example when the
+//! SPI peripheral is already used by another device:
+//!
+//! | Signal | Pin   |
+//! |--------|-------|
+//! | `SCK`  | `PD5` |
+//! | `MOSI` | `PD3` |
+//! | `MISO` | `PD2` |
+//!
+//! It is full-duplex and implements the same `embedded-hal` traits as [spi::Spi].
+//! There is no slave select, use any output pin as chip select.  The clock is
+//! `F_CPU / (2 * (UBRR + 1))`, so at 16MHz everything from 8MHz down to 1953Hz can be
+//! reached.  On the Leonardo, `PD5` is the TX LED.
+//!
+//! ```
+//! let mut spi: serial::SpiMaster<delay::MHz16> = serial::SpiMaster::new(
+//!     dp.USART1,
+//!     portd.pd5.into_output(&mut portd.ddr),
+//!     portd.pd3.into_output(&mut portd.ddr),
+//!     portd.pd2.into_floating_input(&mut portd.ddr),
+//!     1_000_000,
+//!     hal::spi::MODE_0,
+//!     spi::DataOrder::MostSignificantFirst,
+//! );
+//! ```
+//!
+//! # Activity LEDs
+//! Like the Arduino core, [ActivityLeds] can flash the RX/TX LEDs of boards like the
+//! Leonardo (`PB0` and `PD5`, both active low) whenever a byte is received or sent.
+//! It wraps a [Serial] or [Tx] and turns the LED on for each byte.  `tick()` turns it
+//! off again once no byte went through for a number of ticks, so call it
+//! periodically, for example every millisecond:
+//!
+//! ```
+//! use core::fmt::Write;
+//!
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut portb = dp.PORTB.split();
+//! let mut portd = dp.PORTD.split();
+//! let mut delay = delay::Delay::<delay::MHz16>::new();
+//!
+//! let serial: serial::Serial<delay::MHz16> = serial::Serial::new(dp.USART1, 57600);
+//! let mut serial = serial::ActivityLeds::new(
+//!     serial,
+//!     portb.pb0.into_output(&mut portb.ddr),
+//!     portd.pd5.into_output(&mut portd.ddr),
+//!     10,
+//! );
+//!
+//! loop {
+//!     writeln!(serial, "Hello World!").unwrap();
+//!     serial.tick();
+//!     delay.delay_ms(1u8);
+//! }
+//! ```
+use atmega32u4;
+use clock;
+use core::convert::Infallible;
+use core::fmt;
+use core::marker;
+use delay;
+use global::Global;
+use hal;
+use hal::digital::v2::OutputPin;
+use nb;
+use port;
+use port::mode::io::{Floating, Input, Output};
+use spi;
+#[cfg(feature = "ufmt")]
+use ufmt;
+
+/// Size of the transmit buffer used by [Tx]
+pub const TX_BUFFER_SIZE: usize = 64;
+
+/// Size of the receive buffer, see `Serial::enable_rx_buffer()`
+pub const RX_BUFFER_SIZE: usize = 64;
+
+// UCSR1A
+const RXC: u8 = 1 << 7;
+const TXC: u8 = 1 << 6;
+const UDRE: u8 = 1 << 5;
+const FE: u8 = 1 << 4;
+const DOR: u8 = 1 << 3;
+const UPE: u8 = 1 << 2;
+const U2X: u8 = 1 << 1;
+const MPCM: u8 = 1 << 0;
+
+// UCSR1B
+const RXCIE: u8 = 1 << 7;
+const UDRIE: u8 = 1 << 5;
+const RXEN: u8 = 1 << 4;
+const TXEN: u8 = 1 << 3;
+
+// UCSR1C
+const UMSEL_MSPIM: u8 = 0b11 << 6;
+const UDORD: u8 = 1 << 2;
+const UCPHA: u8 = 1 << 1;
+const UCPOL: u8 = 1 << 0;
+const UPM_EVEN: u8 = 0b10 << 4;
+const UPM_ODD: u8 = 0b11 << 4;
+const USBS: u8 = 1 << 3;
+
+/// Serial receive error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A stop bit was not detected
+    Framing,
+    /// A received byte was lost because the previous one wasn't read in time
+    Overrun,
+    /// Parity check failed
+    Parity,
+}
+
+/// Number of data bits per frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataBits {
+    /// 5 data bits
+    Five,
+    /// 6 data bits
+    Six,
+    /// 7 data bits
+    Seven,
+    /// 8 data bits
+    Eight,
+}
+
+/// Parity bit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parity {
+    /// No parity bit
+    None,
+    /// Even parity
+    Even,
+    /// Odd parity
+    Odd,
+}
+
+/// Number of stop bits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopBits {
+    /// 1 stop bit
+    One,
+    /// 2 stop bits
+    Two,
+}
+
+/// Serial frame format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Data bits
+    pub data_bits: DataBits,
+    /// Parity
+    pub parity: Parity,
+    /// Stop bits
+    pub stop_bits: StopBits,
+}
+
+impl Default for Config {
+    /// 8N1
+    fn default() -> Config {
+        Config {
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+        }
+    }
+}
+
+impl Config {
+    /// `UCSR1C` value for this format, in asynchronous mode
+    fn bits(&self) -> u8 {
+        let ucsz = match self.data_bits {
+            DataBits::Five => 0b00,
+            DataBits::Six => 0b01,
+            DataBits::Seven => 0b10,
+            DataBits::Eight => 0b11,
+        };
+        let upm = match self.parity {
+            Parity::None => 0,
+            Parity::Even => UPM_EVEN,
+            Parity::Odd => UPM_ODD,
+        };
+        let usbs = match self.stop_bits {
+            StopBits::One => 0,
+            StopBits::Two => USBS,
+        };
+
+        upm | usbs | (ucsz << 1)
+    }
+}
+
+/// Baudrate divider setting (`UBRR1` and `U2X1`)
+///
+/// The baudrate is `F_CPU / (16 * (UBRR + 1))`, or `F_CPU / (8 * (UBRR + 1))` in
+/// double speed mode (`U2X1`).  `for_clock()` tries both and picks the one closer to
+/// the requested baudrate.  On a tie, normal speed is used, because the receiver
+/// samples each bit more often there and tolerates more clock deviation.
+///
+/// Both sides together should stay within about 2%, otherwise received bytes get
+/// corrupted.  Check `error_permille()` for baudrates which are not a clean divider of
+/// the CPU clock:
+///
+/// ```
+/// // U2X1 with UBRR1 = 34, 57142 baud or -0.8%
+/// let baud = serial::Baudrate::for_clock::<delay::MHz16>(57600);
+/// assert!(baud.error_permille().abs() <= 20, "Baudrate is off by too much!");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Baudrate {
+    ubrr: u16,
+    u2x: bool,
+    requested: u32,
+    actual: u32,
+}
+
+impl Baudrate {
+    /// Best setting for `baudrate` at the CPU speed `SPEED`
+    pub fn for_clock<SPEED: clock::Clock>(baudrate: u32) -> Baudrate {
+        Baudrate::for_frequency(SPEED::FREQ, baudrate)
+    }
+
+    fn for_frequency(freq: u32, baudrate: u32) -> Baudrate {
+        let normal = Baudrate::with_divider(freq, baudrate, false);
+        let double = Baudrate::with_divider(freq, baudrate, true);
+
+        if double.deviation() < normal.deviation() {
+            double
+        } else {
+            normal
+        }
+    }
+
+    /// Nearest `UBRR` for one of the two modes
+    fn with_divider(freq: u32, baudrate: u32, u2x: bool) -> Baudrate {
+        let div = if u2x { 8 } else { 16 };
+        let baudrate = ::core::cmp::max(baudrate, 1);
+
+        // Rounded to the nearest divider, UBRR is 12 bits wide
+        let steps = (freq + div * baudrate / 2) / (div * baudrate);
+        let steps = ::core::cmp::min(::core::cmp::max(steps, 1), 0x1000);
+
+        Baudrate {
+            ubrr: (steps - 1) as u16,
+            u2x: u2x,
+            requested: baudrate,
+            actual: freq / (div * steps),
+        }
+    }
+
+    fn deviation(&self) -> u32 {
+        if self.actual > self.requested {
+            self.actual - self.requested
+        } else {
+            self.requested - self.actual
+        }
+    }
+
+    /// Value for `UBRR1`
+    pub fn ubrr(&self) -> u16 {
+        self.ubrr
+    }
+
+    /// Whether double speed mode (`U2X1`) is used
+    pub fn double_speed(&self) -> bool {
+        self.u2x
+    }
+
+    /// Baudrate which is actually reached
+    pub fn actual(&self) -> u32 {
+        self.actual
+    }
+
+    /// Deviation from the requested baudrate, in 0.1%
+    ///
+    /// Positive if the actual baudrate is too fast.
+    pub fn error_permille(&self) -> i32 {
+        let deviation = (self.deviation() as u64 * 1000 / self.requested as u64) as i32;
+        if self.actual > self.requested {
+            deviation
+        } else {
+            -deviation
+        }
+    }
+}
+
+/// Serial interface using `USART1`
+///
+/// If `SPEED` is omitted, the speed selected using the `mhz*` features is used.
+pub struct Serial<SPEED = delay::Speed> {
+    usart: atmega32u4::USART1,
+    // A byte was sent since TXC was last seen
+    sending: bool,
+    _speed: marker::PhantomData<SPEED>,
+}
+
+impl<SPEED: clock::Clock> Serial<SPEED> {
+    /// Initialize `USART1` with the given baudrate and 8N1
+    ///
+    /// Receiver and transmitter are both enabled.
+    pub fn new(usart: atmega32u4::USART1, baudrate: u32) -> Serial<SPEED> {
+        Serial::with_config(usart, baudrate, Config::default())
+    }
+
+    /// Initialize `USART1` with the given baudrate and frame format
+    ///
+    /// Receiver and transmitter are both enabled.  The divider is chosen by
+    /// [Baudrate::for_clock].
+    pub fn with_config(usart: atmega32u4::USART1, baudrate: u32, config: Config) -> Serial<SPEED> {
+        let baud = Baudrate::for_clock::<SPEED>(baudrate);
+
+        usart.ubrr_h.write(|w| unsafe { w.bits((baud.ubrr >> 8) as u8) });
+        usart.ubrr_l.write(|w| unsafe { w.bits(baud.ubrr as u8) });
+        usart.ucsr_a.write(|w| unsafe { w.bits(if baud.u2x { U2X } else { 0 }) });
+        usart.ucsr_c.write(|w| unsafe { w.bits(config.bits()) });
+        usart.ucsr_b.write(|w| unsafe { w.bits(RXEN | TXEN) });
+
+        Serial {
+            usart: usart,
+            sending: false,
+            _speed: marker::PhantomData,
+        }
+    }
+}
+
+impl<SPEED> Serial<SPEED> {
+    /// Access the raw `USART1` registers
+    ///
+    /// # Safety
+    /// Receiver and transmitter must stay enabled, and the `USART1_RX` interrupt must
+    /// only be touched through `enable_rx_buffer()`.
+    pub unsafe fn registers(&mut self) -> &atmega32u4::USART1 {
+        &self.usart
+    }
+
+    /// Wait for the last byte to be sent, then disable `USART1` and release it
+    pub fn free(mut self) -> atmega32u4::USART1 {
+        while hal::serial::Write::flush(&mut self).is_err() {}
+        self.usart.ucsr_b.reset();
+        RX_BUFFER.take();
+        self.usart
+    }
+
+    /// Receive into a ring buffer using the `USART1_RX` interrupt
+    ///
+    /// From now on, `read()` returns bytes from the buffer.  The interrupt handler has
+    /// to call [rx_isr].
+    pub fn enable_rx_buffer(&mut self) {
+        RX_BUFFER.set(RxBuffer {
+            data: [0; RX_BUFFER_SIZE],
+            head: 0,
+            len: 0,
+            error: None,
+        });
+        atmega32u4::interrupt::free(|_| {
+            self.usart
+                .ucsr_b
+                .modify(|r, w| unsafe { w.bits(r.bits() | RXCIE) })
+        });
+    }
+
+    /// Go back to polling the hardware in `read()`
+    ///
+    /// Bytes still in the buffer are lost.
+    pub fn disable_rx_buffer(&mut self) {
+        atmega32u4::interrupt::free(|_| {
+            self.usart
+                .ucsr_b
+                .modify(|r, w| unsafe { w.bits(r.bits() & !RXCIE) })
+        });
+        RX_BUFFER.take();
+    }
+
+    /// Number of received bytes waiting in the buffer
+    pub fn rx_pending(&self) -> usize {
+        RX_BUFFER.get(|buf| buf.len).unwrap_or(0)
+    }
+
+    /// Turn this serial into an interrupt driven, buffered transmitter
+    ///
+    /// The receiver is disabled.
+    pub fn into_buffered_tx(self) -> Tx {
+        TX_BUFFER.set(TxBuffer {
+            data: [0; TX_BUFFER_SIZE],
+            head: 0,
+            len: 0,
+        });
+        self.usart.ucsr_b.write(|w| unsafe { w.bits(TXEN) });
+
+        Tx {
+            usart: self.usart,
+            sending: self.sending,
+        }
+    }
+}
+
+impl<SPEED> hal::serial::Read<u8> for Serial<SPEED> {
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        if let Ok(result) = RX_BUFFER.get(|buf| buf.pop()) {
+            return result;
+        }
+
+        let status = self.usart.ucsr_a.read().bits();
+        if status & RXC == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        // The error flags belong to the byte in UDR, so read them first
+        let byte = self.usart.udr.read().bits();
+        if status & FE != 0 {
+            Err(nb::Error::Other(Error::Framing))
+        } else if status & DOR != 0 {
+            Err(nb::Error::Other(Error::Overrun))
+        } else if status & UPE != 0 {
+            Err(nb::Error::Other(Error::Parity))
+        } else {
+            Ok(byte)
+        }
+    }
+}
+
+impl<SPEED> hal::serial::Write<u8> for Serial<SPEED> {
+    type Error = Infallible;
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+        if self.usart.ucsr_a.read().bits() & UDRE == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+        transmit(&self.usart, byte);
+        self.sending = true;
+        Ok(())
+    }
+
+    /// Waits for `TXC`, so the last byte has completely left the shift register
+    fn flush(&mut self) -> nb::Result<(), Infallible> {
+        if self.sending && self.usart.ucsr_a.read().bits() & TXC == 0 {
+            Err(nb::Error::WouldBlock)
+        } else {
+            self.sending = false;
+            Ok(())
+        }
+    }
+}
+
+impl<SPEED> fmt::Write for Serial<SPEED> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            while self.usart.ucsr_a.read().bits() & UDRE == 0 {}
+            transmit(&self.usart, byte);
+        }
+        self.sending = true;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl<SPEED> ufmt::uWrite for Serial<SPEED> {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+        for &byte in s.as_bytes() {
+            while self.usart.ucsr_a.read().bits() & UDRE == 0 {}
+            transmit(&self.usart, byte);
+        }
+        self.sending = true;
+        Ok(())
+    }
+}
+
+struct TxBuffer {
+    data: [u8; TX_BUFFER_SIZE],
+    head: usize,
+    len: usize,
+}
+
+static TX_BUFFER: Global<TxBuffer> = Global::new();
+
+struct RxBuffer {
+    data: [u8; RX_BUFFER_SIZE],
+    head: usize,
+    len: usize,
+    // First error since the last read
+    error: Option<Error>,
+}
+
+impl RxBuffer {
+    fn pop(&mut self) -> nb::Result<u8, Error> {
+        if let Some(error) = self.error.take() {
+            return Err(nb::Error::Other(error));
+        }
+        if self.len == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let byte = self.data[self.head];
+        self.head = (self.head + 1) % RX_BUFFER_SIZE;
+        self.len -= 1;
+        Ok(byte)
+    }
+}
+
+static RX_BUFFER: Global<RxBuffer> = Global::new();
+
+/// Interrupt driven, buffered transmitter
+///
+/// Created using [Serial::into_buffered_tx].  Only one `Tx` can exist at a time,
+/// because it owns `USART1`.
+pub struct Tx {
+    usart: atmega32u4::USART1,
+    // A byte was queued since TXC was last seen
+    sending: bool,
+}
+
+impl Tx {
+    /// Queue a byte, returning `WouldBlock` if the buffer is full
+    pub fn try_write_byte(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+        let queued = TX_BUFFER
+            .get(|buf| {
+                if buf.len == TX_BUFFER_SIZE {
+                    false
+                } else {
+                    buf.data[(buf.head + buf.len) % TX_BUFFER_SIZE] = byte;
+                    buf.len += 1;
+                    true
+                }
+            })
+            .unwrap_or(false);
+
+        if queued {
+            self.sending = true;
+            // Start draining the buffer
+            atmega32u4::interrupt::free(|_| {
+                self.usart
+                    .ucsr_b
+                    .modify(|r, w| unsafe { w.bits(r.bits() | UDRIE) })
+            });
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Queue a byte, waiting for space in the buffer if it is full
+    pub fn write_byte(&mut self, byte: u8) {
+        while self.try_write_byte(byte).is_err() {}
+    }
+
+    /// Number of bytes waiting to be sent
+    pub fn pending(&self) -> usize {
+        TX_BUFFER.get(|buf| buf.len).unwrap_or(0)
+    }
+
+    /// Wait until all queued bytes have been sent completely
+    pub fn flush(&mut self) {
+        while hal::serial::Write::flush(self).is_err() {}
+    }
+
+    /// Access the raw `USART1` registers
+    ///
+    /// # Safety
+    /// The `UDRIE` bit and `UDR` belong to the transmit buffer, changing them loses or
+    /// corrupts queued bytes.
+    pub unsafe fn registers(&mut self) -> &atmega32u4::USART1 {
+        &self.usart
+    }
+
+    /// Wait for all queued bytes, then disable `USART1` and release it
+    pub fn free(mut self) -> atmega32u4::USART1 {
+        self.flush();
+        self.usart.ucsr_b.reset();
+        TX_BUFFER.take();
+        self.usart
+    }
+}
+
+impl hal::serial::Write<u8> for Tx {
+    type Error = Infallible;
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+        self.try_write_byte(byte)
+    }
+
+    /// Waits for the buffer to drain and for `TXC`, so the last byte has completely
+    /// left the shift register
+    fn flush(&mut self) -> nb::Result<(), Infallible> {
+        if self.pending() > 0
+            || (self.sending && self.usart.ucsr_a.read().bits() & TXC == 0)
+        {
+            Err(nb::Error::WouldBlock)
+        } else {
+            self.sending = false;
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Write for Tx {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl ufmt::uWrite for Tx {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+        for &byte in s.as_bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+/// Load a byte into `UDR1` and clear a stale `TXC`
+///
+/// `TXC` can only be set again once this byte has been shifted out.
+fn transmit(usart: &atmega32u4::USART1, byte: u8) {
+    usart.udr.write(|w| unsafe { w.bits(byte) });
+    // TXC is cleared by writing a one, FE, DOR and UPE must be written as zero
+    usart
+        .ucsr_a
+        .modify(|r, w| unsafe { w.bits((r.bits() & (U2X | MPCM)) | TXC) });
+}
+
+/// `USART1_UDRE` interrupt hook for [Tx]
+///
+/// Call this from your `USART1_UDRE` interrupt handler.  It sends the next queued
+/// byte and disables the interrupt once the buffer is empty.
+pub fn udre_isr() {
+    let usart = unsafe { &*atmega32u4::USART1::ptr() };
+
+    let next = TX_BUFFER
+        .get(|buf| {
+            if buf.len == 0 {
+                None
+            } else {
+                let byte = buf.data[buf.head];
+                buf.head = (buf.head + 1) % TX_BUFFER_SIZE;
+                buf.len -= 1;
+                Some(byte)
+            }
+        })
+        .unwrap_or(None);
+
+    match next {
+        Some(byte) => transmit(usart, byte),
+        None => usart
+            .ucsr_b
+            .modify(|r, w| unsafe { w.bits(r.bits() & !UDRIE) }),
+    }
+}
+
+/// Serial wrapper flashing RX/TX activity LEDs
+///
+/// The LEDs are active low, as on the Arduino boards.
+pub struct ActivityLeds<S, RX, TX> {
+    serial: S,
+    rx: RX,
+    tx: TX,
+    hold: u8,
+    rx_ticks: u8,
+    tx_ticks: u8,
+}
+
+impl<S, RX: OutputPin, TX: OutputPin> ActivityLeds<S, RX, TX> {
+    /// Wrap a serial
+    ///
+    /// After the last byte, the LEDs stay on for `hold` calls to `tick()`.  Both LEDs
+    /// start out off.
+    pub fn new(serial: S, mut rx: RX, mut tx: TX, hold: u8) -> ActivityLeds<S, RX, TX> {
+        rx.set_high().ok();
+        tx.set_high().ok();
+
+        ActivityLeds {
+            serial: serial,
+            rx: rx,
+            tx: tx,
+            hold: hold,
+            rx_ticks: 0,
+            tx_ticks: 0,
+        }
+    }
+
+    /// Turn off LEDs which have been idle for long enough
+    pub fn tick(&mut self) {
+        if self.rx_ticks > 0 {
+            self.rx_ticks -= 1;
+            if self.rx_ticks == 0 {
+                self.rx.set_high().ok();
+            }
+        }
+        if self.tx_ticks > 0 {
+            self.tx_ticks -= 1;
+            if self.tx_ticks == 0 {
+                self.tx.set_high().ok();
+            }
+        }
+    }
+
+    /// Turn off both LEDs and release the serial and the pins
+    pub fn free(mut self) -> (S, RX, TX) {
+        self.rx.set_high().ok();
+        self.tx.set_high().ok();
+        (self.serial, self.rx, self.tx)
+    }
+}
+
+impl<S, RX, TX> hal::serial::Read<u8> for ActivityLeds<S, RX, TX>
+where
+    S: hal::serial::Read<u8>,
+    RX: OutputPin,
+{
+    type Error = S::Error;
+
+    fn read(&mut self) -> nb::Result<u8, S::Error> {
+        let byte = self.serial.read()?;
+        self.rx.set_low().ok();
+        self.rx_ticks = self.hold;
+        Ok(byte)
+    }
+}
+
+impl<S, RX, TX> hal::serial::Write<u8> for ActivityLeds<S, RX, TX>
+where
+    S: hal::serial::Write<u8>,
+    TX: OutputPin,
+{
+    type Error = S::Error;
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), S::Error> {
+        self.serial.write(byte)?;
+        self.tx.set_low().ok();
+        self.tx_ticks = self.hold;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), S::Error> {
+        self.serial.flush()
+    }
+}
+
+impl<S, RX, TX> fmt::Write for ActivityLeds<S, RX, TX>
+where
+    S: hal::serial::Write<u8>,
+    TX: OutputPin,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        use hal::serial::Write;
+
+        for &byte in s.as_bytes() {
+            loop {
+                match self.write(byte) {
+                    Ok(()) => break,
+                    Err(nb::Error::WouldBlock) => continue,
+                    Err(nb::Error::Other(_)) => return Err(fmt::Error),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `USART1_RX` interrupt hook for the receive buffer
+///
+/// Call this from your `USART1_RX` interrupt handler after
+/// `Serial::enable_rx_buffer()`.
+pub fn rx_isr() {
+    let usart = unsafe { &*atmega32u4::USART1::ptr() };
+
+    // The error flags belong to the byte in UDR, so read them first
+    let status = usart.ucsr_a.read().bits();
+    let byte = usart.udr.read().bits();
+
+    RX_BUFFER
+        .get(|buf| {
+            // On a hardware overrun, an earlier byte was lost but this one is fine
+            let (error, keep) = if status & FE != 0 {
+                (Some(Error::Framing), false)
+            } else if status & UPE != 0 {
+                (Some(Error::Parity), false)
+            } else if buf.len == RX_BUFFER_SIZE {
+                (Some(Error::Overrun), false)
+            } else if status & DOR != 0 {
+                (Some(Error::Overrun), true)
+            } else {
+                (None, true)
+            };
+
+            if buf.error.is_none() {
+                buf.error = error;
+            }
+            if keep {
+                buf.data[(buf.head + buf.len) % RX_BUFFER_SIZE] = byte;
+                buf.len += 1;
+            }
+        })
+        .ok();
+}
+
+/// `USART1` in SPI master mode
+///
+/// If `SPEED` is omitted, the speed selected using the `mhz*` features is used.
+pub struct SpiMaster<SPEED = delay::Speed> {
+    usart: atmega32u4::USART1,
+    sck: port::portd::PD5<Output>,
+    mosi: port::portd::PD3<Output>,
+    miso: port::portd::PD2<Input<Floating>>,
+    _speed: marker::PhantomData<SPEED>,
+}
+
+impl<SPEED: clock::Clock> SpiMaster<SPEED> {
+    /// Switch `USART1` to SPI master mode
+    ///
+    /// `frequency` is rounded down to the next possible clock rate.
+    pub fn new(
+        usart: atmega32u4::USART1,
+        sck: port::portd::PD5<Output>,
+        mosi: port::portd::PD3<Output>,
+        miso: port::portd::PD2<Input<Floating>>,
+        frequency: u32,
+        mode: hal::spi::Mode,
+        order: spi::DataOrder,
+    ) -> SpiMaster<SPEED> {
+        let frequency = if frequency == 0 { 1 } else { frequency };
+        let ubrr = (SPEED::FREQ + 2 * frequency - 1) / (2 * frequency);
+        let ubrr = if ubrr == 0 { 0 } else { ubrr - 1 };
+        let ubrr = if ubrr > 0x0fff { 0x0fff } else { ubrr as u16 };
+
+        let mut ucsr_c = UMSEL_MSPIM;
+        if order == spi::DataOrder::LeastSignificantFirst {
+            ucsr_c |= UDORD;
+        }
+        if mode.phase == hal::spi::Phase::CaptureOnSecondTransition {
+            ucsr_c |= UCPHA;
+        }
+        if mode.polarity == hal::spi::Polarity::IdleHigh {
+            ucsr_c |= UCPOL;
+        }
+
+        // The datasheet requires UBRR to be zero while enabling the transmitter
+        usart.ubrr_h.write(|w| unsafe { w.bits(0) });
+        usart.ubrr_l.write(|w| unsafe { w.bits(0) });
+        usart.ucsr_c.write(|w| unsafe { w.bits(ucsr_c) });
+        usart.ucsr_b.write(|w| unsafe { w.bits(RXEN | TXEN) });
+        usart.ubrr_h.write(|w| unsafe { w.bits((ubrr >> 8) as u8) });
+        usart.ubrr_l.write(|w| unsafe { w.bits(ubrr as u8) });
+
+        SpiMaster {
+            usart: usart,
+            sck: sck,
+            mosi: mosi,
+            miso: miso,
+            _speed: marker::PhantomData,
+        }
+    }
+}
+
+impl<SPEED> SpiMaster<SPEED> {
+    /// Access the raw `USART1` registers
+    ///
+    /// # Safety
+    /// `UCSR1C` must keep master SPI mode, changing it turns the pins back into a
+    /// regular USART.
+    pub unsafe fn registers(&mut self) -> &atmega32u4::USART1 {
+        &self.usart
+    }
+
+    /// Disable `USART1` and release it together with its pins
+    pub fn free(
+        self,
+    ) -> (
+        atmega32u4::USART1,
+        port::portd::PD5<Output>,
+        port::portd::PD3<Output>,
+        port::portd::PD2<Input<Floating>>,
+    ) {
+        self.usart.ucsr_b.reset();
+        self.usart.ucsr_c.reset();
+        (self.usart, self.sck, self.mosi, self.miso)
+    }
+}
+
+impl<SPEED> hal::spi::FullDuplex<u8> for SpiMaster<SPEED> {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Infallible> {
+        if self.usart.ucsr_a.read().bits() & RXC == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(self.usart.udr.read().bits())
+    }
+
+    fn send(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+        if self.usart.ucsr_a.read().bits() & UDRE == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.usart.udr.write(|w| unsafe { w.bits(byte) });
+        Ok(())
+    }
+}
+
+impl<SPEED> hal::blocking::spi::transfer::Default<u8> for SpiMaster<SPEED> {}
+impl<SPEED> hal::blocking::spi::write::Default<u8> for SpiMaster<SPEED> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baudrate_115200_at_16mhz() {
+        // 111111 baud in normal mode, 117647 in double speed mode
+        let baud = Baudrate::for_frequency(16_000_000, 115200);
+        assert!(baud.double_speed());
+        assert_eq!(baud.ubrr(), 16);
+        assert_eq!(baud.actual(), 117647);
+        assert_eq!(baud.error_permille(), 21);
+    }
+
+    #[test]
+    fn baudrate_57600_at_16mhz() {
+        let baud = Baudrate::for_frequency(16_000_000, 57600);
+        assert!(baud.double_speed());
+        assert_eq!(baud.ubrr(), 34);
+        assert_eq!(baud.error_permille(), -7);
+    }
+
+    #[test]
+    fn baudrate_tie_uses_normal_speed() {
+        // 9615 baud in both modes
+        let baud = Baudrate::for_frequency(16_000_000, 9600);
+        assert!(!baud.double_speed());
+        assert_eq!(baud.ubrr(), 103);
+        assert_eq!(baud.actual(), 9615);
+    }
+
+    #[test]
+    fn baudrate_exact_divider() {
+        let baud = Baudrate::for_frequency(16_000_000, 1_000_000);
+        assert!(!baud.double_speed());
+        assert_eq!(baud.ubrr(), 0);
+        assert_eq!(baud.error_permille(), 0);
+    }
+
+    #[test]
+    fn baudrate_is_clamped() {
+        // UBRR1 is only 12 bits wide
+        let slow = Baudrate::for_frequency(16_000_000, 1);
+        assert_eq!(slow.ubrr(), 0x0fff);
+        let fast = Baudrate::for_frequency(16_000_000, 4_000_000);
+        assert!(fast.double_speed());
+        assert_eq!(fast.ubrr(), 0);
+        assert_eq!(fast.actual(), 2_000_000);
+    }
+}
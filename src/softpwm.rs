@@ -0,0 +1,218 @@
+//! Software PWM
+//!
+//! Only a few pins are connected to a timer output.  [SoftPwm] dims any other output
+//! pin by switching it from the `TIMER0_COMPA` interrupt.  Up to [SOFT_PWM_CHANNELS]
+//! pins can be registered, each with its own duty cycle.  Timer0 runs in CTC mode, so it can't
+//! be used for hardware PWM at the same time.
+//!
+//! Each period is divided into `steps` interrupts.  A pin is switched on at the start
+//! of a period and off again after `duty` steps, so a duty of `steps` or more keeps it
+//! on and 0 keeps it off.  The interrupt handler has to call [softpwm_isr].
+//!
+//! # Example
+//! ```
+//! fn main() {
+//!     let dp = atmega32u4::Peripherals::take().unwrap();
+//!     let mut portd = dp.PORTD.split();
+//!     let mut portf = dp.PORTF.split();
+//!
+//!     // 100Hz with 64 brightness levels
+//!     let mut pwm: softpwm::SoftPwm<delay::MHz16> = softpwm::SoftPwm::new(dp.TIMER0, 100, 64);
+//!     let led1 = pwm.register(portd.pd4.into_output(&mut portd.ddr), 16).ok().unwrap();
+//!     let led2 = pwm.register(portf.pf5.into_output(&mut portf.ddr), 48).ok().unwrap();
+//!
+//!     unsafe { atmega32u4::interrupt::enable() };
+//!
+//!     pwm.set_duty(led1, 32);
+//! }
+//!
+//! interrupt!(TIMER0_COMPA, timer0_compa_isr);
+//! fn timer0_compa_isr() {
+//!     atmega32u4_hal::softpwm::softpwm_isr();
+//! }
+//! ```
+//!
+//! # Tradeoffs
+//! The interrupt fires `frequency * steps` times per second, and each time it has to
+//! look at every registered pin.  It takes roughly 40 cycles plus 30 cycles per pin.
+//! For 8 pins at 100Hz with 64 steps, that is about 1.8 million cycles per second, or
+//! 11% of the CPU at 16MHz.  Halving the steps or the frequency halves the load.
+//! LEDs start to visibly flicker below about 100Hz, and other interrupts delaying the
+//! handler cause jitter.  For motors or anything needing a clean signal, use the
+//! hardware PWM of the [timer] module.
+use atmega32u4;
+use clock;
+use core::marker;
+use delay;
+use global::Global;
+use hal::digital::v2::OutputPin;
+use port;
+use port::mode::io::Output;
+
+/// Maximum number of pins driven by [SoftPwm]
+pub const SOFT_PWM_CHANNELS: usize = 8;
+
+// TIMSK0
+const OCIE0A: u8 = 1 << 1;
+
+// Prescaler choices for Timer0, as (divider, CS bits)
+const PRESCALERS: [(u32, u8); 5] = [(1, 1), (8, 2), (64, 3), (256, 4), (1024, 5)];
+
+struct State {
+    pins: [Option<port::Pin<Output>>; SOFT_PWM_CHANNELS],
+    duty: [u8; SOFT_PWM_CHANNELS],
+    steps: u8,
+    step: u8,
+}
+
+static STATE: Global<State> = Global::new();
+
+/// A pin registered with [SoftPwm]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Channel(u8);
+
+/// Software PWM using Timer0
+///
+/// If `SPEED` is omitted, the speed selected using the `mhz*` features is used.
+pub struct SoftPwm<SPEED = delay::Speed> {
+    tim: atmega32u4::TIMER0,
+    _speed: marker::PhantomData<SPEED>,
+}
+
+impl<SPEED: clock::Clock> SoftPwm<SPEED> {
+    /// Start Timer0 for `frequency` periods per second, divided into `steps`
+    ///
+    /// The interrupt rate `frequency * steps` is rounded to what Timer0 can reach.
+    pub fn new(tim: atmega32u4::TIMER0, frequency: u16, steps: u8) -> SoftPwm<SPEED> {
+        let steps = if steps == 0 { 1 } else { steps };
+        let rate = ::core::cmp::max(frequency as u32 * steps as u32, 1);
+
+        let mut setting = (0xff, 5);
+        for &(div, cs) in PRESCALERS.iter() {
+            let ticks = SPEED::FREQ / div / rate;
+            if ticks >= 1 && ticks <= 0x100 {
+                setting = ((ticks - 1) as u8, cs);
+                break;
+            }
+        }
+        let (top, cs) = setting;
+
+        STATE.set(State {
+            pins: [None, None, None, None, None, None, None, None],
+            duty: [0; SOFT_PWM_CHANNELS],
+            steps: steps,
+            step: 0,
+        });
+
+        // CTC mode, TOP = OCR0A (WGM = 2)
+        tim.tccr_a.write(|w| unsafe { w.wgm0().bits(0b10) });
+        tim.ocr_a.write(|w| unsafe { w.bits(top) });
+        tim.tccr_b.write(|w| unsafe { w.cs().bits(cs) });
+        tim.timsk.write(|w| unsafe { w.bits(OCIE0A) });
+
+        SoftPwm {
+            tim: tim,
+            _speed: marker::PhantomData,
+        }
+    }
+}
+
+impl<SPEED> SoftPwm<SPEED> {
+    /// Start driving `pin` with the given duty
+    ///
+    /// Any output pin can be passed, it is downgraded to a [port::Pin].  If all
+    /// channels are in use, the pin is returned.
+    pub fn register<P>(&mut self, pin: P, duty: u8) -> Result<Channel, port::Pin<Output>>
+    where
+        P: Into<port::Pin<Output>>,
+    {
+        let mut pin = Some(pin.into());
+        let channel = STATE
+            .get(|state| {
+                let free = state.pins.iter().position(|p| p.is_none());
+                if let Some(i) = free {
+                    state.pins[i] = pin.take();
+                    state.duty[i] = duty;
+                }
+                free
+            })
+            .ok()
+            .and_then(|free| free);
+
+        match channel {
+            Some(i) => Ok(Channel(i as u8)),
+            None => Err(pin.unwrap()),
+        }
+    }
+
+    /// Change the duty of a channel
+    ///
+    /// Takes effect at the start of the next period.
+    pub fn set_duty(&mut self, channel: Channel, duty: u8) {
+        STATE.get(|state| state.duty[channel.0 as usize] = duty).ok();
+    }
+
+    /// Current duty of a channel
+    pub fn get_duty(&self, channel: Channel) -> u8 {
+        STATE.get(|state| state.duty[channel.0 as usize]).unwrap_or(0)
+    }
+
+    /// Number of steps per period, the duty for a pin which is always on
+    pub fn get_max_duty(&self) -> u8 {
+        STATE.get(|state| state.steps).unwrap_or(0)
+    }
+
+    /// Stop driving a channel and return its pin, switched off
+    pub fn unregister(&mut self, channel: Channel) -> Option<port::Pin<Output>> {
+        STATE
+            .get(|state| state.pins[channel.0 as usize].take())
+            .ok()
+            .and_then(|pin| pin)
+            .map(|mut pin| {
+                pin.set_low().ok();
+                pin
+            })
+    }
+
+    /// Stop Timer0 and release it
+    ///
+    /// Registered pins keep their current level and are dropped.
+    pub fn free(self) -> atmega32u4::TIMER0 {
+        self.tim.timsk.reset();
+        self.tim.tccr_a.reset();
+        self.tim.tccr_b.reset();
+        STATE.take();
+        self.tim
+    }
+}
+
+/// `TIMER0_COMPA` interrupt hook for [SoftPwm]
+///
+/// Call this from your `TIMER0_COMPA` interrupt handler.
+pub fn softpwm_isr() {
+    // Interrupts are disabled inside the handler
+    unsafe {
+        STATE.get_unchecked(|state| {
+            let step = state.step;
+            for (pin, &duty) in state.pins.iter_mut().zip(state.duty.iter()) {
+                if let Some(ref mut pin) = *pin {
+                    if step == 0 {
+                        if duty == 0 {
+                            pin.set_low().ok();
+                        } else {
+                            pin.set_high().ok();
+                        }
+                    } else if step == duty {
+                        pin.set_low().ok();
+                    }
+                }
+            }
+
+            state.step += 1;
+            if state.step >= state.steps {
+                state.step = 0;
+            }
+        })
+    }
+    .ok();
+}
@@ -0,0 +1,381 @@
+//! SPI Master
+//!
+//! The SPI peripheral uses `PB1` (SCK), `PB2` (MOSI) and `PB3` (MISO).  [Spi]
+//! implements [embedded_hal::spi::FullDuplex] and the blocking
+//! [embedded_hal::blocking::spi::Transfer] and [embedded_hal::blocking::spi::Write]
+//! traits, so it works with most driver crates.
+//!
+//! `transfer()` works in place:  The received bytes overwrite the sent ones in the same
+//! buffer, so sending a large display buffer doesn't need a second one.  With `MOSI`
+//! connected to `MISO`, the buffer comes back unchanged.
+//!
+//! Clock rate, data order and SPI mode are configured using [Settings].  They can be
+//! changed between transactions using `reconfigure()`, for example to initialize an
+//! SD card at less than 400kHz and switch to full speed afterwards.
+//!
+//! # Mode Faults on `PB0` (SS)
+//! **If `PB0` is an input and goes low, the hardware silently switches to slave mode.**
+//! Make `PB0` an output, for example as a chip select, or make sure it stays high,
+//! e.g. using `into_pull_up_input()`.
+//!
+//! The driver checks for this on every transfer.  If the peripheral dropped out of
+//! master mode, the transfer is aborted with [Error::ModeFault] and master mode is
+//! re-enabled, so the next transfer can work again once `PB0` is high.  While `PB0`
+//! stays low, each transfer fails the same way.
+//!
+//! # Example
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut portb = dp.PORTB.split();
+//!
+//! let mut cs = portb.pb0.into_output_high(&mut portb.ddr);
+//! let mut spi = spi::Spi::new(
+//!     dp.SPI,
+//!     portb.pb1.into_output(&mut portb.ddr),
+//!     portb.pb2.into_output(&mut portb.ddr),
+//!     portb.pb3.into_floating_input(&mut portb.ddr),
+//!     spi::Settings::default(),
+//! );
+//!
+//! let mut buf = [0x9f, 0x00, 0x00, 0x00];
+//! cs.set_low().unwrap();
+//! spi.transfer(&mut buf).unwrap();
+//! cs.set_high().unwrap();
+//! ```
+//!
+//! # Transactions
+//! With several devices on the bus, `transaction()` handles the chip select: It drives
+//! `cs` low, runs the closure and drives `cs` high again once the last byte has been
+//! shifted out, whatever the closure returns:
+//!
+//! ```
+//! let id = spi.transaction(&mut flash_cs, |spi| {
+//!     let mut buf = [0x9f, 0x00, 0x00, 0x00];
+//!     spi.transfer(&mut buf)?;
+//!     Ok(buf[1])
+//! });
+//! ```
+use atmega32u4;
+use hal;
+use nb;
+use hal::digital::v2::OutputPin;
+use port;
+use port::mode::io::{Floating, Input, Output};
+
+// SPCR
+const SPE: u8 = 1 << 6;
+const DORD: u8 = 1 << 5;
+const MSTR: u8 = 1 << 4;
+const CPOL: u8 = 1 << 3;
+const CPHA: u8 = 1 << 2;
+
+// SPSR
+const SPIF: u8 = 1 << 7;
+const SPI2X: u8 = 1 << 0;
+
+/// SPI error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// `PB0` (SS) was pulled low, which switched the peripheral to slave mode
+    ///
+    /// Master mode has been re-enabled.  The transfer was aborted.
+    ModeFault,
+}
+
+/// Order in which the bits of a byte are sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataOrder {
+    /// Most significant bit first
+    MostSignificantFirst,
+    /// Least significant bit first
+    LeastSignificantFirst,
+}
+
+/// SPI clock rate as a divider of the CPU clock
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockRate {
+    /// `F_CPU / 2`
+    Div2,
+    /// `F_CPU / 4`
+    Div4,
+    /// `F_CPU / 8`
+    Div8,
+    /// `F_CPU / 16`
+    Div16,
+    /// `F_CPU / 32`
+    Div32,
+    /// `F_CPU / 64`
+    Div64,
+    /// `F_CPU / 128`
+    Div128,
+}
+
+impl ClockRate {
+    /// `SPR1:0` bits and whether `SPI2X` is needed
+    fn bits(self) -> (u8, bool) {
+        match self {
+            ClockRate::Div2 => (0b00, true),
+            ClockRate::Div4 => (0b00, false),
+            ClockRate::Div8 => (0b01, true),
+            ClockRate::Div16 => (0b01, false),
+            ClockRate::Div32 => (0b10, true),
+            ClockRate::Div64 => (0b10, false),
+            ClockRate::Div128 => (0b11, false),
+        }
+    }
+}
+
+/// SPI configuration
+// hal::spi::Mode doesn't implement Debug
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// Bit order
+    pub data_order: DataOrder,
+    /// Clock rate
+    pub clock: ClockRate,
+    /// Clock polarity and phase
+    pub mode: hal::spi::Mode,
+}
+
+impl Default for Settings {
+    /// MSB first, `F_CPU / 4` and SPI mode 0
+    fn default() -> Settings {
+        Settings {
+            data_order: DataOrder::MostSignificantFirst,
+            clock: ClockRate::Div4,
+            mode: hal::spi::MODE_0,
+        }
+    }
+}
+
+impl Settings {
+    /// `SPCR` and `SPSR` values for these settings
+    fn bits(&self) -> (u8, u8) {
+        let (spr, double) = self.clock.bits();
+
+        let mut spcr = SPE | MSTR | spr;
+        if self.data_order == DataOrder::LeastSignificantFirst {
+            spcr |= DORD;
+        }
+        if self.mode.polarity == hal::spi::Polarity::IdleHigh {
+            spcr |= CPOL;
+        }
+        if self.mode.phase == hal::spi::Phase::CaptureOnSecondTransition {
+            spcr |= CPHA;
+        }
+
+        (spcr, if double { SPI2X } else { 0 })
+    }
+}
+
+/// SPI master
+pub struct Spi {
+    spi: atmega32u4::SPI,
+    sck: port::portb::PB1<Output>,
+    mosi: port::portb::PB2<Output>,
+    miso: port::portb::PB3<Input<Floating>>,
+    // Whether a byte was sent, but SPDR hasn't been read since
+    write_in_progress: bool,
+}
+
+impl Spi {
+    /// Enable the SPI peripheral as a master
+    pub fn new(
+        spi: atmega32u4::SPI,
+        sck: port::portb::PB1<Output>,
+        mosi: port::portb::PB2<Output>,
+        miso: port::portb::PB3<Input<Floating>>,
+        settings: Settings,
+    ) -> Spi {
+        let mut spi = Spi {
+            spi: spi,
+            sck: sck,
+            mosi: mosi,
+            miso: miso,
+            write_in_progress: false,
+        };
+        spi.reconfigure(settings);
+        spi
+    }
+
+    /// Change the settings
+    ///
+    /// Waits for a running transfer to finish first.
+    pub fn reconfigure(&mut self, settings: Settings) {
+        self.wait();
+
+        let (spcr, spsr) = settings.bits();
+        self.spi.spcr.write(|w| unsafe { w.bits(spcr) });
+        self.spi.spsr.write(|w| unsafe { w.bits(spsr) });
+    }
+
+    /// Run `f` with the chip select `cs` held low
+    ///
+    /// `cs` is driven high again after `f` returned and the last byte has been
+    /// transferred, also if `f` returned early with an error.
+    pub fn transaction<CS, R, F>(&mut self, cs: &mut CS, f: F) -> R
+    where
+        CS: OutputPin,
+        F: FnOnce(&mut Spi) -> R,
+    {
+        cs.set_low().ok();
+        let result = f(self);
+        // Wait for the last byte to be shifted out, if it wasn't read back yet
+        if self.write_in_progress {
+            self.wait();
+            // Reading SPDR clears SPIF, so it can't be mistaken for the next byte
+            self.spi.spdr.read();
+        }
+        cs.set_high().ok();
+        result
+    }
+
+    /// Access the raw `SPI` registers
+    ///
+    /// # Safety
+    /// `SPCR` must keep the SPI enabled in master mode.  Writing `SPDR` directly
+    /// confuses the `FullDuplex` implementation's bookkeeping.
+    pub unsafe fn registers(&mut self) -> &atmega32u4::SPI {
+        &self.spi
+    }
+
+    /// Disable the SPI peripheral and release it together with its pins
+    pub fn free(
+        self,
+    ) -> (
+        atmega32u4::SPI,
+        port::portb::PB1<Output>,
+        port::portb::PB2<Output>,
+        port::portb::PB3<Input<Floating>>,
+    ) {
+        self.spi.spcr.reset();
+        (self.spi, self.sck, self.mosi, self.miso)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Error> {
+        if self.write_in_progress {
+            if self.spi.spsr.read().bits() & SPIF == 0 {
+                return Err(nb::Error::WouldBlock);
+            }
+            self.write_in_progress = false;
+        }
+        self.check_mode()?;
+        Ok(())
+    }
+
+    /// Wait for a running transfer to finish, ignoring a mode fault
+    fn wait(&mut self) {
+        while let Err(nb::Error::WouldBlock) = self.flush() {}
+    }
+
+    /// Re-enable master mode if a low `SS` switched to slave mode
+    fn check_mode(&mut self) -> Result<(), Error> {
+        if self.spi.spcr.read().bits() & MSTR != 0 {
+            return Ok(());
+        }
+
+        // The mode fault also sets SPIF, reading SPDR after SPSR clears it
+        self.spi.spsr.read();
+        self.spi.spdr.read();
+        self.spi.spcr.modify(|r, w| unsafe { w.bits(r.bits() | MSTR) });
+        Err(Error::ModeFault)
+    }
+}
+
+impl hal::spi::FullDuplex<u8> for Spi {
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        self.flush()?;
+        // Reading SPSR (in flush) followed by SPDR clears SPIF
+        Ok(self.spi.spdr.read().bits())
+    }
+
+    fn send(&mut self, byte: u8) -> nb::Result<(), Error> {
+        self.flush()?;
+        self.spi.spdr.write(|w| unsafe { w.bits(byte) });
+        self.write_in_progress = true;
+        Ok(())
+    }
+}
+
+/// Each byte of `words` is overwritten with the byte received while sending it, so no
+/// second buffer is needed.
+impl hal::blocking::spi::Transfer<u8> for Spi {
+    type Error = Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Error> {
+        self.wait();
+        self.check_mode()?;
+
+        let spi = self;
+        transfer_in_place(words, |word| {
+            spi.spi.spdr.write(|w| unsafe { w.bits(word) });
+            while spi.spi.spsr.read().bits() & SPIF == 0 {}
+            spi.check_mode()?;
+            Ok(spi.spi.spdr.read().bits())
+        })?;
+        Ok(words)
+    }
+}
+
+/// Replace each word by the one received while sending it
+///
+/// Stops at the first error, the remaining words are left as they are.
+fn transfer_in_place<E, F>(words: &mut [u8], mut exchange: F) -> Result<(), E>
+where
+    F: FnMut(u8) -> Result<u8, E>,
+{
+    for word in words.iter_mut() {
+        *word = exchange(*word)?;
+    }
+    Ok(())
+}
+
+// The default implementation sends a byte and reads the received one back each time,
+// which also clears SPIF.
+impl hal::blocking::spi::write::Default<u8> for Spi {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_64_bytes_in_place() {
+        let mut words = [0u8; 64];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = i as u8;
+        }
+
+        // The device answers each byte with its complement
+        let mut sent = 0;
+        let res: Result<(), ()> = transfer_in_place(&mut words, |word| {
+            assert_eq!(word, sent as u8);
+            sent += 1;
+            Ok(!word)
+        });
+
+        assert_eq!(res, Ok(()));
+        assert_eq!(sent, 64);
+        for (i, word) in words.iter().enumerate() {
+            assert_eq!(*word, !(i as u8));
+        }
+    }
+
+    #[test]
+    fn transfer_stops_at_mode_fault() {
+        let mut words = [1u8, 2, 3, 4];
+        let mut count = 0;
+        let res = transfer_in_place(&mut words, |word| {
+            count += 1;
+            if count == 3 {
+                Err(Error::ModeFault)
+            } else {
+                Ok(word + 10)
+            }
+        });
+
+        assert_eq!(res, Err(Error::ModeFault));
+        assert_eq!(words, [11, 12, 3, 4]);
+    }
+}
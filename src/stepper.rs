@@ -0,0 +1,317 @@
+//! Stepper Motors
+//!
+//! Two kinds of stepper drivers are supported:
+//!
+//! * [Stepper] drives the four coils of a unipolar motor (for example the 28BYJ-48
+//!   with a ULN2003 board) or the four inputs of an H-bridge directly.
+//! * [StepDir] controls a driver chip like the A4988 or DRV8825 using a step and a
+//!   direction pin.
+//!
+//! ## Sequences
+//! [Stepper] supports three coil sequences, coils are named `A` to `D` in the order
+//! they are passed to `new()`:
+//!
+//! | Sequence | Coils                          | Torque | Steps per cycle |
+//! |----------|--------------------------------|--------|-----------------|
+//! | `Wave`   | `A`, `B`, `C`, `D`             | Low    | 4               |
+//! | `Full`   | `AB`, `BC`, `CD`, `DA`         | High   | 4               |
+//! | `Half`   | `A`, `AB`, `B`, `BC`, `C`, ... | Medium | 8               |
+//!
+//! `Half` doubles the resolution.
+//!
+//! ## Switching the Coils
+//! The coils are switched one after another, in the order `A` to `D`, so for a few
+//! cycles between two steps the motor sees an intermediate pattern.  `Half` steps
+//! only change a single coil and have none.  Between two `Wave` steps, either no
+//! coil or the two coils of the half step in between are on.  Between two `Full`
+//! steps, one or three coils are on.  If the coils need to change at exactly the same
+//! time, connect them to one port and write `pattern()` using `write_port()` on the
+//! port's `DDR` after each step.
+//!
+//! # Example
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut portb = dp.PORTB.split();
+//! let mut delay = delay::Delay::<delay::MHz16>::new();
+//!
+//! let mut motor = stepper::Stepper::new(
+//!     portb.pb4.into_output(&mut portb.ddr),
+//!     portb.pb5.into_output(&mut portb.ddr),
+//!     portb.pb6.into_output(&mut portb.ddr),
+//!     portb.pb7.into_output(&mut portb.ddr),
+//!     stepper::Sequence::Half,
+//! );
+//!
+//! // One revolution of a 28BYJ-48, 2ms per step
+//! motor.step_n(4096, stepper::Direction::Forward, &mut delay, 2000);
+//! ```
+use core::marker;
+use clock;
+use delay;
+use hal::blocking::delay::DelayUs;
+use hal::digital::v2::OutputPin;
+
+/// Direction of rotation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Forward, `A` towards `D`
+    Forward,
+    /// Backward, `D` towards `A`
+    Backward,
+}
+
+/// Coil sequence of a [Stepper]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sequence {
+    /// One coil at a time
+    Wave,
+    /// Two coils at a time
+    Full,
+    /// Alternating between one and two coils
+    Half,
+}
+
+/// Half step sequence, bit 0 is coil `A`.  Wave steps are the even entries, full
+/// steps the odd ones.
+const HALF_STEPS: [u8; 8] = [
+    0b0001, 0b0011, 0b0010, 0b0110, 0b0100, 0b1100, 0b1000, 0b1001,
+];
+
+/// Four coil stepper motor
+pub struct Stepper<A, B, C, D> {
+    a: A,
+    b: B,
+    c: C,
+    d: D,
+    sequence: Sequence,
+    // Index into HALF_STEPS
+    phase: u8,
+}
+
+impl<A, B, C, D> Stepper<A, B, C, D>
+where
+    A: OutputPin,
+    B: OutputPin,
+    C: OutputPin,
+    D: OutputPin,
+{
+    /// Create a stepper from its four coil pins
+    ///
+    /// The coils of the first step are energized right away.
+    pub fn new(a: A, b: B, c: C, d: D, sequence: Sequence) -> Stepper<A, B, C, D> {
+        let mut stepper = Stepper {
+            a: a,
+            b: b,
+            c: c,
+            d: d,
+            sequence: sequence,
+            phase: if sequence == Sequence::Full { 1 } else { 0 },
+        };
+        stepper.output();
+        stepper
+    }
+
+    /// Change the coil sequence
+    ///
+    /// The motor might move by half a step to get onto the new sequence.
+    pub fn set_sequence(&mut self, sequence: Sequence) {
+        self.sequence = sequence;
+        match sequence {
+            Sequence::Wave => self.phase &= !1,
+            Sequence::Full => self.phase |= 1,
+            Sequence::Half => (),
+        }
+        self.output();
+    }
+
+    /// Advance by one step
+    pub fn step(&mut self, direction: Direction) {
+        let increment = match self.sequence {
+            Sequence::Half => 1,
+            _ => 2,
+        };
+        self.phase = match direction {
+            Direction::Forward => (self.phase + increment) % 8,
+            Direction::Backward => (self.phase + 8 - increment) % 8,
+        };
+        self.output();
+    }
+
+    /// Do `count` steps, waiting `step_us` microseconds after each one
+    pub fn step_n<DELAY: DelayUs<u16>>(
+        &mut self,
+        count: u16,
+        direction: Direction,
+        delay: &mut DELAY,
+        step_us: u16,
+    ) {
+        for _ in 0..count {
+            self.step(direction);
+            delay.delay_us(step_us);
+        }
+    }
+
+    /// Current coil pattern, bit 0 is coil `A`
+    pub fn pattern(&self) -> u8 {
+        HALF_STEPS[self.phase as usize]
+    }
+
+    /// Turn off all coils
+    ///
+    /// The motor loses its holding torque.  The next step energizes the coils again.
+    pub fn release(&mut self) {
+        self.a.set_low().ok();
+        self.b.set_low().ok();
+        self.c.set_low().ok();
+        self.d.set_low().ok();
+    }
+
+    /// Turn off all coils and release the pins
+    pub fn free(mut self) -> (A, B, C, D) {
+        self.release();
+        (self.a, self.b, self.c, self.d)
+    }
+
+    // Coils are set one by one, see "Switching the Coils" in the module docs
+    fn output(&mut self) {
+        let pattern = self.pattern();
+        set(&mut self.a, pattern & 0b0001 != 0);
+        set(&mut self.b, pattern & 0b0010 != 0);
+        set(&mut self.c, pattern & 0b0100 != 0);
+        set(&mut self.d, pattern & 0b1000 != 0);
+    }
+}
+
+fn set<P: OutputPin>(pin: &mut P, high: bool) {
+    if high {
+        pin.set_high().ok();
+    } else {
+        pin.set_low().ok();
+    }
+}
+
+/// Stepper driver with step and direction inputs
+///
+/// The direction pin is high for [Direction::Forward].  Step pulses are 2µs long,
+/// which is enough for common driver chips.  If `SPEED` is omitted, the speed
+/// selected using the `mhz*` features is used.
+pub struct StepDir<STEP, DIR, SPEED = delay::Speed> {
+    step: STEP,
+    dir: DIR,
+    _speed: marker::PhantomData<SPEED>,
+}
+
+impl<STEP, DIR, SPEED> StepDir<STEP, DIR, SPEED>
+where
+    STEP: OutputPin,
+    DIR: OutputPin,
+    SPEED: clock::Clock,
+    delay::Delay<SPEED>: DelayUs<u16>,
+{
+    /// Create a driver from its step and direction pins
+    pub fn new(mut step: STEP, dir: DIR) -> StepDir<STEP, DIR, SPEED> {
+        step.set_low().ok();
+
+        StepDir {
+            step: step,
+            dir: dir,
+            _speed: marker::PhantomData,
+        }
+    }
+
+    /// Do a single step
+    pub fn step(&mut self, direction: Direction) {
+        set(&mut self.dir, direction == Direction::Forward);
+
+        let mut delay = delay::Delay::<SPEED>::new();
+        // Direction setup time
+        delay.delay_us(1u16);
+        self.step.set_high().ok();
+        delay.delay_us(2u16);
+        self.step.set_low().ok();
+    }
+
+    /// Do `count` steps, waiting `step_us` microseconds after each one
+    pub fn step_n<DELAY: DelayUs<u16>>(
+        &mut self,
+        count: u16,
+        direction: Direction,
+        delay: &mut DELAY,
+        step_us: u16,
+    ) {
+        for _ in 0..count {
+            self.step(direction);
+            delay.delay_us(step_us);
+        }
+    }
+
+    /// Release the pins
+    pub fn free(self) -> (STEP, DIR) {
+        (self.step, self.dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_steps_change_one_coil() {
+        for i in 0..8 {
+            let next = HALF_STEPS[(i + 1) % 8];
+            assert_eq!((HALF_STEPS[i] ^ next).count_ones(), 1);
+        }
+    }
+
+    #[test]
+    fn wave_and_full_steps() {
+        for i in 0..8 {
+            let coils = if i % 2 == 0 { 1 } else { 2 };
+            assert_eq!(HALF_STEPS[i].count_ones(), coils);
+        }
+    }
+
+    #[test]
+    fn no_opposite_coils() {
+        for pattern in HALF_STEPS.iter() {
+            assert_ne!(pattern & 0b0101, 0b0101);
+            assert_ne!(pattern & 0b1010, 0b1010);
+        }
+    }
+
+    // Patterns seen while switching the coils from `from` to `to`, in order A to D
+    fn intermediates(from: u8, to: u8) -> [u8; 3] {
+        let mut pattern = from;
+        let mut seen = [0; 3];
+        for coil in 0..3 {
+            pattern = (pattern & !(1 << coil)) | (to & (1 << coil));
+            seen[coil] = pattern;
+        }
+        seen
+    }
+
+    #[test]
+    fn wave_intermediate_patterns() {
+        for from in (0..8).step_by(2) {
+            for &distance in [2, 6].iter() {
+                let to = HALF_STEPS[(from + distance) % 8];
+                for &pattern in intermediates(HALF_STEPS[from], to).iter() {
+                    assert!(pattern == 0 || HALF_STEPS.contains(&pattern));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn full_intermediate_patterns() {
+        for from in (1..8).step_by(2) {
+            for &distance in [2, 6].iter() {
+                let to = HALF_STEPS[(from + distance) % 8];
+                for &pattern in intermediates(HALF_STEPS[from], to).iter() {
+                    // Never all coils off, never all on
+                    assert!(pattern != 0 && pattern != 0b1111);
+                }
+            }
+        }
+    }
+}
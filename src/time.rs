@@ -0,0 +1,212 @@
+//! Timekeeping
+//!
+//! [Monotonic] counts the time since it was started, like `millis()` and `micros()`
+//! of the Arduino core.  It runs Timer0 with a prescaler of 64 and counts its
+//! overflows in the `TIMER0_OVF` interrupt, so the interrupt handler has to call
+//! [overflow_isr].  Timer0 can't be used for PWM or [softpwm] at the same time.
+//!
+//! | Speed   | Resolution | Overflow interrupt |
+//! |---------|------------|--------------------|
+//! | `MHz16` | 4µs        | every 1024µs       |
+//! | `MHz8`  | 8µs        | every 2048µs       |
+//! | `MHz1`  | 64µs       | every 16384µs      |
+//!
+//! `micros()` wraps around after about 71.6 minutes and `millis()` after about 49.7
+//! days, at any speed.  Both wrap cleanly from `u32::MAX` to 0, so compare timestamps
+//! using `wrapping_sub()` and the wrap doesn't matter.  The overflows are counted in
+//! a `u64`, which doesn't wrap in the lifetime of the device.
+//!
+//! # Example
+//! ```
+//! fn main() {
+//!     let dp = atmega32u4::Peripherals::take().unwrap();
+//!     let time: time::Monotonic<delay::MHz16> = time::Monotonic::new(dp.TIMER0);
+//!
+//!     unsafe { atmega32u4::interrupt::enable() };
+//!
+//!     let start = time.micros();
+//!     do_something();
+//!     let elapsed = time.micros().wrapping_sub(start);
+//! }
+//!
+//! interrupt!(TIMER0_OVF, timer0_ovf_isr);
+//! fn timer0_ovf_isr() {
+//!     atmega32u4_hal::time::overflow_isr();
+//! }
+//! ```
+//!
+//! # Reading the Counter
+//! The time is put together from the overflow count and `TCNT0`.  If the timer
+//! overflows between reading the two, the result would jump back by 256 ticks.  Both
+//! are read with interrupts disabled, and an overflow which happened in the meantime
+//! is detected using the pending `TOV0` flag and added.  Each call returns at least
+//! the value of the previous one (until the counter wraps around).
+//!
+//! This only works if `TIMER0_OVF` is serviced within 256 ticks, 1ms at 16MHz.  Code
+//! keeping interrupts disabled for longer loses time.
+use atmega32u4;
+use clock;
+use core::marker;
+use delay;
+use global::Global;
+
+// TIMSK0
+const TOIE0: u8 = 1 << 0;
+// TIFR0
+const TOV0: u8 = 1 << 0;
+
+// CS0 bits for a prescaler of 64
+const CS_64: u8 = 0b011;
+const PRESCALER: u64 = 64;
+
+static OVERFLOWS: Global<u64> = Global::new();
+
+/// Time since start using Timer0
+///
+/// If `SPEED` is omitted, the speed selected using the `mhz*` features is used.
+pub struct Monotonic<SPEED = delay::Speed> {
+    tim: atmega32u4::TIMER0,
+    _speed: marker::PhantomData<SPEED>,
+}
+
+impl<SPEED: clock::Clock> Monotonic<SPEED> {
+    /// Start counting from 0
+    ///
+    /// Timer0 runs in normal mode with its overflow interrupt enabled.
+    pub fn new(tim: atmega32u4::TIMER0) -> Monotonic<SPEED> {
+        OVERFLOWS.set(0);
+
+        tim.tccr_a.reset();
+        tim.tcnt.write(|w| unsafe { w.bits(0) });
+        // The flag is cleared by writing a one
+        tim.tifr.write(|w| unsafe { w.bits(TOV0) });
+        tim.timsk.write(|w| unsafe { w.bits(TOIE0) });
+        tim.tccr_b.write(|w| unsafe { w.cs().bits(CS_64) });
+
+        Monotonic {
+            tim: tim,
+            _speed: marker::PhantomData,
+        }
+    }
+
+    /// Microseconds since `new()`
+    pub fn micros(&self) -> u32 {
+        ticks_to_micros(self.ticks(), SPEED::FREQ)
+    }
+
+    /// Milliseconds since `new()`
+    pub fn millis(&self) -> u32 {
+        ticks_to_millis(self.ticks(), SPEED::FREQ)
+    }
+}
+
+impl<SPEED> Monotonic<SPEED> {
+    /// Timer ticks since start, consistent with a pending overflow
+    fn ticks(&self) -> u64 {
+        atmega32u4::interrupt::free(|_| {
+            let overflows = unsafe { OVERFLOWS.get_unchecked(|o| *o) }.unwrap_or(0);
+            let tcnt = self.tim.tcnt.read().bits();
+
+            // TCNT0 wrapped before it was read, but the interrupt didn't run yet.  If
+            // TCNT0 is still 255, the overflow happened after reading it.
+            let pending = self.tim.tifr.read().bits() & TOV0 != 0 && tcnt != 0xff;
+
+            ticks(overflows, tcnt, pending)
+        })
+    }
+
+    /// Stop Timer0 and release it
+    pub fn free(self) -> atmega32u4::TIMER0 {
+        self.tim.timsk.reset();
+        self.tim.tccr_b.reset();
+        OVERFLOWS.take();
+        self.tim
+    }
+}
+
+/// Timer ticks from the overflow count and `TCNT0`
+fn ticks(overflows: u64, tcnt: u8, pending: bool) -> u64 {
+    let overflows = overflows + if pending { 1 } else { 0 };
+    (overflows << 8) | tcnt as u64
+}
+
+// Both are converted from the full tick count, so they wrap cleanly at u32::MAX.  The
+// multiplication only overflows after thousands of years.
+fn ticks_to_micros(ticks: u64, freq: u32) -> u32 {
+    (ticks * PRESCALER / (freq as u64 / 1_000_000)) as u32
+}
+
+fn ticks_to_millis(ticks: u64, freq: u32) -> u32 {
+    (ticks * PRESCALER / (freq as u64 / 1_000)) as u32
+}
+
+/// `TIMER0_OVF` interrupt hook for [Monotonic]
+///
+/// Call this from your `TIMER0_OVF` interrupt handler.
+pub fn overflow_isr() {
+    // Interrupts are disabled inside the handler
+    unsafe { OVERFLOWS.get_unchecked(|o| *o = o.wrapping_add(1)) }.ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEEDS: [u32; 6] = [
+        24_000_000, 20_000_000, 16_000_000, 12_000_000, 8_000_000, 1_000_000,
+    ];
+
+    #[test]
+    fn pending_overflow_is_added() {
+        assert_eq!(ticks(3, 10, false), 3 * 256 + 10);
+        assert_eq!(ticks(3, 10, true), 4 * 256 + 10);
+    }
+
+    // The overflow count used to be a u32, wrapping after 2^32 overflows
+    #[test]
+    fn no_jump_past_u32_overflows() {
+        for &freq in SPEEDS.iter() {
+            let mut last_micros = ticks_to_micros(ticks(0xffff_fff0, 0, false), freq);
+            let mut last_millis = ticks_to_millis(ticks(0xffff_fff0, 0, false), freq);
+            for overflows in 0xffff_fff0u64..0x1_0000_0010 {
+                for &tcnt in [0, 0x80, 0xff].iter() {
+                    let t = ticks(overflows, tcnt, false);
+                    let micros = ticks_to_micros(t, freq);
+                    let millis = ticks_to_millis(t, freq);
+
+                    // At most 128 ticks of 64µs at 1MHz have passed
+                    assert!(micros.wrapping_sub(last_micros) <= 128 * 64);
+                    assert!(millis.wrapping_sub(last_millis) <= 9);
+                    last_micros = micros;
+                    last_millis = millis;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wrap_at_u32_max() {
+        for &freq in SPEEDS.iter() {
+            let ticks_per_second = freq as u64 / PRESCALER;
+            let mut last_micros = 0;
+            let mut last_millis = 0;
+            // Over the micros() wrap after about 71.6 minutes and the millis() wrap
+            // after about 49.7 days, one step of about 1/10s at a time
+            for &start in [4290u64, 4_294_900].iter() {
+                for step in 0..2000 {
+                    let t = start * ticks_per_second + step * ticks_per_second / 10;
+                    let micros = ticks_to_micros(t, freq);
+                    let millis = ticks_to_millis(t, freq);
+                    if step > 0 {
+                        let dus = micros.wrapping_sub(last_micros);
+                        let dms = millis.wrapping_sub(last_millis);
+                        assert!(dus >= 99_900 && dus <= 100_100, "{} {}", freq, dus);
+                        assert!(dms >= 99 && dms <= 101, "{} {}", freq, dms);
+                    }
+                    last_micros = micros;
+                    last_millis = millis;
+                }
+            }
+        }
+    }
+}
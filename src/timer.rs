@@ -10,13 +10,84 @@
 //! let mut pwm4 = atmega32u4_hal::timer::Timer4Pwm::new(dp.TIMER4);
 //! ```
 //!
-//! Next up, convert your pin into a PWM output.  You can only configure PWM for pins
-//! already configured as outputs:
+//! Each timer can run in either fast or phase-correct mode, see [PwmMode].  Use
+//! `with_mode()` instead of `new()` to select one:
+//!
+//! ```
+//! let mut pwm1 = Timer1Pwm::with_mode(dp.TIMER1, timer::PwmMode::PhaseCorrectPwm);
+//! ```
+//!
+//! To use the timer for something else later on, `free()` returns the raw peripheral
+//! after resetting the timer's control registers:
+//!
+//! | Timer       | Reset registers                                     |
+//! |-------------|-----------------------------------------------------|
+//! | `Timer0Pwm` | `TCCR0A`, `TCCR0B`                                  |
+//! | `Timer1Pwm` | `TCCR1A`, `TCCR1B`, `TCCR1C`                        |
+//! | `Timer3Pwm` | `TCCR3A`, `TCCR3B`, `TCCR3C`                        |
+//! | `Timer4Pwm` | `TCCR4A`, `TCCR4B`, `TCCR4C`, `TCCR4D`, `TCCR4E`    |
+//!
+//! Next up, convert your pin into a PWM output.  Input pins (like freshly split ones)
+//! need the `DDR` to be switched to an output at the same time:
+//!
+//! ```
+//! let mut pin = portc.pc7.into_pwm(&mut portc.ddr, &mut pwm4);
+//! ```
+//!
+//! Pins already configured as outputs only need the timer:
 //!
 //! ```
 //! let mut pin = portc.pc7.into_output(&mut portc.ddr).into_pwm(&mut pwm4);
 //! ```
 //!
+//! The timer is part of the pin's type, for example `PC7<Pwm<Timer4Pwm>>`.  `into_pwm()`
+//! only exists for the timer a pin is connected to, and `set_duty()`/`get_duty()`
+//! always use that timer's compare register, so mixing up pins and timers does not
+//! compile:
+//!
+//! ```compile_fail
+//! let mut pwm0 = atmega32u4_hal::timer::Timer0Pwm::new(dp.TIMER0);
+//! // PC7 is not connected to Timer0
+//! let mut pin = portc.pc7.into_pwm(&mut portc.ddr, &mut pwm0);
+//! ```
+//!
+//! To use the pin as a normal output again, call `into_output()` on it.  This only
+//! disconnects this one channel; other PWM pins of the same timer keep running.
+//!
+//! Timer4 is a 10-bit timer, but it is used in 8-bit mode like the others: `TC4H`,
+//! which holds the upper two bits of every 10-bit access, is cleared when the timer
+//! is created and the duty is written to the 8-bit `OCR4x` registers only.  Don't
+//! write `TC4H` yourself while using `Timer4Pwm`.
+//!
+//! Instead of raw duty values, [PwmPinExt] allows setting the duty in percent or as a
+//! fraction:
+//!
+//! ```
+//! pin.set_duty_percent(25);
+//! pin.set_duty_fraction(1, 3);
+//! ```
+//!
+//! `fade()` walks the duty from one value to another, waiting between the steps.  An
+//! LED breathing in and out once per second looks like this:
+//!
+//! ```
+//! let mut delay = delay::Delay::<delay::MHz16>::new();
+//! let mut led = portc.pc7.into_pwm(&mut portc.ddr, &mut pwm4);
+//!
+//! loop {
+//!     // 0 to 255 in steps of 5 with 10ms each, about 0.5s
+//!     led.fade(0, 255, 5, &mut delay, 10);
+//!     led.fade(255, 0, 5, &mut delay, 10);
+//! }
+//! ```
+//!
+//! To line something up with the PWM cycle, `counter()` reads the current value of
+//! the timer's counter.  For the 16-bit timers, the two halves have to be read in a
+//! fixed order through a shared register, so this happens with interrupts disabled.
+//!
+//! For active-low loads like common-anode RGB LEDs, the output can be inverted using
+//! `set_polarity(Polarity::Inverted)`.
+//!
 //! ## Pins supporting PWM
 //! Only the following pins support PWM:
 //!
@@ -29,7 +100,7 @@
 //! | [atmega32u4::TIMER1] | `OC1C`  | [atmega32u4::PORTB] | `PB7` |
 //! | [atmega32u4::TIMER3] | `OC3A`  | [atmega32u4::PORTC] | `PC6` |
 //! | [atmega32u4::TIMER4] | `OC4A`  | [atmega32u4::PORTC] | `PC7` |
-//! | [atmega32u4::TIMER4] | `OC4B`  | [atmega32u4::PORTC] | `PB6` |
+//! | [atmega32u4::TIMER4] | `OC4B`  | [atmega32u4::PORTB] | `PB6` |
 //! | [atmega32u4::TIMER4] | `OC4D`  | [atmega32u4::PORTD] | `PD7` |
 //!
 //! # Example
@@ -42,23 +113,341 @@
 //! // Split portc into 8 pins
 //! let mut portc = dp.PORTC.split();
 //!
-//! // First make the pin an output, then enable the PWM timer
-//! let mut pin = portc.pc7.into_output(&mut portc.ddr).into_pwm(&mut pwm4);
+//! // Make the pin an output and connect it to the PWM timer
+//! let mut pin = portc.pc7.into_pwm(&mut portc.ddr, &mut pwm4);
 //!
 //! // Set a duty cycle
 //! pin.set_duty(pin.get_max_duty() / 2);
 //! ```
+//!
+//! # Shared Period PWM
+//! [MultiPwm] implements [embedded_hal::Pwm] for the 16-bit timers.  Unlike the 8-bit
+//! `Timer#Pwm` timers, the PWM frequency can be chosen freely using `set_period()` and
+//! the duty cycle has up to 16 bits of resolution.  All channels of a timer share the
+//! period, which is what you want for RGB LEDs or several servos.  `get_max_duty()`
+//! depends on the period, so query it after changing the period.  `set_frequency_hz()`
+//! does the same as `set_period()`, but returns the frequency that was actually reached.
+//!
+//! | Timer                | Channel      | Pin   |
+//! |----------------------|--------------|-------|
+//! | [atmega32u4::TIMER1] | `Channel::A` | `PB5` |
+//! | [atmega32u4::TIMER1] | `Channel::B` | `PB6` |
+//! | [atmega32u4::TIMER1] | `Channel::C` | `PB7` |
+//! | [atmega32u4::TIMER3] | `Channel::A` | `PC6` |
+//!
+//! The pins of enabled channels have to be configured as outputs.  Channels without a
+//! pin are ignored.
+//!
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut portb = dp.PORTB.split();
+//! let _red = portb.pb5.into_output(&mut portb.ddr);
+//! let _green = portb.pb6.into_output(&mut portb.ddr);
+//!
+//! let mut pwm: timer::MultiPwm<_, delay::MHz16> =
+//!     timer::MultiPwm::new(dp.TIMER1, timer::Hertz(1000));
+//! let max = pwm.get_max_duty();
+//! pwm.set_duty(timer::Channel::A, max / 4);
+//! pwm.set_duty(timer::Channel::B, max / 2);
+//! pwm.enable(timer::Channel::A);
+//! pwm.enable(timer::Channel::B);
+//! ```
+//!
+//! # Input Capture
+//! [InputCapture] latches the counter of a 16-bit timer on an edge of its `ICPn` pin,
+//! to measure periods and pulse widths in hardware.  It implements
+//! [embedded_hal::Capture], whose resolution is the timer [Prescaler]:
+//!
+//! | Timer                | Pin   |
+//! |----------------------|-------|
+//! | [atmega32u4::TIMER1] | `PD4` |
+//! | [atmega32u4::TIMER3] | `PC7` |
+//!
+//! The counter wraps after 65536 ticks, so compute differences using
+//! `wrapping_sub()` and pick a prescaler which makes the measured period shorter
+//! than that.
+//!
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut portd = dp.PORTD.split();
+//!
+//! let mut capture = timer::InputCapture::new(
+//!     dp.TIMER1,
+//!     portd.pd4.into_floating_input(&mut portd.ddr),
+//!     timer::Edge::Rising,
+//!     timer::Prescaler::Div64,
+//! );
+//! capture.enable(());
+//!
+//! let first = nb::block!(capture.capture(())).unwrap();
+//! let second = nb::block!(capture.capture(())).unwrap();
+//! // Period in units of 4µs at 16MHz
+//! let period = second.wrapping_sub(first);
+//! ```
+//!
+//! # Tones
+//! The 16-bit timers can also generate a square wave for a piezo buzzer using
+//! [Tone].  The timer runs in CTC mode and toggles the `OCnA` pin on every compare
+//! match:
+//!
+//! | Timer                | Pin   |
+//! |----------------------|-------|
+//! | [atmega32u4::TIMER1] | `PB5` |
+//! | [atmega32u4::TIMER3] | `PC6` |
+//!
+//! The prescaler is chosen automatically as the smallest one (1, 8, 64, 256, 1024)
+//! that can reach the requested frequency.  The achievable range is
+//! `F_CPU / (2 * 1024 * 65536)` up to `F_CPU / 2`.  At 16MHz that is everything from
+//! 1Hz up to the 65535Hz limit of the `u16` argument.  The compare value is rounded
+//! down, so the output is slightly too high if `F_CPU / (2 * prescaler * frequency)`
+//! is not an integer.  The error grows with the frequency, at 16MHz it stays below
+//! 0.3% up to 20kHz.
+//!
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut portb = dp.PORTB.split();
+//!
+//! let buzzer = portb.pb5.into_output(&mut portb.ddr);
+//! let mut tone: timer::Tone<_, _, delay::MHz16> = timer::Tone::new(dp.TIMER1, buzzer);
+//!
+//! // Play an A4 for half a second
+//! tone.play_for(440, 500);
+//! ```
+//!
+//! # Servos
+//! [ServoTimer] configures a 16-bit timer for the 50Hz signal hobby servos expect.
+//! Each `OCnx` pin of the timer can then drive one [Servo]:
+//!
+//! | Timer                | Pins           |
+//! |----------------------|----------------|
+//! | [atmega32u4::TIMER1] | `PB5`, `PB6`   |
+//! | [atmega32u4::TIMER3] | `PC6`          |
+//!
+//! By default, 0° maps to a 1.0ms pulse and 180° to a 2.0ms pulse.  Use
+//! `set_limits()` if your servo needs a wider range.
+//!
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut portb = dp.PORTB.split();
+//!
+//! let mut servos: timer::ServoTimer<_, delay::MHz16> = timer::ServoTimer::new(dp.TIMER1);
+//! let mut servo = servos.servo_a(portb.pb5.into_output(&mut portb.ddr));
+//!
+//! servo.set_angle(90);
+//! ```
+//!
+//! # Delays
+//! [TimerDelay] implements the blocking `DelayMs` and `DelayUs` traits using a 16-bit
+//! timer instead of a busy loop.  The timer runs in CTC mode with a prescaler of 8 and
+//! the delay polls its compare match flag, so interrupts firing in between don't
+//! stretch the delay, unless they take longer than the remaining time.  The
+//! resolution is 8 CPU cycles, 0.5µs at 16MHz.  Longer delays are split into several
+//! timer periods of at most 65536 ticks.
+//!
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//! let mut delay: timer::TimerDelay<_, delay::MHz16> = timer::TimerDelay::new(dp.TIMER3);
+//!
+//! delay.delay_ms(1500u16);
+//! ```
+//!
+//! Use [delay::Delay] if you need all timers or delays below a few microseconds.
+//!
+//! # Periodic Interrupts
+//! [Periodic] turns a 16-bit timer into a timebase which overflows at a fixed rate,
+//! for example to run a control loop on Timer3 while Timer1 does input capture.  The
+//! period is `prescaler * (top + 1) / F_CPU`.  At 16MHz:
+//!
+//! | Prescaler | Resolution | Longest period | 1ms with `top` |
+//! |-----------|------------|----------------|----------------|
+//! | `Div1`    | 62.5ns     | 4.1ms          | 15999          |
+//! | `Div8`    | 0.5µs      | 32.8ms         | 1999           |
+//! | `Div64`   | 4µs        | 262ms          | 249            |
+//! | `Div256`  | 16µs       | 1.05s          | -              |
+//! | `Div1024` | 64µs       | 4.19s          | -              |
+//!
+//! Either poll `wait()`, or enable the `TIMERn_OVF` interrupt using `listen()`.  The
+//! handler can do its work directly; if it calls [timer3_ovf_isr] (or
+//! [timer1_ovf_isr]), `ticks()` counts the periods.  `OCFnA` is set at the same
+//! moment, so `listen_compare()` and the `TIMERn_COMPA` interrupt can be used instead,
+//! for example if another driver already handles `TIMERn_OVF`.  Its handler can call
+//! the same hook:
+//!
+//! ```
+//! fn main() {
+//!     let dp = atmega32u4::Peripherals::take().unwrap();
+//!
+//!     // 100Hz: 16MHz / 64 / 2500
+//!     let mut timebase = timer::Periodic::new(dp.TIMER3, timer::Prescaler::Div64, 2499);
+//!     timebase.listen();
+//!
+//!     unsafe { atmega32u4::interrupt::enable() };
+//!
+//!     loop {
+//!         let seconds = timebase.ticks() / 100;
+//!     }
+//! }
+//!
+//! interrupt!(TIMER3_OVF, timer3_ovf_isr);
+//! fn timer3_ovf_isr() {
+//!     atmega32u4_hal::timer::timer3_ovf_isr();
+//! }
+//! ```
+//!
+//! [Periodic] doesn't drive any pin, so `PC6` stays free to be used as a normal GPIO,
+//! but not for Timer3 PWM at the same time.
+use core::cmp;
+use core::convert::{Infallible, TryFrom};
 use core::marker;
 use hal;
+use hal::digital::v2::OutputPin;
 use atmega32u4;
 use port;
+use clock;
+use delay;
+use global::Global;
+use nb;
+
+/// Duty cycle helpers for all PWM pins
+///
+/// Implemented for every [embedded_hal::PwmPin] with an unsigned integer duty, so the
+/// 8-bit and the 16-bit timers are both covered.  The duty is scaled against
+/// `get_max_duty()` using integer math.
+///
+/// PWM pins don't implement `OutputPin`, so they can't implement `StatefulOutputPin`
+/// either.  `is_off()` and `is_full()` tell whether the duty is at one of its
+/// extremes instead.  They look at the duty only, with [Polarity::Inverted] a duty of
+/// 0 means the pin is high.
+pub trait PwmPinExt: hal::PwmPin
+where
+    Self::Duty: Into<u32> + TryFrom<u32>,
+{
+    /// Set the duty cycle in percent
+    ///
+    /// Values above 100 are clamped to 100%.
+    fn set_duty_percent(&mut self, percent: u8) {
+        self.set_duty_fraction(cmp::min(percent, 100) as u16, 100);
+    }
+
+    /// Set the duty cycle to `numerator / denominator`
+    ///
+    /// Fractions above 1 are clamped to 100%.
+    fn set_duty_fraction(&mut self, numerator: u16, denominator: u16) {
+        let max: u32 = self.get_max_duty().into();
+        let duty = if numerator >= denominator {
+            max
+        } else {
+            max * numerator as u32 / denominator as u32
+        };
+
+        if let Ok(duty) = Self::Duty::try_from(duty) {
+            self.set_duty(duty);
+        }
+    }
+
+    /// Whether the duty cycle is 0
+    ///
+    /// Useful to skip updates of an LED which is off anyway.  In fast PWM mode, the pin
+    /// still goes high for one timer tick per period, so it is only almost steady.
+    fn is_off(&self) -> bool {
+        self.get_duty().into() == 0
+    }
+
+    /// Whether the duty cycle is at `get_max_duty()`
+    fn is_full(&self) -> bool {
+        self.get_duty().into() == self.get_max_duty().into()
+    }
+
+    /// Walk the duty cycle from `from` to `to`, waiting `ms_per_step` between steps
+    ///
+    /// Works in both directions.  The last step is shortened if necessary, so the duty
+    /// always ends at `to`.  A `step` of 0 is treated as 1.
+    fn fade<D>(
+        &mut self,
+        from: Self::Duty,
+        to: Self::Duty,
+        step: Self::Duty,
+        delay: &mut D,
+        ms_per_step: u16,
+    ) where
+        D: hal::blocking::delay::DelayMs<u16>,
+    {
+        let mut duty: u32 = from.into();
+        let to: u32 = to.into();
+        let step: u32 = cmp::max(step.into(), 1);
+
+        loop {
+            if let Ok(d) = Self::Duty::try_from(duty) {
+                self.set_duty(d);
+            }
+            if duty == to {
+                break;
+            }
+
+            delay.delay_ms(ms_per_step);
+            duty = if duty < to {
+                cmp::min(duty + step, to)
+            } else {
+                cmp::max(duty.saturating_sub(step), to)
+            };
+        }
+    }
+}
+
+impl<P> PwmPinExt for P
+where
+    P: hal::PwmPin,
+    P::Duty: Into<u32> + TryFrom<u32>,
+{
+}
+
+/// PWM waveform generation mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PwmMode {
+    /// Fast PWM, counting up only
+    ///
+    /// Frequency is `F_CPU / (64 * 256)`, about 976Hz at 16MHz.  This is the default
+    /// for Timer0, Timer1 and Timer3.
+    FastPwm,
+    /// Phase-correct PWM, counting up and down
+    ///
+    /// Frequency is `F_CPU / (64 * 510)`, about 490Hz at 16MHz, with edges symmetric
+    /// to the middle of the period.  This is the default for Timer4.
+    PhaseCorrectPwm,
+}
+
+/// PWM output polarity
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Polarity {
+    /// Pin is high for `duty` ticks, then low (clear on compare match)
+    Normal,
+    /// Pin is low for `duty` ticks, then high (set on compare match)
+    ///
+    /// A duty of 0 keeps the pin (almost) always high, max duty keeps it low.
+    Inverted,
+}
+
+impl Polarity {
+    /// `COMnx1:0` bits for this polarity
+    fn com_bits(self) -> u8 {
+        match self {
+            Polarity::Normal => 0b10,
+            Polarity::Inverted => 0b11,
+        }
+    }
+}
 
 macro_rules! timer_impl {
     (
         Info: ($Timer:ident, $TIMER:ident, $tim:ident),
         Init: $init:block,
+        Modes: ($default:ident, {
+            FastPwm: $fast:block,
+            PhaseCorrectPwm: $phase:block,
+        }),
+        Reset: [$($reg:ident),+],
         Pins: [
-            $(|$port:ident, $PIN:ident, $pwm:ident| ($ocr:ident, $setup:block),)+
+            $(|$port:ident, $PIN:ident, $pwm:ident| ($ocr:ident, $ctrl:ident.$com:ident, $setup:block, $teardown:block),)+
         ]
     ) => {
         /// PWM Timer
@@ -69,15 +458,55 @@ macro_rules! timer_impl {
         impl $Timer {
             /// Initialize this PWM timer
             ///
-            /// *Note*: Right now, once a timer is configured for PWM, it can't be used for
-            /// anything else afterwards.
+            /// The timer starts in its default mode, see [PwmMode].  To use the timer
+            /// for something else afterwards, release it using `free()`.
             pub fn new($tim: atmega32u4::$TIMER) -> $Timer {
+                $Timer::with_mode($tim, PwmMode::$default)
+            }
+
+            /// Initialize this PWM timer in a specific mode
+            pub fn with_mode($tim: atmega32u4::$TIMER, mode: PwmMode) -> $Timer {
                 $init
 
-                $Timer {
+                let mut timer = $Timer {
                     $tim: $tim,
+                };
+                timer.set_mode(mode);
+                timer
+            }
+
+            /// Switch between fast and phase-correct PWM
+            ///
+            /// This changes the PWM frequency of all pins of this timer.
+            pub fn set_mode(&mut self, mode: PwmMode) {
+                let $tim = &self.$tim;
+                match mode {
+                    PwmMode::FastPwm => $fast,
+                    PwmMode::PhaseCorrectPwm => $phase,
                 }
             }
+
+            /// Access the raw timer registers
+            ///
+            /// # Safety
+            /// The waveform generation mode and the clock select bits belong to this driver.
+            /// Changing them changes the PWM of all pins of the timer.
+            pub unsafe fn registers(&mut self) -> &atmega32u4::$TIMER {
+                &self.$tim
+            }
+
+            /// Stop this timer and release the raw peripheral
+            ///
+            /// The timer's control registers are reset to their power-on defaults,
+            /// which stops the timer and disconnects all PWM pins.  The compare
+            /// registers keep their values.
+            pub fn free(self) -> atmega32u4::$TIMER {
+                $(
+                    self.$tim.$reg.reset();
+                )+
+
+                self.$tim
+            }
         }
 
         $(
@@ -97,6 +526,45 @@ macro_rules! timer_impl {
                 }
             }
 
+            impl<MODE> port::$port::$PIN<port::mode::io::Input<MODE>> {
+                /// Make this pin a PWM pin
+                ///
+                /// The pin is switched to an output first.
+                pub fn into_pwm<D: port::$port::PortDDR>(
+                    self,
+                    ddr: &mut D,
+                    $pwm: &mut $Timer,
+                ) -> port::$port::$PIN<port::mode::Pwm<$Timer>> {
+                    self.into_output(ddr).into_pwm($pwm)
+                }
+            }
+
+            impl port::$port::$PIN<port::mode::Pwm<$Timer>> {
+                /// Set the output polarity of this pin
+                pub fn set_polarity(&mut self, polarity: Polarity) {
+                    atmega32u4::interrupt::free(|_| unsafe {
+                        (*atmega32u4::$TIMER::ptr())
+                            .$ctrl
+                            .modify(|_, w| w.$com().bits(polarity.com_bits()))
+                    });
+                }
+
+                /// Turn this pin back into a plain output
+                ///
+                /// The pin is disconnected from the timer and driven by `PORT` again.
+                /// Other channels of the same timer are not affected.
+                pub fn into_output(self) -> port::$port::$PIN<port::mode::io::Output> {
+                    atmega32u4::interrupt::free(|_| {
+                        let $tim = unsafe { &*atmega32u4::$TIMER::ptr() };
+                        $teardown
+                    });
+
+                    port::$port::$PIN {
+                        _mode: marker::PhantomData,
+                    }
+                }
+            }
+
             impl hal::PwmPin for port::$port::$PIN<port::mode::Pwm<$Timer>> {
                 type Duty = u8;
 
@@ -128,19 +596,30 @@ macro_rules! timer_impl {
 timer_impl! {
     Info: (Timer0Pwm, TIMER0, tim),
     Init: {
-        // Fast PWM Mode
-        tim.tccr_a.modify(|_, w| w.wgm0().pwm_fast());
         // Enable Timer
         tim.tccr_b.modify(|_, w| w.cs().io_64());
     },
+    Modes: (FastPwm, {
+        FastPwm: {
+            tim.tccr_a.modify(|_, w| w.wgm0().pwm_fast());
+        },
+        PhaseCorrectPwm: {
+            tim.tccr_a.modify(|_, w| unsafe { w.wgm0().bits(0b01) });
+        },
+    }),
+    Reset: [tccr_a, tccr_b],
     Pins: [
-        |portb, PB7, pwm| (ocr_a, {
+        |portb, PB7, pwm| (ocr_a, tccr_a.com_a, {
             // Use OCR_A as Duty Cycle
             pwm.tim.tccr_a.modify(|_, w| w.com_a().match_clear());
+        }, {
+            tim.tccr_a.modify(|_, w| unsafe { w.com_a().bits(0) });
         }),
-        |portd, PD0, pwm| (ocr_b, {
+        |portd, PD0, pwm| (ocr_b, tccr_a.com_b, {
             // Use OCR_B as Duty Cycle
             pwm.tim.tccr_a.modify(|_, w| w.com_b().match_clear());
+        }, {
+            tim.tccr_a.modify(|_, w| unsafe { w.com_b().bits(0) });
         }),
     ]
 }
@@ -149,22 +628,38 @@ timer_impl! {
 timer_impl! {
     Info: (Timer1Pwm, TIMER1, tim),
     Init: {
-        tim.tccr_a.modify(|_, w| unsafe { w.wgm0().bits(0b01) });
-        tim.tccr_b.modify(|_, w| unsafe { w.wgm2().bits(0b01)}.cs().io_64());
+        tim.tccr_b.modify(|_, w| w.cs().io_64());
     },
+    Modes: (FastPwm, {
+        // 8-bit fast PWM (WGM = 5)
+        FastPwm: {
+            tim.tccr_a.modify(|_, w| unsafe { w.wgm0().bits(0b01) });
+            tim.tccr_b.modify(|_, w| unsafe { w.wgm2().bits(0b01) });
+        },
+        // 8-bit phase-correct PWM (WGM = 1)
+        PhaseCorrectPwm: {
+            tim.tccr_a.modify(|_, w| unsafe { w.wgm0().bits(0b01) });
+            tim.tccr_b.modify(|_, w| unsafe { w.wgm2().bits(0b00) });
+        },
+    }),
+    Reset: [tccr_a, tccr_b, tccr_c],
     Pins: [
-        |portb, PB5, pwm| (ocr_a_l, {
+        |portb, PB5, pwm| (ocr_a_l, tccr_a.com_a, {
             // Use OCR_A as Duty Cycle
             pwm.tim.tccr_a.modify(|_, w| w.com_a().match_clear());
+        }, {
+            tim.tccr_a.modify(|_, w| unsafe { w.com_a().bits(0) });
         }),
-        |portb, PB6, pwm| (ocr_b_l, {
+        |portb, PB6, pwm| (ocr_b_l, tccr_a.com_b, {
             // Use OCR_B as Duty Cycle
             pwm.tim.tccr_a.modify(|_, w| w.com_b().match_clear());
+        }, {
+            tim.tccr_a.modify(|_, w| unsafe { w.com_b().bits(0) });
         }),
         //////////////////////////////////////////////////////////////////
         // The following can be used instead of Timer0.ocr_a:
         //
-        // |portb, PB7, pwm, dc| (ocr_c_l, {
+        // |portb, PB7, pwm| (ocr_c_l, tccr_a.com_c, {
         //     // Use OCR_C as Duty Cycle
         //     pwm.tim.tccr_a.modify(|_, w| w.com_c().match_clear());
         // }),
@@ -183,6 +678,43 @@ impl port::portb::PB7<port::mode::io::Output> {
     }
 }
 
+impl<MODE> port::portb::PB7<port::mode::io::Input<MODE>> {
+    /// Make this pin a PWM pin using Timer1
+    ///
+    /// The pin is switched to an output first.
+    pub fn into_pwm1<D: port::portb::PortDDR>(
+        self,
+        ddr: &mut D,
+        pwm: &mut Timer1Pwm,
+    ) -> port::portb::PB7<port::mode::Pwm<Timer1Pwm>> {
+        self.into_output(ddr).into_pwm1(pwm)
+    }
+}
+
+impl port::portb::PB7<port::mode::Pwm<Timer1Pwm>> {
+    /// Set the output polarity of this pin
+    pub fn set_polarity(&mut self, polarity: Polarity) {
+        atmega32u4::interrupt::free(|_| unsafe {
+            (*atmega32u4::TIMER1::ptr())
+                .tccr_a
+                .modify(|_, w| w.com_c().bits(polarity.com_bits()))
+        });
+    }
+
+    /// Turn this pin back into a plain output
+    ///
+    /// The pin is disconnected from Timer1 and driven by `PORT` again.
+    pub fn into_output(self) -> port::portb::PB7<port::mode::io::Output> {
+        atmega32u4::interrupt::free(|_| unsafe {
+            (*atmega32u4::TIMER1::ptr())
+                .tccr_a
+                .modify(|_, w| w.com_c().bits(0))
+        });
+
+        port::portb::PB7 { _mode: marker::PhantomData }
+    }
+}
+
 impl hal::PwmPin for port::portb::PB7<port::mode::Pwm<Timer1Pwm>> {
     type Duty = u8;
 
@@ -218,13 +750,27 @@ impl hal::PwmPin for port::portb::PB7<port::mode::Pwm<Timer1Pwm>> {
 timer_impl! {
     Info: (Timer3Pwm, TIMER3, tim),
     Init: {
-        tim.tccr_a.modify(|_, w| unsafe { w.wgm0().bits(0b01) });
-        tim.tccr_b.modify(|_, w| unsafe { w.wgm2().bits(0b01) }.cs().io_64());
+        tim.tccr_b.modify(|_, w| w.cs().io_64());
     },
+    Modes: (FastPwm, {
+        // 8-bit fast PWM (WGM = 5)
+        FastPwm: {
+            tim.tccr_a.modify(|_, w| unsafe { w.wgm0().bits(0b01) });
+            tim.tccr_b.modify(|_, w| unsafe { w.wgm2().bits(0b01) });
+        },
+        // 8-bit phase-correct PWM (WGM = 1)
+        PhaseCorrectPwm: {
+            tim.tccr_a.modify(|_, w| unsafe { w.wgm0().bits(0b01) });
+            tim.tccr_b.modify(|_, w| unsafe { w.wgm2().bits(0b00) });
+        },
+    }),
+    Reset: [tccr_a, tccr_b, tccr_c],
     Pins: [
-        |portc, PC6, pwm| (ocr_a_l, {
+        |portc, PC6, pwm| (ocr_a_l, tccr_a.com_a, {
             // Use OCR_A as Duty Cycle
             pwm.tim.tccr_a.modify(|_, w| w.com_a().match_clear());
+        }, {
+            tim.tccr_a.modify(|_, w| unsafe { w.com_a().bits(0) });
         }),
     ]
 }
@@ -233,25 +779,87 @@ timer_impl! {
 timer_impl! {
     Info: (Timer4Pwm, TIMER4, tim),
     Init: {
+        // TC4H holds bits 9:8 for every 10-bit register access.  Keep it zero, so
+        // the 8-bit compare writes don't pick up stale high bits and TOP (OCR4C)
+        // stays at 0xff.
+        tim.tc4h.write(|w| unsafe { w.bits(0) });
         // Prescale/64
         tim.tccr_b.modify(|_, w| w.cs().clk_64());
-        // Set WGM to Phase-Correct PWM Mode
-        tim.tccr_d.modify(|_, w| unsafe { w.wgm().bits(0b01) });
     },
+    Modes: (PhaseCorrectPwm, {
+        FastPwm: {
+            tim.tccr_d.modify(|_, w| unsafe { w.wgm().bits(0b00) });
+        },
+        // Phase and Frequency Correct PWM
+        PhaseCorrectPwm: {
+            tim.tccr_d.modify(|_, w| unsafe { w.wgm().bits(0b01) });
+        },
+    }),
+    Reset: [tccr_a, tccr_b, tccr_c, tccr_d, tccr_e],
     Pins: [
-        |portc, PC7, pwm| (ocr_a, {
+        |portc, PC7, pwm| (ocr_a, tccr_a.com_a, {
             // Use OCR_A as Duty Cycle
             // Enable PWM for OCR_A
             pwm.tim.tccr_a.modify(|_, w| w.com_a().match_clear().pwm_a().set_bit());
+        }, {
+            tim.tccr_a.modify(|_, w| unsafe { w.com_a().bits(0) }.pwm_a().clear_bit());
         }),
-        |portd, PD7, pwm| (ocr_d, {
+        |portd, PD7, pwm| (ocr_d, tccr_c.com_d, {
             // Use OCR_D as Duty Cycle
             // Enable PWM for OCR_D
             pwm.tim.tccr_c.modify(|_, w| w.com_d().match_clear().pwm_d().set_bit());
+        }, {
+            tim.tccr_c.modify(|_, w| unsafe { w.com_d().bits(0) }.pwm_d().clear_bit());
         }),
     ]
 }
 
+impl Timer0Pwm {
+    /// Current value of the counter (`TCNT0`)
+    pub fn counter(&self) -> u8 {
+        self.tim.tcnt.read().bits()
+    }
+}
+
+macro_rules! counter16_impl {
+    ($Timer:ident) => {
+        impl $Timer {
+            /// Current value of the counter (`TCNTn`)
+            ///
+            /// The low byte is read first, which latches the high byte into the timer's
+            /// shared `TEMP` register.  Interrupts are disabled during the read, so an
+            /// interrupt handler accessing another 16-bit register of the same timer
+            /// can't overwrite `TEMP` in between.
+            pub fn counter(&self) -> u16 {
+                atmega32u4::interrupt::free(|_| {
+                    let low = self.tim.tcnt_l.read().bits() as u16;
+                    let high = self.tim.tcnt_h.read().bits() as u16;
+                    (high << 8) | low
+                })
+            }
+        }
+    };
+}
+
+counter16_impl!(Timer1Pwm);
+counter16_impl!(Timer3Pwm);
+
+impl Timer4Pwm {
+    /// Current value of the 10-bit counter (`TCNT4`)
+    ///
+    /// Reading `TCNT4` latches bits 9:8 into `TC4H`, which is read afterwards.
+    /// Interrupts are disabled during the read, as `TC4H` is shared by all 10-bit
+    /// registers of Timer4.  In the 8-bit PWM modes used by `Timer4Pwm`, the upper
+    /// bits are always 0.
+    pub fn counter(&self) -> u16 {
+        atmega32u4::interrupt::free(|_| {
+            let low = self.tim.tcnt.read().bits() as u16;
+            let high = (self.tim.tc4h.read().bits() & 0b11) as u16;
+            (high << 8) | low
+        })
+    }
+}
+
 // Manual second implementation
 impl port::portb::PB6<port::mode::io::Output> {
     /// Make this pin a PWM pin, but using Timer4 instead of Timer1
@@ -266,6 +874,43 @@ impl port::portb::PB6<port::mode::io::Output> {
     }
 }
 
+impl<MODE> port::portb::PB6<port::mode::io::Input<MODE>> {
+    /// Make this pin a PWM pin using Timer4
+    ///
+    /// The pin is switched to an output first.
+    pub fn into_pwm4<D: port::portb::PortDDR>(
+        self,
+        ddr: &mut D,
+        pwm: &mut Timer4Pwm,
+    ) -> port::portb::PB6<port::mode::Pwm<Timer4Pwm>> {
+        self.into_output(ddr).into_pwm4(pwm)
+    }
+}
+
+impl port::portb::PB6<port::mode::Pwm<Timer4Pwm>> {
+    /// Set the output polarity of this pin
+    pub fn set_polarity(&mut self, polarity: Polarity) {
+        atmega32u4::interrupt::free(|_| unsafe {
+            (*atmega32u4::TIMER4::ptr())
+                .tccr_a
+                .modify(|_, w| w.com_b().bits(polarity.com_bits()))
+        });
+    }
+
+    /// Turn this pin back into a plain output
+    ///
+    /// The pin is disconnected from Timer4 and driven by `PORT` again.
+    pub fn into_output(self) -> port::portb::PB6<port::mode::io::Output> {
+        atmega32u4::interrupt::free(|_| unsafe {
+            (*atmega32u4::TIMER4::ptr())
+                .tccr_a
+                .modify(|_, w| w.com_b().bits(0).pwm_b().clear_bit())
+        });
+
+        port::portb::PB6 { _mode: marker::PhantomData }
+    }
+}
+
 impl hal::PwmPin for port::portb::PB6<port::mode::Pwm<Timer4Pwm>> {
     type Duty = u8;
 
@@ -291,3 +936,1023 @@ impl hal::PwmPin for port::portb::PB6<port::mode::Pwm<Timer4Pwm>> {
         });
     }
 }
+
+/// Tone generator
+///
+/// Generates a square wave with 50% duty cycle on the `OCnA` pin of a 16-bit timer.
+/// If `SPEED` is omitted, the speed selected using the `mhz*` features is used.
+pub struct Tone<TIMER, PIN, SPEED = delay::Speed> {
+    tim: TIMER,
+    pin: PIN,
+    _speed: marker::PhantomData<SPEED>,
+}
+
+// Prescaler choices for the 16-bit timers, as (divider, CS bits)
+const TONE_PRESCALERS: [(u32, u8); 5] = [(1, 1), (8, 2), (64, 3), (256, 4), (1024, 5)];
+
+macro_rules! tone_impl {
+    ($TIMER:ident, $port:ident, $PIN:ident) => {
+        impl<SPEED: clock::Clock> Tone<atmega32u4::$TIMER, port::$port::$PIN<port::mode::io::Output>, SPEED> {
+            /// Create a tone generator from a timer and its `OCnA` pin
+            ///
+            /// The pin is driven low while no tone is playing.
+            pub fn new(
+                tim: atmega32u4::$TIMER,
+                mut pin: port::$port::$PIN<port::mode::io::Output>,
+            ) -> Self {
+                pin.set_low().unwrap();
+
+                // CTC mode with OCRnA as TOP, timer stopped
+                tim.tccr_a.reset();
+                tim.tccr_b.write(|w| unsafe { w.wgm2().bits(0b01) });
+
+                Tone {
+                    tim: tim,
+                    pin: pin,
+                    _speed: marker::PhantomData,
+                }
+            }
+
+            /// Start playing a tone with the given frequency
+            ///
+            /// A frequency of 0 stops the output.
+            pub fn play(&mut self, frequency: u16) {
+                if frequency == 0 {
+                    return self.stop();
+                }
+
+                let half_period = SPEED::FREQ / (2 * frequency as u32);
+                let mut setting = None;
+                for &(div, cs) in TONE_PRESCALERS.iter() {
+                    let top = half_period / div;
+                    if top > 0 && top - 1 <= 0xffff {
+                        setting = Some((top - 1, cs));
+                        break;
+                    }
+                }
+                let (top, cs) = match setting {
+                    Some(s) => s,
+                    // Too fast or too slow for this timer
+                    None => return self.stop(),
+                };
+
+                atmega32u4::interrupt::free(|_| {
+                    self.tim.tccr_b.modify(|_, w| unsafe { w.cs().bits(0) });
+
+                    // The high byte has to be written first
+                    self.tim.ocr_a_h.write(|w| unsafe { w.bits((top >> 8) as u8) });
+                    self.tim.ocr_a_l.write(|w| unsafe { w.bits(top as u8) });
+                    self.tim.tcnt_h.write(|w| unsafe { w.bits(0) });
+                    self.tim.tcnt_l.write(|w| unsafe { w.bits(0) });
+
+                    // Toggle OCnA on compare match
+                    self.tim.tccr_a.modify(|_, w| unsafe { w.com_a().bits(0b01) });
+                    self.tim.tccr_b.modify(|_, w| unsafe { w.cs().bits(cs) });
+                });
+            }
+
+            /// Stop the tone and drive the pin low
+            pub fn stop(&mut self) {
+                atmega32u4::interrupt::free(|_| {
+                    self.tim.tccr_b.modify(|_, w| unsafe { w.cs().bits(0) });
+                    self.tim.tccr_a.modify(|_, w| unsafe { w.com_a().bits(0) });
+                });
+                self.pin.set_low().unwrap();
+            }
+
+            /// Play a tone for `duration_ms` milliseconds, then stop
+            ///
+            /// This blocks using [delay::Delay].
+            pub fn play_for(&mut self, frequency: u16, duration_ms: u16)
+            where
+                delay::Delay<SPEED>: hal::blocking::delay::DelayMs<u16>,
+            {
+                use hal::blocking::delay::DelayMs;
+
+                self.play(frequency);
+                delay::Delay::<SPEED>::new().delay_ms(duration_ms);
+                self.stop();
+            }
+
+            /// Access the raw timer registers
+            ///
+            /// # Safety
+            /// The mode and prescaler are recalculated by `play()`, but changing them while a
+            /// tone is playing changes its frequency.
+            pub unsafe fn registers(&mut self) -> &atmega32u4::$TIMER {
+                &self.tim
+            }
+
+            /// Stop the tone and release timer and pin
+            ///
+            /// The timer's control registers are reset to their power-on defaults.
+            pub fn free(mut self) -> (atmega32u4::$TIMER, port::$port::$PIN<port::mode::io::Output>) {
+                self.stop();
+                self.tim.tccr_a.reset();
+                self.tim.tccr_b.reset();
+
+                (self.tim, self.pin)
+            }
+        }
+    }
+}
+
+tone_impl!(TIMER1, portb, PB5);
+tone_impl!(TIMER3, portc, PC6);
+
+/// Delay using a 16-bit timer
+///
+/// If `SPEED` is omitted, the speed selected using the `mhz*` features is used.
+pub struct TimerDelay<TIMER, SPEED = delay::Speed> {
+    tim: TIMER,
+    _speed: marker::PhantomData<SPEED>,
+}
+
+// TIFRn
+const OCFA: u8 = 1 << 1;
+// Clock select for a prescaler of 8
+const DELAY_CS: u8 = 2;
+
+/// Split a delay of `us` into whole milliseconds and the ticks for the rest
+///
+/// The timer ticks at `freq / 8`.  The rest is rounded up to the next tick.
+fn delay_us_ticks(us: u32, freq: u32) -> (u32, u32) {
+    let ticks_per_ms = freq / 8000;
+    (us / 1000, ((us % 1000) * ticks_per_ms + 999) / 1000)
+}
+
+macro_rules! delay_impl {
+    ($TIMER:ident) => {
+        impl<SPEED: clock::Clock> TimerDelay<atmega32u4::$TIMER, SPEED> {
+            /// Use this timer for delays
+            ///
+            /// The timer only runs while a delay is in progress.
+            pub fn new(tim: atmega32u4::$TIMER) -> Self {
+                // CTC mode with OCRnA as TOP, timer stopped
+                tim.tccr_a.reset();
+                tim.tccr_b.write(|w| unsafe { w.wgm2().bits(0b01) });
+
+                TimerDelay {
+                    tim: tim,
+                    _speed: marker::PhantomData,
+                }
+            }
+
+            /// Wait for `ticks` timer ticks of 8 CPU cycles each
+            fn wait_ticks(&mut self, mut ticks: u32) {
+                while ticks > 0 {
+                    let chunk = cmp::min(ticks, 0x10000);
+                    let top = chunk - 1;
+
+                    self.tim.ocr_a_h.write(|w| unsafe { w.bits((top >> 8) as u8) });
+                    self.tim.ocr_a_l.write(|w| unsafe { w.bits(top as u8) });
+                    self.tim.tcnt_h.write(|w| unsafe { w.bits(0) });
+                    self.tim.tcnt_l.write(|w| unsafe { w.bits(0) });
+                    // The flag is cleared by writing a one
+                    self.tim.tifr.write(|w| unsafe { w.bits(OCFA) });
+
+                    self.tim.tccr_b.modify(|_, w| unsafe { w.cs().bits(DELAY_CS) });
+                    while self.tim.tifr.read().bits() & OCFA == 0 {}
+                    self.tim.tccr_b.modify(|_, w| unsafe { w.cs().bits(0) });
+
+                    ticks -= chunk;
+                }
+            }
+
+            /// Access the raw timer registers
+            ///
+            /// # Safety
+            /// The timer must be stopped and in CTC mode whenever a delay is started.
+            pub unsafe fn registers(&mut self) -> &atmega32u4::$TIMER {
+                &self.tim
+            }
+
+            /// Reset the timer and release it
+            pub fn free(self) -> atmega32u4::$TIMER {
+                self.tim.tccr_a.reset();
+                self.tim.tccr_b.reset();
+                self.tim
+            }
+        }
+
+        impl<SPEED: clock::Clock> hal::blocking::delay::DelayUs<u32> for TimerDelay<atmega32u4::$TIMER, SPEED> {
+            fn delay_us(&mut self, us: u32) {
+                // Like `delay_ms()`, whole milliseconds are waited one at a time, so
+                // the tick count can't overflow
+                let (ms, ticks) = delay_us_ticks(us, SPEED::FREQ);
+                for _ in 0..ms {
+                    self.wait_ticks(SPEED::FREQ / 8000);
+                }
+                self.wait_ticks(ticks);
+            }
+        }
+
+        impl<SPEED: clock::Clock> hal::blocking::delay::DelayUs<u16> for TimerDelay<atmega32u4::$TIMER, SPEED> {
+            fn delay_us(&mut self, us: u16) {
+                hal::blocking::delay::DelayUs::<u32>::delay_us(self, us as u32);
+            }
+        }
+
+        impl<SPEED: clock::Clock> hal::blocking::delay::DelayUs<u8> for TimerDelay<atmega32u4::$TIMER, SPEED> {
+            fn delay_us(&mut self, us: u8) {
+                hal::blocking::delay::DelayUs::<u32>::delay_us(self, us as u32);
+            }
+        }
+
+        impl<SPEED: clock::Clock> hal::blocking::delay::DelayMs<u32> for TimerDelay<atmega32u4::$TIMER, SPEED> {
+            fn delay_ms(&mut self, ms: u32) {
+                // Wait one millisecond at a time, so long delays can't overflow
+                for _ in 0..ms {
+                    self.wait_ticks(SPEED::FREQ / 8000);
+                }
+            }
+        }
+
+        impl<SPEED: clock::Clock> hal::blocking::delay::DelayMs<u16> for TimerDelay<atmega32u4::$TIMER, SPEED> {
+            fn delay_ms(&mut self, ms: u16) {
+                hal::blocking::delay::DelayMs::<u32>::delay_ms(self, ms as u32);
+            }
+        }
+
+        impl<SPEED: clock::Clock> hal::blocking::delay::DelayMs<u8> for TimerDelay<atmega32u4::$TIMER, SPEED> {
+            fn delay_ms(&mut self, ms: u8) {
+                hal::blocking::delay::DelayMs::<u32>::delay_ms(self, ms as u32);
+            }
+        }
+    }
+}
+
+delay_impl!(TIMER1);
+delay_impl!(TIMER3);
+
+/// 16-bit timer configured for servo control
+///
+/// The timer runs in fast PWM mode with `ICRn` as TOP and a prescaler of 8, which
+/// gives a period of 20ms.  If `SPEED` is omitted, the speed selected using the
+/// `mhz*` features is used.
+pub struct ServoTimer<TIMER, SPEED = delay::Speed> {
+    tim: TIMER,
+    _speed: marker::PhantomData<SPEED>,
+}
+
+/// A servo connected to an `OCnx` pin
+///
+/// Created using the `servo_*()` methods of [ServoTimer].
+pub struct Servo<PIN> {
+    pin: PIN,
+    // Timer ticks per millisecond
+    ticks_per_ms: u16,
+    min_us: u16,
+    max_us: u16,
+}
+
+impl<PIN> Servo<PIN> {
+    /// Set the pulse widths for 0° and 180°
+    ///
+    /// Defaults to 1000µs and 2000µs.  If `min_us` is larger than `max_us`, the two
+    /// are swapped.
+    pub fn set_limits(&mut self, min_us: u16, max_us: u16) {
+        if min_us <= max_us {
+            self.min_us = min_us;
+            self.max_us = max_us;
+        } else {
+            self.min_us = max_us;
+            self.max_us = min_us;
+        }
+    }
+
+    fn ticks(&self, us: u16) -> u16 {
+        let us = if us < self.min_us {
+            self.min_us
+        } else if us > self.max_us {
+            self.max_us
+        } else {
+            us
+        };
+
+        (us as u32 * self.ticks_per_ms as u32 / 1000) as u16
+    }
+
+    fn angle_to_us(&self, degrees: u8) -> u16 {
+        let degrees = if degrees > 180 { 180 } else { degrees };
+        let range = (self.max_us - self.min_us) as u32;
+
+        self.min_us + (range * degrees as u32 / 180) as u16
+    }
+}
+
+macro_rules! servo_impl {
+    ($TIMER:ident, [$(($servo:ident, $port:ident, $PIN:ident, $ocr_h:ident, $ocr_l:ident, $com:ident),)+]) => {
+        impl<SPEED: clock::Clock> ServoTimer<atmega32u4::$TIMER, SPEED> {
+            /// Configure this timer for servo control
+            pub fn new(tim: atmega32u4::$TIMER) -> Self {
+                // 50Hz at a prescaler of 8
+                let top = (SPEED::FREQ / 8 / 50 - 1) as u16;
+
+                atmega32u4::interrupt::free(|_| {
+                    // The high byte has to be written first
+                    tim.icr_h.write(|w| unsafe { w.bits((top >> 8) as u8) });
+                    tim.icr_l.write(|w| unsafe { w.bits(top as u8) });
+
+                    // Fast PWM, TOP = ICRn (WGM = 14), clk/8
+                    tim.tccr_a.write(|w| unsafe { w.wgm0().bits(0b10) });
+                    tim.tccr_b.write(|w| unsafe { w.wgm2().bits(0b11).cs().bits(0b010) });
+                });
+
+                ServoTimer {
+                    tim: tim,
+                    _speed: marker::PhantomData,
+                }
+            }
+
+            /// Access the raw timer registers
+            ///
+            /// # Safety
+            /// The 50Hz period depends on the mode, `ICRn` and the prescaler, which must not
+            /// be changed.
+            pub unsafe fn registers(&mut self) -> &atmega32u4::$TIMER {
+                &self.tim
+            }
+
+            /// Stop this timer and release the raw peripheral
+            ///
+            /// The timer's control registers are reset to their power-on defaults.
+            pub fn free(self) -> atmega32u4::$TIMER {
+                self.tim.tccr_a.reset();
+                self.tim.tccr_b.reset();
+
+                self.tim
+            }
+
+            $(
+                /// Drive a servo using this pin
+                ///
+                /// No pulses are generated until an angle or pulse width is set.
+                pub fn $servo(
+                    &mut self,
+                    pin: port::$port::$PIN<port::mode::io::Output>,
+                ) -> Servo<port::$port::$PIN<port::mode::io::Output>> {
+                    Servo {
+                        pin: pin,
+                        ticks_per_ms: (SPEED::FREQ / 8 / 1000) as u16,
+                        min_us: 1000,
+                        max_us: 2000,
+                    }
+                }
+            )+
+        }
+
+        $(
+            impl Servo<port::$port::$PIN<port::mode::io::Output>> {
+                /// Move the servo to an angle between 0° and 180°
+                ///
+                /// Larger angles are clamped to 180°.
+                pub fn set_angle(&mut self, degrees: u8) {
+                    let us = self.angle_to_us(degrees);
+                    self.set_microseconds(us);
+                }
+
+                /// Set the pulse width directly
+                ///
+                /// The width is clamped to the range configured using `set_limits()`.
+                pub fn set_microseconds(&mut self, us: u16) {
+                    let ticks = self.ticks(us);
+
+                    atmega32u4::interrupt::free(|_| {
+                        let tim = unsafe { &*atmega32u4::$TIMER::ptr() };
+
+                        // The high byte has to be written first
+                        tim.$ocr_h.write(|w| unsafe { w.bits((ticks >> 8) as u8) });
+                        tim.$ocr_l.write(|w| unsafe { w.bits(ticks as u8) });
+                        tim.tccr_a.modify(|_, w| w.$com().match_clear());
+                    });
+                }
+
+                /// Stop sending pulses and release the pin
+                pub fn free(self) -> port::$port::$PIN<port::mode::io::Output> {
+                    atmega32u4::interrupt::free(|_| {
+                        let tim = unsafe { &*atmega32u4::$TIMER::ptr() };
+                        tim.tccr_a.modify(|_, w| unsafe { w.$com().bits(0) });
+                    });
+
+                    self.pin
+                }
+            }
+        )+
+    }
+}
+
+servo_impl!(TIMER1, [
+    (servo_a, portb, PB5, ocr_a_h, ocr_a_l, com_a),
+    (servo_b, portb, PB6, ocr_b_h, ocr_b_l, com_b),
+]);
+servo_impl!(TIMER3, [
+    (servo_a, portc, PC6, ocr_a_h, ocr_a_l, com_a),
+]);
+
+/// Frequency in Hertz
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hertz(pub u32);
+
+/// Output compare channel of a [MultiPwm] timer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// `OCnA`
+    A,
+    /// `OCnB`
+    B,
+    /// `OCnC`
+    C,
+}
+
+/// 16-bit timer with a configurable PWM period, shared by all its channels
+///
+/// The timer runs in fast PWM mode with `ICRn` as TOP.  If `SPEED` is omitted, the
+/// speed selected using the `mhz*` features is used.
+pub struct MultiPwm<TIMER, SPEED = delay::Speed> {
+    tim: TIMER,
+    frequency: Hertz,
+    _speed: marker::PhantomData<SPEED>,
+}
+
+macro_rules! multi_pwm_impl {
+    ($TIMER:ident, [$(($Channel:ident, $ocr_h:ident, $ocr_l:ident, $com:ident),)+]) => {
+        impl<SPEED: clock::Clock> MultiPwm<atmega32u4::$TIMER, SPEED> {
+            /// Configure this timer for PWM with the given frequency
+            ///
+            /// All channels start out disabled.
+            pub fn new(tim: atmega32u4::$TIMER, frequency: Hertz) -> Self {
+                let mut pwm = MultiPwm {
+                    tim: tim,
+                    frequency: frequency,
+                    _speed: marker::PhantomData,
+                };
+                pwm.tim.tccr_a.reset();
+                hal::Pwm::set_period(&mut pwm, frequency);
+                pwm
+            }
+
+            /// Change the PWM frequency, returning the frequency actually reached
+            ///
+            /// Like `set_period()`, this picks the smallest prescaler for which `TOP`
+            /// fits into 16 bits.  Frequencies from 1Hz up to `F_CPU / 2` can be
+            /// reached, but only frequencies which divide `F_CPU / prescaler` evenly
+            /// are exact.
+            ///
+            /// The duty cycles are not rescaled, set them again using the new
+            /// `get_max_duty()`.
+            pub fn set_frequency_hz(&mut self, hz: u32) -> u32 {
+                hal::Pwm::set_period(self, Hertz(hz));
+                self.frequency.0
+            }
+
+            /// Access the raw timer registers
+            ///
+            /// # Safety
+            /// Changing the mode, `ICRn` or the prescaler breaks `get_period()` and the duty
+            /// scaling.
+            pub unsafe fn registers(&mut self) -> &atmega32u4::$TIMER {
+                &self.tim
+            }
+
+            /// Stop this timer and release the raw peripheral
+            ///
+            /// The timer's control registers are reset to their power-on defaults,
+            /// which disconnects all channels.
+            pub fn free(self) -> atmega32u4::$TIMER {
+                self.tim.tccr_a.reset();
+                self.tim.tccr_b.reset();
+
+                self.tim
+            }
+
+            fn top(&self) -> u16 {
+                // ICRnL has to be read first
+                let low = self.tim.icr_l.read().bits() as u16;
+                let high = self.tim.icr_h.read().bits() as u16;
+                (high << 8) | low
+            }
+        }
+
+        impl<SPEED: clock::Clock> hal::Pwm for MultiPwm<atmega32u4::$TIMER, SPEED> {
+            type Channel = Channel;
+            type Time = Hertz;
+            type Duty = u16;
+
+            fn disable(&mut self, channel: Channel) {
+                match channel {
+                    $(
+                        Channel::$Channel => atmega32u4::interrupt::free(|_| {
+                            self.tim.tccr_a.modify(|_, w| unsafe { w.$com().bits(0) })
+                        }),
+                    )+
+                    #[allow(unreachable_patterns)]
+                    _ => (),
+                }
+            }
+
+            fn enable(&mut self, channel: Channel) {
+                match channel {
+                    $(
+                        Channel::$Channel => atmega32u4::interrupt::free(|_| {
+                            self.tim.tccr_a.modify(|_, w| w.$com().match_clear())
+                        }),
+                    )+
+                    #[allow(unreachable_patterns)]
+                    _ => (),
+                }
+            }
+
+            fn get_period(&self) -> Hertz {
+                self.frequency
+            }
+
+            fn get_duty(&self, channel: Channel) -> u16 {
+                match channel {
+                    $(
+                        Channel::$Channel => {
+                            // The low byte has to be read first
+                            let low = self.tim.$ocr_l.read().bits() as u16;
+                            let high = self.tim.$ocr_h.read().bits() as u16;
+                            (high << 8) | low
+                        }
+                    )+
+                    #[allow(unreachable_patterns)]
+                    _ => 0,
+                }
+            }
+
+            fn get_max_duty(&self) -> u16 {
+                self.top()
+            }
+
+            fn set_duty(&mut self, channel: Channel, duty: u16) {
+                let duty = cmp::min(duty, self.top());
+                match channel {
+                    $(
+                        Channel::$Channel => atmega32u4::interrupt::free(|_| {
+                            // The high byte has to be written first
+                            self.tim.$ocr_h.write(|w| unsafe { w.bits((duty >> 8) as u8) });
+                            self.tim.$ocr_l.write(|w| unsafe { w.bits(duty as u8) });
+                        }),
+                    )+
+                    #[allow(unreachable_patterns)]
+                    _ => (),
+                }
+            }
+
+            fn set_period<P>(&mut self, period: P)
+            where
+                P: Into<Hertz>,
+            {
+                let frequency = cmp::max(period.into().0, 1);
+
+                // Smallest prescaler which can reach the frequency, for the best
+                // duty resolution
+                let mut setting = if frequency > SPEED::FREQ / 2 {
+                    (1, 1)
+                } else {
+                    (0xffff, 5)
+                };
+                for &(div, cs) in TONE_PRESCALERS.iter() {
+                    let ticks = SPEED::FREQ / div / frequency;
+                    if ticks >= 2 && ticks - 1 <= 0xffff {
+                        setting = (ticks - 1, cs);
+                        break;
+                    }
+                }
+                let (top, cs) = setting;
+
+                atmega32u4::interrupt::free(|_| {
+                    // The high byte has to be written first
+                    self.tim.icr_h.write(|w| unsafe { w.bits((top >> 8) as u8) });
+                    self.tim.icr_l.write(|w| unsafe { w.bits(top as u8) });
+
+                    // Fast PWM, TOP = ICRn (WGM = 14)
+                    self.tim.tccr_a.modify(|_, w| unsafe { w.wgm0().bits(0b10) });
+                    self.tim.tccr_b.write(|w| unsafe { w.wgm2().bits(0b11).cs().bits(cs) });
+                });
+
+                let div = TONE_PRESCALERS[cs as usize - 1].0;
+                self.frequency = Hertz(SPEED::FREQ / div / (top + 1));
+            }
+        }
+    }
+}
+
+multi_pwm_impl!(TIMER1, [
+    (A, ocr_a_h, ocr_a_l, com_a),
+    (B, ocr_b_h, ocr_b_l, com_b),
+    (C, ocr_c_h, ocr_c_l, com_c),
+]);
+multi_pwm_impl!(TIMER3, [
+    (A, ocr_a_h, ocr_a_l, com_a),
+]);
+
+/// Edge which triggers an input capture
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    /// Low to high
+    Rising,
+    /// High to low
+    Falling,
+}
+
+/// Timer clock prescaler, the resolution of an [InputCapture]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prescaler {
+    /// `F_CPU`
+    Div1,
+    /// `F_CPU / 8`
+    Div8,
+    /// `F_CPU / 64`
+    Div64,
+    /// `F_CPU / 256`
+    Div256,
+    /// `F_CPU / 1024`
+    Div1024,
+}
+
+impl Prescaler {
+    /// `CSn2:0` bits
+    fn bits(self) -> u8 {
+        match self {
+            Prescaler::Div1 => 1,
+            Prescaler::Div8 => 2,
+            Prescaler::Div64 => 3,
+            Prescaler::Div256 => 4,
+            Prescaler::Div1024 => 5,
+        }
+    }
+}
+
+// TCCRnB
+const ICNC: u8 = 1 << 7;
+const ICES: u8 = 1 << 6;
+const CS_MASK: u8 = 0b111;
+// TIFRn
+const ICF: u8 = 1 << 5;
+
+/// Input capture using a 16-bit timer
+///
+/// The timer counts freely in normal mode.  On each selected edge of the `ICPn`
+/// pin, the counter value is latched into `ICRn`.
+pub struct InputCapture<TIMER, PIN> {
+    tim: TIMER,
+    pin: PIN,
+    prescaler: Prescaler,
+}
+
+macro_rules! capture_impl {
+    ($TIMER:ident, $port:ident, $PIN:ident) => {
+        impl<MODE> InputCapture<atmega32u4::$TIMER, port::$port::$PIN<port::mode::io::Input<MODE>>> {
+            /// Configure this timer for input capture on its `ICPn` pin
+            ///
+            /// The noise canceler is enabled, which delays captures by 4 CPU cycles.
+            /// Capturing starts once `enable()` is called.
+            pub fn new(
+                tim: atmega32u4::$TIMER,
+                pin: port::$port::$PIN<port::mode::io::Input<MODE>>,
+                edge: Edge,
+                prescaler: Prescaler,
+            ) -> Self {
+                // Normal mode, timer stopped
+                tim.tccr_a.reset();
+                tim.tccr_b.write(|w| unsafe { w.bits(ICNC) });
+
+                let mut capture = InputCapture {
+                    tim: tim,
+                    pin: pin,
+                    prescaler: prescaler,
+                };
+                capture.set_edge(edge);
+                capture
+            }
+
+            /// Select the edge which triggers a capture
+            ///
+            /// Can be changed at any time, for example to measure the high and the
+            /// low time of a signal.
+            pub fn set_edge(&mut self, edge: Edge) {
+                self.tim.tccr_b.modify(|r, w| unsafe {
+                    match edge {
+                        Edge::Rising => w.bits(r.bits() | ICES),
+                        Edge::Falling => w.bits(r.bits() & !ICES),
+                    }
+                });
+                // Changing the edge can set the flag
+                self.tim.tifr.write(|w| unsafe { w.bits(ICF) });
+            }
+
+            /// Return the latched counter value of the last capture
+            ///
+            /// Returns `WouldBlock` until an edge was captured since the last call.
+            pub fn read(&mut self) -> nb::Result<u16, Infallible> {
+                if self.tim.tifr.read().bits() & ICF == 0 {
+                    return Err(nb::Error::WouldBlock);
+                }
+
+                // ICRnL has to be read first
+                let low = self.tim.icr_l.read().bits() as u16;
+                let high = self.tim.icr_h.read().bits() as u16;
+                self.tim.tifr.write(|w| unsafe { w.bits(ICF) });
+
+                Ok((high << 8) | low)
+            }
+
+            /// Access the raw timer registers
+            ///
+            /// # Safety
+            /// The timer must keep running in normal mode, and the `ICFn` flag must only be
+            /// cleared by `read()`.
+            pub unsafe fn registers(&mut self) -> &atmega32u4::$TIMER {
+                &self.tim
+            }
+
+            /// Stop the timer and release it together with the pin
+            pub fn free(self) -> (atmega32u4::$TIMER, port::$port::$PIN<port::mode::io::Input<MODE>>) {
+                self.tim.tccr_a.reset();
+                self.tim.tccr_b.reset();
+
+                (self.tim, self.pin)
+            }
+        }
+
+        impl<MODE> hal::Capture for InputCapture<atmega32u4::$TIMER, port::$port::$PIN<port::mode::io::Input<MODE>>> {
+            type Error = Infallible;
+            type Channel = ();
+            type Time = Prescaler;
+            type Capture = u16;
+
+            fn capture(&mut self, _channel: ()) -> nb::Result<u16, Infallible> {
+                self.read()
+            }
+
+            fn disable(&mut self, _channel: ()) {
+                self.tim.tccr_b.modify(|r, w| unsafe { w.bits(r.bits() & !CS_MASK) });
+            }
+
+            fn enable(&mut self, _channel: ()) {
+                self.tim.tifr.write(|w| unsafe { w.bits(ICF) });
+                let cs = self.prescaler.bits();
+                self.tim.tccr_b.modify(|r, w| unsafe { w.bits((r.bits() & !CS_MASK) | cs) });
+            }
+
+            fn get_resolution(&self) -> Prescaler {
+                self.prescaler
+            }
+
+            fn set_resolution<R>(&mut self, resolution: R)
+            where
+                R: Into<Prescaler>,
+            {
+                self.prescaler = resolution.into();
+
+                // Only touch the clock if the timer is running
+                let cs = self.prescaler.bits();
+                self.tim.tccr_b.modify(|r, w| unsafe {
+                    if r.bits() & CS_MASK != 0 {
+                        w.bits((r.bits() & !CS_MASK) | cs)
+                    } else {
+                        w.bits(r.bits())
+                    }
+                });
+            }
+        }
+    }
+}
+
+capture_impl!(TIMER1, portd, PD4);
+capture_impl!(TIMER3, portc, PC7);
+
+// TIMSKn
+const TOIE: u8 = 1 << 0;
+const OCIEA: u8 = 1 << 1;
+// TIFRn
+const TOV: u8 = 1 << 0;
+// Smallest TOP mode 15 allows
+const PERIODIC_MIN_TOP: u16 = 3;
+
+fn periodic_top(top: u16) -> u16 {
+    cmp::max(top, PERIODIC_MIN_TOP)
+}
+
+/// Periodic timebase using a 16-bit timer
+///
+/// The timer runs in fast PWM mode with `OCRnA` as TOP (mode 15), but doesn't drive
+/// any pin.  It overflows every `prescaler * (top + 1)` CPU cycles, `top` is at least
+/// 3.
+pub struct Periodic<TIMER> {
+    tim: TIMER,
+}
+
+macro_rules! periodic_impl {
+    ($TIMER:ident, $TICKS:ident, $isr:ident) => {
+        static $TICKS: Global<u32> = Global::new();
+
+        impl Periodic<atmega32u4::$TIMER> {
+            /// Start the timer with a period of `prescaler * (top + 1)` CPU cycles
+            ///
+            /// The interrupts are not enabled yet, see `listen()`.  A `top` below 3 is
+            /// raised to 3.
+            pub fn new(tim: atmega32u4::$TIMER, prescaler: Prescaler, top: u16) -> Self {
+                $TICKS.set(0);
+
+                let mut periodic = Periodic { tim: tim };
+                periodic.set_period(prescaler, top);
+                periodic
+            }
+
+            /// Change the period
+            ///
+            /// The counter is restarted, so the current period is cut short.  Mode 15
+            /// needs a TOP of at least 3, smaller values of `top` are raised to 3.
+            pub fn set_period(&mut self, prescaler: Prescaler, top: u16) {
+                let top = periodic_top(top);
+
+                atmega32u4::interrupt::free(|_| {
+                    self.tim.tccr_b.write(|w| unsafe { w.cs().bits(0) });
+
+                    // The high byte has to be written first
+                    self.tim.ocr_a_h.write(|w| unsafe { w.bits((top >> 8) as u8) });
+                    self.tim.ocr_a_l.write(|w| unsafe { w.bits(top as u8) });
+                    self.tim.tcnt_h.write(|w| unsafe { w.bits(0) });
+                    self.tim.tcnt_l.write(|w| unsafe { w.bits(0) });
+
+                    // Fast PWM, TOP = OCRnA (WGM = 15)
+                    self.tim.tccr_a.write(|w| unsafe { w.wgm0().bits(0b11) });
+                    self.tim.tccr_b.write(|w| unsafe {
+                        w.wgm2().bits(0b11).cs().bits(prescaler.bits())
+                    });
+                });
+            }
+
+            /// Enable the overflow interrupt
+            ///
+            /// A flag left over from an earlier period is cleared first, so the
+            /// interrupt doesn't fire right away.
+            pub fn listen(&mut self) {
+                self.clear();
+                self.tim.timsk.modify(|r, w| unsafe { w.bits(r.bits() | TOIE) });
+            }
+
+            /// Disable the overflow interrupt
+            pub fn unlisten(&mut self) {
+                self.tim.timsk.modify(|r, w| unsafe { w.bits(r.bits() & !TOIE) });
+            }
+
+            /// Whether a period ended since the flag was last cleared
+            ///
+            /// With the interrupt enabled, the hardware clears the flag when the
+            /// handler runs.
+            pub fn is_pending(&self) -> bool {
+                self.tim.tifr.read().bits() & TOV != 0
+            }
+
+            /// Clear the overflow flag
+            pub fn clear(&mut self) {
+                // The flag is cleared by writing a one
+                self.tim.tifr.write(|w| unsafe { w.bits(TOV) });
+            }
+
+            /// Enable the compare match A interrupt (`TIMERn_COMPA`)
+            ///
+            /// Fires at the same time as the overflow interrupt.  Like `listen()`, a
+            /// stale flag is cleared first.
+            pub fn listen_compare(&mut self) {
+                self.clear_compare();
+                self.tim.timsk.modify(|r, w| unsafe { w.bits(r.bits() | OCIEA) });
+            }
+
+            /// Disable the compare match A interrupt
+            pub fn unlisten_compare(&mut self) {
+                self.tim.timsk.modify(|r, w| unsafe { w.bits(r.bits() & !OCIEA) });
+            }
+
+            /// Whether the compare match A flag (`OCFnA`) is set
+            ///
+            /// The flag is independent of the overflow flag, `wait()` only looks at
+            /// the latter.
+            pub fn is_compare_pending(&self) -> bool {
+                self.tim.tifr.read().bits() & OCFA != 0
+            }
+
+            /// Clear the compare match A flag
+            pub fn clear_compare(&mut self) {
+                self.tim.tifr.write(|w| unsafe { w.bits(OCFA) });
+            }
+
+            /// Wait for the end of the current period, without the interrupt
+            ///
+            /// Returns `WouldBlock` until the timer overflowed, then clears the flag.
+            pub fn wait(&mut self) -> nb::Result<(), Infallible> {
+                if self.is_pending() {
+                    self.clear();
+                    Ok(())
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            }
+
+            /// Number of periods counted by the interrupt hook
+            ///
+            /// Wraps around after `u32::MAX` periods.
+            pub fn ticks(&self) -> u32 {
+                $TICKS.get_copy().unwrap_or(0)
+            }
+
+            /// Access the raw timer registers
+            ///
+            /// # Safety
+            /// Mode and `OCRnA` define the period.  Change them using `set_period()`
+            /// only, otherwise the timer might not overflow at all.
+            pub unsafe fn registers(&mut self) -> &atmega32u4::$TIMER {
+                &self.tim
+            }
+
+            /// Stop this timer and release the raw peripheral
+            ///
+            /// Both interrupts are disabled and the timer's control registers are
+            /// reset to their power-on defaults.
+            pub fn free(mut self) -> atmega32u4::$TIMER {
+                self.unlisten();
+                self.unlisten_compare();
+                self.tim.tccr_a.reset();
+                self.tim.tccr_b.reset();
+                $TICKS.take();
+
+                self.tim
+            }
+        }
+
+        /// Overflow interrupt hook for [Periodic]
+        ///
+        /// Call this from the timer's `TIMERn_OVF` interrupt handler (or from
+        /// `TIMERn_COMPA`, with `listen_compare()`) to count the periods for
+        /// `ticks()`.
+        pub fn $isr() {
+            // Interrupts are disabled inside the handler
+            unsafe { $TICKS.get_unchecked(|t| *t = t.wrapping_add(1)) }.ok();
+        }
+    }
+}
+
+periodic_impl!(TIMER1, TIMER1_TICKS, timer1_ovf_isr);
+periodic_impl!(TIMER3, TIMER3_TICKS, timer3_ovf_isr);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polarity_com_bits() {
+        // Clear OCnx on compare match
+        assert_eq!(Polarity::Normal.com_bits(), 0b10);
+        // Set OCnx on compare match
+        assert_eq!(Polarity::Inverted.com_bits(), 0b11);
+    }
+
+    #[test]
+    fn polarity_com_bits_keep_the_pin_connected() {
+        for polarity in [Polarity::Normal, Polarity::Inverted].iter() {
+            let bits = polarity.com_bits();
+            assert_eq!(bits & !0b11, 0);
+            // COMnx1:0 = 0b00 would disconnect the pin, 0b01 toggles it
+            assert_eq!(bits & 0b10, 0b10);
+        }
+    }
+
+    #[test]
+    fn periodic_top_is_at_least_3() {
+        assert_eq!(periodic_top(0), 3);
+        assert_eq!(periodic_top(2), 3);
+        assert_eq!(periodic_top(3), 3);
+        assert_eq!(periodic_top(2499), 2499);
+        assert_eq!(periodic_top(0xffff), 0xffff);
+    }
+
+    // Ticks actually waited by `TimerDelay`'s `DelayUs<u32>`
+    fn delay_us_total(us: u32, freq: u32) -> u64 {
+        let (ms, ticks) = delay_us_ticks(us, freq);
+        ms as u64 * (freq / 8000) as u64 + ticks as u64
+    }
+
+    #[test]
+    fn delay_us_ticks_at_u32_max() {
+        for &freq in [24_000_000, 16_000_000, 8_000_000, 1_000_000].iter() {
+            let us = ::core::u32::MAX;
+            let exact = (us as u64 * (freq / 8) as u64 + 999_999) / 1_000_000;
+            assert_eq!(delay_us_total(us, freq), exact);
+        }
+    }
+
+    #[test]
+    fn delay_us_ticks_rest() {
+        // 2 ticks per microsecond at 16MHz
+        assert_eq!(delay_us_ticks(0, 16_000_000), (0, 0));
+        assert_eq!(delay_us_ticks(1, 16_000_000), (0, 2));
+        assert_eq!(delay_us_ticks(999, 16_000_000), (0, 1998));
+        assert_eq!(delay_us_ticks(1000, 16_000_000), (1, 0));
+        assert_eq!(delay_us_ticks(2_500_001, 16_000_000), (2500, 2));
+    }
+}
@@ -0,0 +1,462 @@
+//! USB Device
+//!
+//! [UsbBus] implements the `UsbBus` trait of the
+//! [`usb-device`](https://crates.io/crates/usb-device) crate for the ATmega32U4's
+//! native USB controller.  Together with class crates like
+//! [`usbd-serial`](https://crates.io/crates/usbd-serial), the device can show up as
+//! a serial port, the same way an Arduino Leonardo does.  This module is only
+//! available with the `usb-device` feature.
+//!
+//! The USB controller needs a 48MHz clock, which is generated by the PLL from the
+//! oscillator.  Only 8MHz and 16MHz oscillators are supported, selected by the `SPEED`
+//! parameter (see [delay]).  Other speeds don't implement [PllClock], so using them
+//! fails to compile instead of hanging while waiting for the PLL to lock.
+//!
+//! Control, bulk and interrupt endpoints are supported, isochronous ones are not.
+//! Endpoints 1-6 can be up to 64 bytes large and use a single bank each.
+//!
+//! # Example
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//!
+//! let bus = usb::UsbBus::<delay::MHz16>::new(dp.USB_DEVICE, dp.PLL);
+//! let allocator = usb_device::bus::UsbBusAllocator::new(bus);
+//!
+//! let mut serial = usbd_serial::SerialPort::new(&allocator);
+//! let mut device = UsbDeviceBuilder::new(&allocator, UsbVidPid(0x16c0, 0x27dd))
+//!     .device_class(usbd_serial::USB_CLASS_CDC)
+//!     .build();
+//!
+//! loop {
+//!     // Needs to be called at least every 10ms, or from the USB interrupts
+//!     if !device.poll(&mut [&mut serial]) {
+//!         continue;
+//!     }
+//!
+//!     let mut buf = [0u8; 64];
+//!     if let Ok(count) = serial.read(&mut buf) {
+//!         // Echo everything back
+//!         serial.write(&buf[..count]).ok();
+//!     }
+//! }
+//! ```
+use atmega32u4;
+use clock;
+use core::cell;
+use core::marker;
+use delay;
+use usb_device::bus::PollResult;
+use usb_device::endpoint::{EndpointAddress, EndpointType};
+use usb_device::{Result, UsbDirection, UsbError};
+
+const ENDPOINTS: usize = 7;
+// Size of the endpoint memory (DPRAM)
+const DPRAM_SIZE: u16 = 832;
+
+// UHWCON
+const UVREGE: u8 = 1 << 0;
+
+// USBCON
+const USBE: u8 = 1 << 7;
+const FRZCLK: u8 = 1 << 5;
+const OTGPADE: u8 = 1 << 4;
+
+// PLLCSR
+const PINDIV: u8 = 1 << 4;
+const PLLE: u8 = 1 << 1;
+const PLOCK: u8 = 1 << 0;
+
+// UDCON
+const DETACH: u8 = 1 << 0;
+
+// UDINT
+const WAKEUPI: u8 = 1 << 4;
+const EORSTI: u8 = 1 << 3;
+const SUSPI: u8 = 1 << 0;
+
+// UDADDR
+const ADDEN: u8 = 1 << 7;
+
+// UECONX
+const STALLRQ: u8 = 1 << 5;
+const STALLRQC: u8 = 1 << 4;
+const RSTDT: u8 = 1 << 3;
+const EPEN: u8 = 1 << 0;
+
+// UECFG0X
+const EPDIR_IN: u8 = 1 << 0;
+
+// UECFG1X
+const ALLOC: u8 = 1 << 1;
+
+// UESTA0X
+const CFGOK: u8 = 1 << 7;
+
+// UEINTX
+const FIFOCON: u8 = 1 << 7;
+const RXSTPI: u8 = 1 << 3;
+const RXOUTI: u8 = 1 << 2;
+const TXINI: u8 = 1 << 0;
+
+#[derive(Clone, Copy)]
+struct EndpointConfig {
+    ep_type: EndpointType,
+    // Only endpoint 0 is used in both directions
+    dir_in: bool,
+    size: u16,
+}
+
+impl EndpointConfig {
+    fn uecfg0x(&self) -> u8 {
+        let eptype = match self.ep_type {
+            EndpointType::Control => 0b00,
+            EndpointType::Bulk => 0b10,
+            EndpointType::Interrupt => 0b11,
+            // Rejected in alloc_ep
+            _ => 0b01,
+        };
+        if self.dir_in {
+            (eptype << 6) | EPDIR_IN
+        } else {
+            eptype << 6
+        }
+    }
+
+    fn uecfg1x(&self) -> u8 {
+        let epsize = match self.size {
+            8 => 0b000,
+            16 => 0b001,
+            32 => 0b010,
+            _ => 0b011,
+        };
+        (epsize << 4) | ALLOC
+    }
+}
+
+/// A clock speed the PLL can make 48MHz from
+///
+/// The PLL needs 8MHz at its input, a 16MHz clock is divided by two first.
+pub trait PllClock: clock::Clock {
+    /// `PINDIV` bit of `PLLCSR` for this speed
+    const PINDIV: u8;
+}
+
+impl PllClock for delay::MHz16 {
+    const PINDIV: u8 = PINDIV;
+}
+
+impl PllClock for delay::MHz8 {
+    const PINDIV: u8 = 0;
+}
+
+/// `usb-device` bus implementation
+///
+/// If `SPEED` is omitted, the speed selected using the `mhz*` features is used.
+pub struct UsbBus<SPEED = delay::Speed> {
+    usb: atmega32u4::USB_DEVICE,
+    pll: atmega32u4::PLL,
+    endpoints: [Option<EndpointConfig>; ENDPOINTS],
+    allocated: u16,
+    // IN endpoints with a packet in flight, bit n is endpoint n
+    pending_in: cell::Cell<u8>,
+    _speed: marker::PhantomData<SPEED>,
+}
+
+// The bus is only ever accessed from a single core.  `usb-device` requires `Sync`
+// so the allocator can be shared between the device and its classes.
+unsafe impl<SPEED> Sync for UsbBus<SPEED> {}
+
+impl<SPEED: PllClock> UsbBus<SPEED> {
+    /// Create the bus from the USB controller and the PLL
+    ///
+    /// The controller is enabled once `usb-device` calls `enable()`, which happens
+    /// when building the `UsbDevice`.
+    pub fn new(usb: atmega32u4::USB_DEVICE, pll: atmega32u4::PLL) -> UsbBus<SPEED> {
+        UsbBus {
+            usb: usb,
+            pll: pll,
+            endpoints: [None; ENDPOINTS],
+            allocated: 0,
+            pending_in: cell::Cell::new(0),
+            _speed: marker::PhantomData,
+        }
+    }
+}
+
+impl<SPEED> UsbBus<SPEED> {
+    /// Select an endpoint for the `UEXXX` registers
+    fn select(&self, index: usize) {
+        self.usb.uenum.write(|w| unsafe { w.bits(index as u8) });
+    }
+
+    fn clear_ueintx(&self, mask: u8) {
+        // Flags are cleared by writing a zero, writing a one has no effect
+        self.usb.ueintx.write(|w| unsafe { w.bits(!mask) });
+    }
+
+    fn configure_endpoint(&self, index: usize, config: &EndpointConfig) -> Result<()> {
+        self.select(index);
+        self.usb.ueconx.write(|w| unsafe { w.bits(EPEN) });
+        self.usb.uecfg0x.write(|w| unsafe { w.bits(config.uecfg0x()) });
+        self.usb.uecfg1x.write(|w| unsafe { w.bits(config.uecfg1x()) });
+
+        if self.usb.uesta0x.read().bits() & CFGOK == 0 {
+            Err(UsbError::EndpointMemoryOverflow)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<SPEED: PllClock> ::usb_device::bus::UsbBus for UsbBus<SPEED> {
+    fn alloc_ep(
+        &mut self,
+        ep_dir: UsbDirection,
+        ep_addr: Option<EndpointAddress>,
+        ep_type: EndpointType,
+        max_packet_size: u16,
+        _interval: u8,
+    ) -> Result<EndpointAddress> {
+        match ep_type {
+            EndpointType::Control | EndpointType::Bulk | EndpointType::Interrupt => (),
+            _ => return Err(UsbError::Unsupported),
+        }
+
+        let size = match max_packet_size {
+            0..=8 => 8,
+            9..=16 => 16,
+            17..=32 => 32,
+            33..=64 => 64,
+            _ => return Err(UsbError::EndpointMemoryOverflow),
+        };
+
+        let dir_in = ep_dir == UsbDirection::In;
+        let index = match ep_addr {
+            Some(addr) => addr.index(),
+            None => match (1..ENDPOINTS).find(|&i| self.endpoints[i].is_none()) {
+                Some(i) => i,
+                None => return Err(UsbError::EndpointOverflow),
+            },
+        };
+        if index >= ENDPOINTS {
+            return Err(UsbError::InvalidEndpoint);
+        }
+
+        match self.endpoints[index] {
+            // Endpoint 0 is bidirectional and allocated once for each direction
+            Some(_) if index == 0 => (),
+            Some(_) => return Err(UsbError::InvalidEndpoint),
+            None => {
+                if self.allocated + size > DPRAM_SIZE {
+                    return Err(UsbError::EndpointMemoryOverflow);
+                }
+                self.allocated += size;
+                self.endpoints[index] = Some(EndpointConfig {
+                    ep_type: ep_type,
+                    dir_in: dir_in && index != 0,
+                    size: size,
+                });
+            }
+        }
+
+        Ok(EndpointAddress::from_parts(index, ep_dir))
+    }
+
+    fn enable(&mut self) {
+        atmega32u4::interrupt::free(|_| {
+            self.usb.uhwcon.write(|w| unsafe { w.bits(UVREGE) });
+            self.usb
+                .usbcon
+                .write(|w| unsafe { w.bits(USBE | FRZCLK | OTGPADE) });
+
+            self.pll
+                .pllcsr
+                .write(|w| unsafe { w.bits(SPEED::PINDIV | PLLE) });
+            while self.pll.pllcsr.read().bits() & PLOCK == 0 {}
+
+            self.usb.usbcon.write(|w| unsafe { w.bits(USBE | OTGPADE) });
+
+            // Attach to the bus
+            self.usb
+                .udcon
+                .modify(|r, w| unsafe { w.bits(r.bits() & !DETACH) });
+        });
+    }
+
+    fn reset(&self) {
+        atmega32u4::interrupt::free(|_| {
+            for (index, config) in self.endpoints.iter().enumerate() {
+                if let Some(ref config) = *config {
+                    // Was checked against the memory size when allocating
+                    self.configure_endpoint(index, config).ok();
+                }
+            }
+            self.pending_in.set(0);
+        });
+    }
+
+    fn set_device_address(&self, addr: u8) {
+        // The address has to be written before setting ADDEN
+        self.usb.udaddr.write(|w| unsafe { w.bits(addr & 0x7f) });
+        self.usb
+            .udaddr
+            .write(|w| unsafe { w.bits((addr & 0x7f) | ADDEN) });
+    }
+
+    fn write(&self, ep_addr: EndpointAddress, buf: &[u8]) -> Result<usize> {
+        let index = ep_addr.index();
+        let config = match self.endpoints.get(index) {
+            Some(&Some(config)) => config,
+            _ => return Err(UsbError::InvalidEndpoint),
+        };
+        if buf.len() > config.size as usize {
+            return Err(UsbError::BufferOverflow);
+        }
+
+        atmega32u4::interrupt::free(|_| {
+            self.select(index);
+
+            // TXINI is set when the bank is free for a new packet
+            if self.usb.ueintx.read().bits() & TXINI == 0 {
+                return Err(UsbError::WouldBlock);
+            }
+
+            for &byte in buf {
+                self.usb.uedatx.write(|w| unsafe { w.bits(byte) });
+            }
+
+            // Non-control endpoints additionally need FIFOCON cleared to send the bank
+            self.clear_ueintx(TXINI);
+            if index != 0 {
+                self.clear_ueintx(FIFOCON);
+            }
+            self.pending_in.set(self.pending_in.get() | (1 << index));
+
+            Ok(buf.len())
+        })
+    }
+
+    fn read(&self, ep_addr: EndpointAddress, buf: &mut [u8]) -> Result<usize> {
+        let index = ep_addr.index();
+        match self.endpoints.get(index) {
+            Some(&Some(_)) => (),
+            _ => return Err(UsbError::InvalidEndpoint),
+        }
+
+        atmega32u4::interrupt::free(|_| {
+            self.select(index);
+
+            let flags = self.usb.ueintx.read().bits();
+            let flag = if flags & RXSTPI != 0 {
+                RXSTPI
+            } else if flags & RXOUTI != 0 {
+                RXOUTI
+            } else {
+                return Err(UsbError::WouldBlock);
+            };
+
+            let count = self.usb.uebclx.read().bits() as usize;
+            if count > buf.len() {
+                return Err(UsbError::BufferOverflow);
+            }
+            for byte in buf[..count].iter_mut() {
+                *byte = self.usb.uedatx.read().bits();
+            }
+
+            // Non-control endpoints additionally need FIFOCON cleared to free the bank
+            self.clear_ueintx(flag);
+            if index != 0 {
+                self.clear_ueintx(FIFOCON);
+            }
+
+            Ok(count)
+        })
+    }
+
+    fn set_stalled(&self, ep_addr: EndpointAddress, stalled: bool) {
+        atmega32u4::interrupt::free(|_| {
+            self.select(ep_addr.index());
+            self.usb.ueconx.modify(|r, w| unsafe {
+                if stalled {
+                    w.bits(r.bits() | STALLRQ)
+                } else {
+                    w.bits(r.bits() | STALLRQC | RSTDT)
+                }
+            });
+        });
+    }
+
+    fn is_stalled(&self, ep_addr: EndpointAddress) -> bool {
+        atmega32u4::interrupt::free(|_| {
+            self.select(ep_addr.index());
+            self.usb.ueconx.read().bits() & STALLRQ != 0
+        })
+    }
+
+    fn suspend(&self) {
+        self.usb
+            .usbcon
+            .modify(|r, w| unsafe { w.bits(r.bits() | FRZCLK) });
+    }
+
+    fn resume(&self) {
+        self.usb
+            .usbcon
+            .modify(|r, w| unsafe { w.bits(r.bits() & !FRZCLK) });
+    }
+
+    fn poll(&self) -> PollResult {
+        atmega32u4::interrupt::free(|_| {
+            let udint = self.usb.udint.read().bits();
+
+            // Flags are cleared by writing a zero
+            if udint & EORSTI != 0 {
+                self.usb.udint.write(|w| unsafe { w.bits(!EORSTI) });
+                return PollResult::Reset;
+            }
+            if udint & SUSPI != 0 {
+                self.usb.udint.write(|w| unsafe { w.bits(!SUSPI) });
+                return PollResult::Suspend;
+            }
+            if udint & WAKEUPI != 0 {
+                self.usb.udint.write(|w| unsafe { w.bits(!WAKEUPI) });
+                return PollResult::Resume;
+            }
+
+            let mut ep_out = 0;
+            let mut ep_in_complete = 0;
+            let mut ep_setup = 0;
+            let mut pending_in = self.pending_in.get();
+
+            for (index, config) in self.endpoints.iter().enumerate() {
+                if config.is_none() {
+                    continue;
+                }
+                self.select(index);
+                let flags = self.usb.ueintx.read().bits();
+
+                if flags & RXSTPI != 0 {
+                    ep_setup |= 1 << index;
+                }
+                if flags & RXOUTI != 0 {
+                    ep_out |= 1 << index;
+                }
+                if flags & TXINI != 0 && pending_in & (1 << index) != 0 {
+                    ep_in_complete |= 1 << index;
+                    pending_in &= !(1 << index);
+                }
+            }
+            self.pending_in.set(pending_in);
+
+            if ep_out | ep_in_complete | ep_setup == 0 {
+                PollResult::None
+            } else {
+                PollResult::Data {
+                    ep_out: ep_out,
+                    ep_in_complete: ep_in_complete,
+                    ep_setup: ep_setup,
+                }
+            }
+        })
+    }
+}
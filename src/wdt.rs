@@ -0,0 +1,382 @@
+//! Watchdog Timer
+//!
+//! The watchdog resets the device if it isn't fed regularly.  [Wdt] implements the
+//! `embedded-hal` watchdog traits [embedded_hal::watchdog::WatchdogEnable],
+//! [embedded_hal::watchdog::Watchdog] and [embedded_hal::watchdog::WatchdogDisable].
+//!
+//! Changing the watchdog configuration requires a timed sequence: The new value has
+//! to be written within 4 cycles after setting `WDCE`.  This is done in assembly
+//! with interrupts disabled so the compiler can't reorder or stretch it.
+//!
+//! # Example
+//! ```
+//! let dp = atmega32u4::Peripherals::take().unwrap();
+//!
+//! let mut wdt = atmega32u4_hal::wdt::Wdt::new(dp.WDT);
+//!
+//! if wdt.is_watchdog_reset() {
+//!     // The last reset was caused by the watchdog
+//!     wdt.clear_watchdog_reset();
+//! }
+//!
+//! wdt.start(wdt::Timeout::Ms500);
+//!
+//! loop {
+//!     // Do work ...
+//!
+//!     wdt.feed();
+//! }
+//! ```
+//!
+//! # Interrupt Mode
+//! Instead of resetting, the watchdog can fire the `WDT` interrupt on each timeout
+//! using `start_interrupt()`.  It keeps running while the CPU is in power-down sleep,
+//! so it can wake the device periodically, for example every 8s.  The interrupt
+//! handler has to call [wdt_isr]:
+//!
+//! ```
+//! fn main() {
+//!     let dp = atmega32u4::Peripherals::take().unwrap();
+//!     let mut wdt = wdt::Wdt::new(dp.WDT);
+//!     wdt.start_interrupt(wdt::Timeout::S8, false);
+//!     unsafe { atmega32u4::interrupt::enable() };
+//!
+//!     loop {
+//!         // Sleep until the watchdog fires ...
+//!         if wdt.has_fired() {
+//!             // Do work
+//!         }
+//!     }
+//! }
+//!
+//! interrupt!(WDT, wdt_isr);
+//! fn wdt_isr() {
+//!     atmega32u4_hal::wdt::wdt_isr();
+//! }
+//! ```
+//!
+//! In plain interrupt mode the interrupt fires periodically until the watchdog is
+//! disabled.  With `reset` set to `true`, the first timeout fires the interrupt and
+//! the hardware clears `WDIE`, so the next timeout resets the device.  This is
+//! one-shot: To keep the device running, re-arm the interrupt using
+//! [rearm_interrupt] (or feed the watchdog) before the next timeout.  This gives the
+//! application a chance to save state before a watchdog reset.
+//!
+//! # Software Reset
+//! [reset_mcu] resets the device by letting the watchdog expire, for example to enter
+//! the bootloader.  Because the watchdog stays enabled after a watchdog reset, call
+//! [disable_after_reset] at the very start of `main`:
+//!
+//! ```
+//! fn main() {
+//!     atmega32u4_hal::wdt::disable_after_reset();
+//!
+//!     // ...
+//!
+//!     atmega32u4_hal::wdt::reset_mcu();
+//! }
+//! ```
+use atmega32u4;
+use global::Global;
+use hal;
+
+const WDIE: u8 = 1 << 6;
+const WDCE: u8 = 1 << 4;
+const WDE: u8 = 1 << 3;
+
+const WDRF: u8 = 1 << 3;
+
+/// Watchdog timeout
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timeout {
+    /// 16 milliseconds
+    Ms16,
+    /// 32 milliseconds
+    Ms32,
+    /// 64 milliseconds
+    Ms64,
+    /// 125 milliseconds
+    Ms125,
+    /// 250 milliseconds
+    Ms250,
+    /// 500 milliseconds
+    Ms500,
+    /// 1 second
+    S1,
+    /// 2 seconds
+    S2,
+    /// 4 seconds
+    S4,
+    /// 8 seconds
+    S8,
+}
+
+impl Timeout {
+    /// `WDP3:0` bits for this timeout
+    fn bits(self) -> u8 {
+        let wdp = match self {
+            Timeout::Ms16 => 0,
+            Timeout::Ms32 => 1,
+            Timeout::Ms64 => 2,
+            Timeout::Ms125 => 3,
+            Timeout::Ms250 => 4,
+            Timeout::Ms500 => 5,
+            Timeout::S1 => 6,
+            Timeout::S2 => 7,
+            Timeout::S4 => 8,
+            Timeout::S8 => 9,
+        };
+
+        // WDP3 is not next to WDP2:0
+        ((wdp & 0b1000) << 2) | (wdp & 0b0111)
+    }
+}
+
+/// Watchdog Timer
+pub struct Wdt {
+    _wdt: atmega32u4::WDT,
+}
+
+impl Wdt {
+    /// Wrap the watchdog peripheral
+    ///
+    /// This does not change the watchdog configuration.
+    pub fn new(wdt: atmega32u4::WDT) -> Wdt {
+        Wdt { _wdt: wdt }
+    }
+
+    /// Access the raw `WDT` registers
+    ///
+    /// # Safety
+    /// Changing the prescaler or disabling the watchdog requires the timed `WDCE`
+    /// sequence with interrupts disabled, otherwise the write is ignored.
+    pub unsafe fn registers(&mut self) -> &atmega32u4::WDT {
+        &self._wdt
+    }
+
+    /// Whether the last reset was caused by the watchdog (`WDRF`)
+    pub fn is_watchdog_reset(&self) -> bool {
+        unsafe { (*atmega32u4::CPU::ptr()).mcusr.read().bits() & WDRF != 0 }
+    }
+
+    /// Clear the watchdog reset flag (`WDRF`)
+    ///
+    /// *Note*: As long as `WDRF` is set, the watchdog can't be disabled.
+    pub fn clear_watchdog_reset(&mut self) {
+        unsafe {
+            (*atmega32u4::CPU::ptr())
+                .mcusr
+                .modify(|r, w| w.bits(r.bits() & !WDRF))
+        }
+    }
+}
+
+impl Wdt {
+    /// Start the watchdog in interrupt mode
+    ///
+    /// The `WDT` interrupt fires on each timeout.  If `reset` is `true`, the device
+    /// is reset on the timeout after the interrupt, unless [rearm_interrupt] is called
+    /// in between.
+    pub fn start_interrupt(&mut self, timeout: Timeout, reset: bool) {
+        let mut value = WDIE | timeout.bits();
+        if reset {
+            value |= WDE;
+        }
+
+        atmega32u4::interrupt::free(|_| {
+            wdr();
+            FIRED.set(false);
+            // WDRF overrides WDE, so it needs to be cleared for pure interrupt mode
+            self.clear_watchdog_reset();
+            timed_write(value);
+        })
+    }
+
+    /// Whether the watchdog interrupt fired since the last call
+    pub fn has_fired(&mut self) -> bool {
+        FIRED.replace(false).unwrap_or(false)
+    }
+}
+
+impl hal::watchdog::WatchdogEnable for Wdt {
+    type Time = Timeout;
+
+    fn start<T: Into<Timeout>>(&mut self, period: T) {
+        let value = WDE | period.into().bits();
+
+        atmega32u4::interrupt::free(|_| {
+            wdr();
+            timed_write(value);
+        })
+    }
+}
+
+impl hal::watchdog::Watchdog for Wdt {
+    fn feed(&mut self) {
+        wdr();
+    }
+}
+
+impl hal::watchdog::WatchdogDisable for Wdt {
+    fn disable(&mut self) {
+        atmega32u4::interrupt::free(|_| {
+            wdr();
+            // WDRF overrides WDE, so it needs to be cleared first
+            self.clear_watchdog_reset();
+            timed_write(0);
+        })
+    }
+}
+
+static FIRED: Global<bool> = Global::new();
+
+/// `WDT` interrupt hook for interrupt mode
+///
+/// Call this from your `WDT` interrupt handler.  It records the timeout for
+/// `Wdt::has_fired()`.
+pub fn wdt_isr() {
+    FIRED.set(true);
+}
+
+/// Re-enable the watchdog interrupt in interrupt and reset mode
+///
+/// The hardware clears `WDIE` when the interrupt fires, after which the next timeout
+/// resets the device.  This sets it again.  Setting `WDIE` doesn't need the timed
+/// sequence, so it is safe to call from the interrupt handler.
+pub fn rearm_interrupt() {
+    atmega32u4::interrupt::free(|_| unsafe {
+        (*atmega32u4::WDT::ptr())
+            .wdtcsr
+            .modify(|r, w| w.bits(r.bits() | WDIE))
+    })
+}
+
+/// Reset the microcontroller
+///
+/// Starts the watchdog with the shortest timeout (16ms) and waits for it to expire.
+/// Interrupts are disabled, so nothing can feed the watchdog in the meantime.
+///
+/// After the reset, the watchdog is still running with the 16ms timeout because
+/// `WDRF` is set.  Call [disable_after_reset] early in `main` if the application
+/// doesn't feed the watchdog.
+pub fn reset_mcu() -> ! {
+    atmega32u4::interrupt::disable();
+    wdr();
+    timed_write(WDE | Timeout::Ms16.bits());
+
+    loop {}
+}
+
+/// Disable the watchdog after a watchdog reset
+///
+/// After a watchdog reset, the watchdog stays enabled with its shortest timeout until
+/// `WDRF` is cleared.  This clears `WDRF` and disables the watchdog, without needing
+/// the `WDT` peripheral.  It should be called as early as possible, before anything
+/// that takes longer than 16ms.
+///
+/// *Note*: This also clears `WDRF`, so call [reset::reset_cause] before it if you need
+/// to know whether the watchdog caused the reset.
+pub fn disable_after_reset() {
+    atmega32u4::interrupt::free(|_| {
+        wdr();
+        unsafe {
+            (*atmega32u4::CPU::ptr())
+                .mcusr
+                .modify(|r, w| w.bits(r.bits() & !WDRF))
+        }
+        timed_write(0);
+    })
+}
+
+/// The two values written to `WDTCSR` by the timed sequence
+///
+/// `WDCE` has to be cleared in the second write, or it would start another sequence.
+fn timed_sequence(value: u8) -> (u8, u8) {
+    (WDCE | WDE, value & !WDCE)
+}
+
+// WDTCSR is not in IO space (0x60), so `sts` is needed.  Both values are loaded
+// into registers beforehand, leaving exactly two 2-cycle instructions.
+#[cfg(target_arch = "avr")]
+fn timed_write(value: u8) {
+    let (enable, value) = timed_sequence(value);
+    unsafe {
+        asm!("sts 0x60, $0\n\tsts 0x60, $1"
+             :
+             : "r"(enable), "r"(value)
+             : "memory"
+             : "volatile"
+             );
+    }
+}
+
+#[cfg(target_arch = "avr")]
+fn wdr() {
+    unsafe {
+        asm!("wdr" :::: "volatile");
+    }
+}
+
+#[cfg(not(any(target_arch = "avr", feature = "docs")))]
+fn timed_write(_value: u8) {
+    sorry!(This library is made for avr and cannot be compiled for anything else!)
+}
+
+#[cfg(not(any(target_arch = "avr", feature = "docs")))]
+fn wdr() {
+    sorry!(This library is made for avr and cannot be compiled for anything else!)
+}
+
+#[cfg(feature = "docs")]
+fn timed_write(_value: u8) {
+    // Empty implementation when building documentation
+    unimplemented!("This library is made for avr and cannot be used for anything else!")
+}
+
+#[cfg(feature = "docs")]
+fn wdr() {
+    // Empty implementation when building documentation
+    unimplemented!("This library is made for avr and cannot be used for anything else!")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_bits() {
+        assert_eq!(Timeout::Ms16.bits(), 0b00_0000);
+        assert_eq!(Timeout::Ms500.bits(), 0b00_0101);
+        assert_eq!(Timeout::S2.bits(), 0b00_0111);
+        // WDP3 is bit 5
+        assert_eq!(Timeout::S4.bits(), 0b10_0000);
+        assert_eq!(Timeout::S8.bits(), 0b10_0001);
+    }
+
+    #[test]
+    fn timeout_bits_leave_control_bits_alone() {
+        let all = [
+            Timeout::Ms16, Timeout::Ms32, Timeout::Ms64, Timeout::Ms125, Timeout::Ms250,
+            Timeout::Ms500, Timeout::S1, Timeout::S2, Timeout::S4, Timeout::S8,
+        ];
+        for timeout in all.iter() {
+            assert_eq!(timeout.bits() & (WDIE | WDCE | WDE), 0);
+        }
+    }
+
+    #[test]
+    fn timed_sequence_enables_change_first() {
+        let (enable, value) = timed_sequence(WDE | Timeout::S1.bits());
+        assert_eq!(enable, WDCE | WDE);
+        assert_eq!(value, WDE | Timeout::S1.bits());
+    }
+
+    #[test]
+    fn timed_sequence_clears_wdce() {
+        let (_, value) = timed_sequence(WDCE | WDIE);
+        assert_eq!(value, WDIE);
+
+        // Disabling writes 0 after the enable
+        assert_eq!(timed_sequence(0), (WDCE | WDE, 0));
+    }
+}